@@ -5,20 +5,54 @@ mod types;
 mod views;
 
 use std::collections::HashMap;
-use std::panic;
-use std::sync::mpsc;
-use std::thread;
-use std::time::Duration;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use chrono::Utc;
+use chrono::{DateTime, Local, NaiveDateTime, NaiveTime, Utc};
+use iced::futures::StreamExt;
 use iced::widget::{column, pane_grid};
 use iced::{time, Element, Length, Subscription, Task, Theme};
-
-use crate::config::{AppConfig, MqttProtocol, Subscription as MqttSubscription};
-use crate::mqtt::{ConnectionStatus, MqttMessage, TopicTree};
+use tokio::sync::mpsc;
+
+use crate::config::{
+    AppConfig, ConnectionConfig, CredentialKind, CredentialSettings, EmbeddedBrokerSettings,
+    Endpoint, FailoverSettings, HttpHeader, JwtAlgorithm, JwtSettings, LastWill, MqttProtocol,
+    MqttVersion, ProxyKind, ProxySettings, ReconnectSettings, RetainHandling, SasSettings,
+    SessionSettings, SshAuth, SshTunnelSettings, Subscription as MqttSubscription, TlsSettings,
+    V5Settings, WebSocketSettings, DEFAULT_JWT_CLAIMS,
+};
+use crate::mqtt::bridge::{
+    filter_covers, topic_matches, BridgeQos, BridgeRetain, BridgeRule, Echoes,
+};
+use crate::mqtt::snapshot::SnapshotMessage;
+use crate::mqtt::{
+    embedded_broker, BrokerInfo, ConnectionStatus, MqttMessage, RetainedSnapshot, TopicTree,
+    TrafficStats,
+};
 use crate::theme;
 
-pub use types::{ConnectionState, MqttCommand, MqttEvent, Pane, View};
+pub use types::{
+    ActiveSubscription, Bridge, ConnectionChoice, ConnectionState, DeliveryStatus, MqttCommand,
+    MqttEvent, Pane, PublishJob, PublishRecord, PublishRequest, RetainedClear, RetainedCopy,
+    RpcCall, RpcReply, StageResult, SubscriptionStatus, TestStage, TransferStatus, View,
+};
+
+/// Publishes kept per connection for the delivery list
+const MAX_PUBLISH_RECORDS: usize = 20;
+
+/// Seconds without a new retained message before a snapshot is complete
+const DEFAULT_SNAPSHOT_QUIET_SECS: u64 = 3;
+
+/// Payload field for the correlation id of MQTT 3.1.1 requests
+const DEFAULT_CORRELATION_FIELD: &str = "correlation_id";
+
+/// How long an RPC request waits for its reply before it has failed
+const RPC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Shortest interval between publishes of a repeating job
+const MIN_JOB_INTERVAL_MS: u64 = 10;
 
 #[derive(Debug, Clone)]
 #[allow(dead_code, clippy::enum_variant_names)]
@@ -38,44 +72,188 @@ pub enum Message {
     FormClientIdChanged(String),
     FormUsernameChanged(String),
     FormPasswordChanged(String),
+    FormCredentialKindChanged(CredentialKind),
+    FormJwtAlgorithmChanged(JwtAlgorithm),
+    FormJwtKeyFileChanged(String),
+    FormJwtClaimsChanged(String),
+    FormJwtLifetimeChanged(String),
+    FormSasKeyChanged(String),
+    FormSasKeyNameChanged(String),
+    FormSasResourceChanged(String),
+    FormSasLifetimeChanged(String),
     FormProtocolChanged(MqttProtocol),
+    FormVersionChanged(MqttVersion),
+    FormV5SessionExpiryChanged(String),
+    FormV5ReceiveMaximumChanged(String),
+    FormV5TopicAliasMaxChanged(String),
+    FormTlsSystemRootsChanged(bool),
+    FormTlsCaFileChanged(String),
+    FormTlsClientCertChanged(String),
+    FormTlsClientKeyChanged(String),
+    FormTlsServerNameChanged(String),
+    FormTlsInsecureChanged(bool),
+    FormWsPathChanged(String),
+    FormAddWsHeader,
+    FormRemoveWsHeader(usize),
+    FormWsHeaderNameChanged(usize, String),
+    FormWsHeaderValueChanged(usize, String),
+    FormCleanSessionChanged(bool),
+    FormKeepAliveChanged(String),
+    FormMaxIncomingPacketChanged(String),
+    FormMaxOutgoingPacketChanged(String),
+    FormMaxInflightChanged(String),
+    FormMessageBufferChanged(String),
+    FormWillEnabledChanged(bool),
+    FormWillTopicChanged(String),
+    FormWillPayloadChanged(String),
+    FormWillQosChanged(u8),
+    FormWillRetainChanged(bool),
+    FormWillDelayChanged(String),
+    FormWillExpiryChanged(String),
+    FormWillContentTypeChanged(String),
+    FormWillResponseTopicChanged(String),
+    FormAddWillUserProperty,
+    FormRemoveWillUserProperty(usize),
+    FormWillUserPropertyNameChanged(usize, String),
+    FormWillUserPropertyValueChanged(usize, String),
+    FormReconnectEnabledChanged(bool),
+    FormReconnectInitialBackoffChanged(String),
+    FormReconnectMaxBackoffChanged(String),
+    FormReconnectJitterChanged(String),
+    FormReconnectMaxAttemptsChanged(String),
+    FormAddFailoverEndpoint,
+    FormRemoveFailoverEndpoint(usize),
+    FormFailoverHostChanged(usize, String),
+    FormFailoverPortChanged(usize, String),
+    FormFailoverRoundRobinChanged(bool),
+    FormProxyKindChanged(ProxyKind),
+    FormProxyHostChanged(String),
+    FormProxyPortChanged(String),
+    FormProxyUsernameChanged(String),
+    FormProxyPasswordChanged(String),
+    FormSshEnabledChanged(bool),
+    FormSshHostChanged(String),
+    FormSshPortChanged(String),
+    FormSshUserChanged(String),
+    FormSshAuthChanged(SshAuth),
+    FormSshKeyFileChanged(String),
+    FormSshKeyPassphraseChanged(String),
+    FormSshRemoteHostChanged(String),
+    FormSshRemotePortChanged(String),
     FormAddSubscription,
     FormRemoveSubscription(usize),
     FormSubscriptionTopicChanged(usize, String),
     FormSubscriptionQosChanged(usize, u8),
+    FormSubscriptionShareGroupChanged(usize, String),
+    FormSubscriptionNoLocalChanged(usize, bool),
+    FormSubscriptionRetainAsPublishedChanged(usize, bool),
+    FormSubscriptionRetainHandlingChanged(usize, RetainHandling),
+    FormSubscriptionIdentifierChanged(usize, String),
+    FormSubscribeSysChanged(bool),
     FormSaveConnection,
     FormConnectAndSave,
+    FormTestConnection,
+    ConnectionTestProgress(u64, TestStage, StageResult),
     FormCancel,
 
     // Connection actions
     Connect(String),
     Disconnect(String),
     DeleteConnection(String),
+    /// Accept the SSH host key the connection stopped at and reconnect
+    TrustHostKey(String),
 
     // MQTT events
     MqttConnected(String),
     MqttDisconnected(String),
     MqttMessage(String, MqttMessage),
     MqttError(String, String),
+    MqttEvent(String, MqttEvent),
 
     // Topic tree
     SelectTopic(String, String),
     ExpandTopic(String, String),
     CollapseTopic(String, String),
     ClearTopics(String),
+    ClearRetained(String, String),
+    ConfirmClearRetained(String),
+    CancelClearRetained(String),
+    CopyRetained(String, String),
+    CopyTargetChanged(String, ConnectionChoice),
+    CopyPrefixChanged(String, String),
+    ConfirmCopyRetained(String),
+    CancelCopyRetained(String),
+    CopyProgress(String, TransferStatus),
+
+    // Subscription manager
+    SubscribeTopicChanged(String),
+    SubscribeQosChanged(u8),
+    Subscribe,
+    Unsubscribe(String, String),
+    SaveSubscription(String, String),
 
     // Publish
     PublishTopicChanged(String),
     PublishPayloadChanged(String),
     PublishQosChanged(u8),
     PublishRetainChanged(bool),
+    PublishRpcChanged(bool),
+    PublishReplyTopicChanged(String),
+    PublishCorrelationFieldChanged(String),
+    PublishRepeatChanged(bool),
+    PublishIntervalChanged(String),
+    PublishTimesChanged(String),
+    PublishStartAtChanged(String),
     SendMessage,
+    StartPublishJob,
+    PublishJobTick(String, u64),
+    PausePublishJob(String, u64),
+    ResumePublishJob(String, u64),
+    CancelPublishJob(String, u64),
+
+    // Retained snapshots
+    ShowSnapshots,
+    SnapshotSourceChanged(ConnectionChoice),
+    SnapshotFilterChanged(String),
+    SnapshotQuietChanged(String),
+    SnapshotFileChanged(String),
+    TakeSnapshot,
+    SnapshotProgress(TransferStatus),
+    RestoreFileChanged(String),
+    RestoreTargetChanged(ConnectionChoice),
+    PreviewRestore,
+    RestoreSnapshot,
+    RestoreProgress(TransferStatus),
+
+    // Embedded broker
+    LocalBrokerPortChanged(String),
+    LocalBrokerUsernameChanged(String),
+    LocalBrokerPasswordChanged(String),
+    LocalBrokerListenAllChanged(bool),
+    StartLocalBroker,
+    LocalBrokerStarted(EmbeddedBrokerSettings, Result<SocketAddr, String>),
+
+    // Bridges
+    ShowBridges,
+    BridgeSourceChanged(ConnectionChoice),
+    BridgeTargetChanged(ConnectionChoice),
+    BridgeFilterChanged(String),
+    BridgeRewriteFromChanged(String),
+    BridgeRewriteToChanged(String),
+    BridgeQosChanged(BridgeQos),
+    BridgeRetainChanged(BridgeRetain),
+    AddBridge,
+    StartBridge(u64),
+    StopBridge(u64),
+    RemoveBridge(u64),
 
     // Pane resizing
     PaneResized(pane_grid::ResizeEvent),
 
-    // Tick for polling
+    // Tick for rebuilding tree caches
     Tick,
+    RefreshStats,
+    ExpireRpcCalls,
 }
 
 pub struct MqttUi {
@@ -89,8 +267,70 @@ pub struct MqttUi {
     pub form_client_id: String,
     pub form_username: String,
     pub form_password: String,
+    pub form_credential_kind: CredentialKind,
+    pub form_jwt_algorithm: JwtAlgorithm,
+    pub form_jwt_key_file: String,
+    pub form_jwt_claims: String,
+    pub form_jwt_lifetime: String,
+    pub form_sas_key: String,
+    pub form_sas_key_name: String,
+    pub form_sas_resource: String,
+    pub form_sas_lifetime: String,
     pub form_protocol: MqttProtocol,
-    pub form_subscriptions: Vec<(String, u8)>,
+    pub form_version: MqttVersion,
+    pub form_v5_session_expiry: String,
+    pub form_v5_receive_maximum: String,
+    pub form_v5_topic_alias_max: String,
+    pub form_tls_use_system_roots: bool,
+    pub form_tls_ca_file: String,
+    pub form_tls_client_cert: String,
+    pub form_tls_client_key: String,
+    pub form_tls_server_name: String,
+    pub form_tls_insecure: bool,
+    pub form_ws_path: String,
+    pub form_ws_headers: Vec<(String, String)>,
+    pub form_clean_session: bool,
+    pub form_keep_alive: String,
+    pub form_max_incoming_packet: String,
+    pub form_max_outgoing_packet: String,
+    pub form_max_inflight: String,
+    pub form_message_buffer: String,
+    pub form_will_enabled: bool,
+    pub form_will_topic: String,
+    pub form_will_payload: String,
+    pub form_will_qos: u8,
+    pub form_will_retain: bool,
+    pub form_will_delay: String,
+    pub form_will_expiry: String,
+    pub form_will_content_type: String,
+    pub form_will_response_topic: String,
+    pub form_will_user_properties: Vec<(String, String)>,
+    pub form_reconnect_enabled: bool,
+    pub form_reconnect_initial_backoff: String,
+    pub form_reconnect_max_backoff: String,
+    pub form_reconnect_jitter: String,
+    pub form_reconnect_max_attempts: String,
+    /// Failover nodes as (host, port) text
+    pub form_failover_endpoints: Vec<(String, String)>,
+    pub form_failover_round_robin: bool,
+    pub form_proxy_kind: ProxyKind,
+    pub form_proxy_host: String,
+    pub form_proxy_port: String,
+    pub form_proxy_username: String,
+    pub form_proxy_password: String,
+    pub form_ssh_enabled: bool,
+    pub form_ssh_host: String,
+    pub form_ssh_port: String,
+    pub form_ssh_user: String,
+    pub form_ssh_auth: SshAuth,
+    pub form_ssh_key_file: String,
+    pub form_ssh_key_passphrase: String,
+    pub form_ssh_remote_host: String,
+    pub form_ssh_remote_port: String,
+    pub form_subscriptions: Vec<MqttSubscription>,
+    pub form_subscribe_sys: bool,
+    /// Stages of the last connection test run from the form
+    pub connection_test: Vec<(TestStage, StageResult)>,
 
     // Active connections
     pub connections: HashMap<String, ConnectionState>,
@@ -103,26 +343,83 @@ pub struct MqttUi {
     // Selected topics and messages
     pub selected_topics: HashMap<String, Option<String>>,
     pub selected_messages: HashMap<String, Option<MqttMessage>>,
+    /// Retained messages about to be cleared, per connection
+    pub retained_clears: HashMap<String, RetainedClear>,
+    /// Retained messages about to be copied to another connection
+    pub retained_copies: HashMap<String, RetainedCopy>,
 
     // Publish panel state
     pub publish_topic: String,
     pub publish_payload: String,
 
+    // Subscription manager state
+    pub subscribe_topic: String,
+    pub subscribe_qos: u8,
+
     // Pane layout
     pub panes: pane_grid::State<Pane>,
     pub publish_qos: u8,
     pub publish_retain: bool,
+    /// Send requests and wait for replies (request/response)
+    pub publish_rpc: bool,
+    pub publish_reply_topic: String,
+    pub publish_correlation_field: String,
+    /// Publish on a timer instead of once
+    pub publish_repeat: bool,
+    pub publish_interval: String,
+    pub publish_times: String,
+    pub publish_start_at: String,
+    pub publish_job_error: Option<String>,
+
+    // Snapshot page state
+    pub snapshot_source: Option<String>,
+    pub snapshot_filter: String,
+    pub snapshot_quiet: String,
+    pub snapshot_file: String,
+    pub snapshot_status: Option<TransferStatus>,
+    pub restore_file: String,
+    pub restore_target: Option<String>,
+    /// Snapshot loaded for the dry run, restored as shown
+    pub restore_preview: Option<RetainedSnapshot>,
+    pub restore_status: Option<TransferStatus>,
+
+    // Embedded broker state
+    pub local_broker_port: String,
+    pub local_broker_username: String,
+    pub local_broker_password: String,
+    pub local_broker_listen_all: bool,
+    /// Address the broker listens on, once started
+    pub local_broker: Option<SocketAddr>,
+    pub local_broker_error: Option<String>,
+    pub local_broker_starting: bool,
+
+    // Bridge page state
+    pub bridges: Vec<Bridge>,
+    pub bridge_source: Option<String>,
+    pub bridge_target: Option<String>,
+    pub bridge_filter: String,
+    pub bridge_rewrite_from: String,
+    pub bridge_rewrite_to: String,
+    pub bridge_qos: BridgeQos,
+    pub bridge_retain: BridgeRetain,
+    /// Recent bridge publishes, so they aren't forwarded again
+    bridge_echoes: Echoes,
 
     // UI throttling - cache tree nodes to avoid rebuilding every frame
     pub cached_tree_nodes: HashMap<String, Vec<types::TreeNodeInfo>>,
     pub tree_cache_dirty: HashMap<String, bool>,
-    tick_counter: u32,
+    next_worker_id: u64,
+    next_publish_id: u64,
+    next_test_id: u64,
+    next_bridge_id: u64,
+    next_job_id: u64,
 }
 
 impl MqttUi {
     pub fn new() -> (Self, Task<Message>) {
         let config = AppConfig::load().unwrap_or_default();
         let open_tabs = config.last_opened_tabs.clone();
+        let local_broker = config.embedded_broker.clone();
         let panes = types::create_pane_layout();
 
         (
@@ -135,22 +432,123 @@ impl MqttUi {
                 form_client_id: String::new(),
                 form_username: String::new(),
                 form_password: String::new(),
+                form_credential_kind: CredentialKind::default(),
+                form_jwt_algorithm: JwtAlgorithm::default(),
+                form_jwt_key_file: String::new(),
+                form_jwt_claims: DEFAULT_JWT_CLAIMS.to_string(),
+                form_jwt_lifetime: String::new(),
+                form_sas_key: String::new(),
+                form_sas_key_name: String::new(),
+                form_sas_resource: String::new(),
+                form_sas_lifetime: String::new(),
                 form_protocol: MqttProtocol::default(),
-                form_subscriptions: vec![("#".to_string(), 0)],
+                form_version: MqttVersion::default(),
+                form_v5_session_expiry: String::new(),
+                form_v5_receive_maximum: String::new(),
+                form_v5_topic_alias_max: String::new(),
+                form_tls_use_system_roots: true,
+                form_tls_ca_file: String::new(),
+                form_tls_client_cert: String::new(),
+                form_tls_client_key: String::new(),
+                form_tls_server_name: String::new(),
+                form_tls_insecure: false,
+                form_ws_path: WebSocketSettings::default().path,
+                form_ws_headers: Vec::new(),
+                form_clean_session: true,
+                form_keep_alive: String::new(),
+                form_max_incoming_packet: String::new(),
+                form_max_outgoing_packet: String::new(),
+                form_max_inflight: String::new(),
+                form_message_buffer: String::new(),
+                form_will_enabled: false,
+                form_will_topic: String::new(),
+                form_will_payload: String::new(),
+                form_will_qos: 0,
+                form_will_retain: false,
+                form_will_delay: String::new(),
+                form_will_expiry: String::new(),
+                form_will_content_type: String::new(),
+                form_will_response_topic: String::new(),
+                form_will_user_properties: Vec::new(),
+                form_reconnect_enabled: true,
+                form_reconnect_initial_backoff: String::new(),
+                form_reconnect_max_backoff: String::new(),
+                form_reconnect_jitter: String::new(),
+                form_reconnect_max_attempts: String::new(),
+                form_failover_endpoints: Vec::new(),
+                form_failover_round_robin: false,
+                form_proxy_kind: ProxyKind::default(),
+                form_proxy_host: String::new(),
+                form_proxy_port: String::new(),
+                form_proxy_username: String::new(),
+                form_proxy_password: String::new(),
+                form_ssh_enabled: false,
+                form_ssh_host: String::new(),
+                form_ssh_port: String::new(),
+                form_ssh_user: String::new(),
+                form_ssh_auth: SshAuth::default(),
+                form_ssh_key_file: String::new(),
+                form_ssh_key_passphrase: String::new(),
+                form_ssh_remote_host: String::new(),
+                form_ssh_remote_port: String::new(),
+                form_subscriptions: vec![MqttSubscription::default()],
+                form_subscribe_sys: false,
+                connection_test: Vec::new(),
                 connections: HashMap::new(),
                 topic_trees: HashMap::new(),
                 open_tabs,
                 active_tab: None,
                 selected_topics: HashMap::new(),
                 selected_messages: HashMap::new(),
+                retained_clears: HashMap::new(),
+                retained_copies: HashMap::new(),
                 publish_topic: String::new(),
                 publish_payload: String::new(),
+                subscribe_topic: String::new(),
+                subscribe_qos: 0,
                 panes,
                 publish_qos: 0,
                 publish_retain: false,
+                publish_rpc: false,
+                publish_reply_topic: String::new(),
+                publish_correlation_field: DEFAULT_CORRELATION_FIELD.to_string(),
+                publish_repeat: false,
+                publish_interval: "1000".to_string(),
+                publish_times: String::new(),
+                publish_start_at: String::new(),
+                publish_job_error: None,
+                snapshot_source: None,
+                snapshot_filter: "#".to_string(),
+                snapshot_quiet: String::new(),
+                snapshot_file: String::new(),
+                snapshot_status: None,
+                restore_file: String::new(),
+                restore_target: None,
+                restore_preview: None,
+                restore_status: None,
+                local_broker_port: local_broker.port.to_string(),
+                local_broker_username: local_broker.username.unwrap_or_default(),
+                local_broker_password: local_broker.password.unwrap_or_default(),
+                local_broker_listen_all: local_broker.listen_all,
+                local_broker: None,
+                local_broker_error: None,
+                local_broker_starting: false,
+                bridges: Vec::new(),
+                bridge_source: None,
+                bridge_target: None,
+                bridge_filter: String::new(),
+                bridge_rewrite_from: String::new(),
+                bridge_rewrite_to: String::new(),
+                bridge_qos: BridgeQos::default(),
+                bridge_retain: BridgeRetain::default(),
+                bridge_echoes: Echoes::default(),
                 cached_tree_nodes: HashMap::new(),
                 tree_cache_dirty: HashMap::new(),
-                tick_counter: 0,
+                next_worker_id: 0,
+                next_publish_id: 0,
+                next_test_id: 0,
+                next_bridge_id: 0,
+                next_job_id: 0,
             },
             Task::none(),
         )
@@ -161,8 +559,58 @@ impl MqttUi {
     }
 
     pub fn subscription(&self) -> Subscription<Message> {
-        // Just poll for MQTT messages
-        time::every(Duration::from_millis(50)).map(|_| Message::Tick)
+        let workers = self.connections.iter().filter_map(|(id, conn)| {
+            let worker_id = conn.worker_id?;
+            let id = id.clone();
+            let events = mqtt_worker::run(conn.config.clone(), conn.inbox.clone())
+                .map(move |event| (id.clone(), event));
+            Some(
+                Subscription::run_with_id((conn.config.id.clone(), worker_id), events)
+                    .map(|(id, event)| Message::MqttEvent(id, event)),
+            )
+        });
+
+        // Tree caches are rebuilt at most every 500ms, and only while stale
+        let rebuild = self
+            .tree_cache_dirty
+            .values()
+            .any(|dirty| *dirty)
+            .then(|| time::every(Duration::from_millis(500)).map(|_| Message::Tick));
+
+        // Rates change every second while any window still covers traffic
+        let now = Utc::now().timestamp();
+        let refresh_stats = self
+            .connections
+            .values()
+            .any(|conn| conn.stats.is_active(now))
+            .then(|| time::every(Duration::from_secs(1)).map(|_| Message::RefreshStats));
+
+        // Requests fail once they have waited too long for a reply
+        let expire_rpc = self
+            .connections
+            .values()
+            .any(|conn| conn.publishes.iter().any(PublishRecord::awaits_reply))
+            .then(|| time::every(Duration::from_secs(1)).map(|_| Message::ExpireRpcCalls));
+
+        // Each running publish job ticks on its own timer
+        let jobs = self.connections.iter().flat_map(|(id, conn)| {
+            conn.jobs
+                .iter()
+                .filter(|job| !job.paused && !job.is_done())
+                .map(move |job| {
+                    time::every(job.tick())
+                        .with((id.clone(), job.id))
+                        .map(|((id, job), _)| Message::PublishJobTick(id, job))
+                })
+        });
+
+        Subscription::batch(
+            workers
+                .chain(rebuild)
+                .chain(refresh_stats)
+                .chain(expire_rpc)
+                .chain(jobs),
+        )
     }
 
     pub fn update(&mut self, message: Message) -> Task<Message> {
@@ -185,16 +633,93 @@ impl MqttUi {
                     self.form_client_id = config.client_id.clone().unwrap_or_default();
                     self.form_username = config.username.clone().unwrap_or_default();
                     self.form_password = config.password.clone().unwrap_or_default();
+                    let credentials = &config.credentials;
+                    self.form_credential_kind = credentials.kind;
+                    self.form_jwt_algorithm = credentials.jwt.algorithm;
+                    self.form_jwt_key_file = credentials.jwt.key_file.clone();
+                    self.form_jwt_claims = credentials.jwt.claims.clone();
+                    self.form_jwt_lifetime = credentials.jwt.lifetime_secs.to_string();
+                    self.form_sas_key = credentials.sas.key.clone();
+                    self.form_sas_key_name = credentials.sas.key_name.clone().unwrap_or_default();
+                    self.form_sas_resource =
+                        credentials.sas.resource_uri.clone().unwrap_or_default();
+                    self.form_sas_lifetime = credentials.sas.lifetime_secs.to_string();
                     self.form_protocol = config.protocol;
+                    self.form_version = config.version;
+                    self.form_v5_session_expiry =
+                        optional_number(config.v5.session_expiry_interval);
+                    self.form_v5_receive_maximum = optional_number(config.v5.receive_maximum);
+                    self.form_v5_topic_alias_max = optional_number(config.v5.topic_alias_max);
+                    self.form_tls_use_system_roots = config.tls.use_system_roots;
+                    self.form_tls_ca_file = config.tls.ca_file.unwrap_or_default();
+                    self.form_tls_client_cert = config.tls.client_cert_file.unwrap_or_default();
+                    self.form_tls_client_key = config.tls.client_key_file.unwrap_or_default();
+                    self.form_tls_server_name = config.tls.server_name.unwrap_or_default();
+                    self.form_tls_insecure = config.tls.insecure;
+                    self.form_ws_path = config.websocket.path.clone();
+                    self.form_ws_headers = config
+                        .websocket
+                        .headers
+                        .iter()
+                        .map(|h| (h.name.clone(), h.value.clone()))
+                        .collect();
+                    self.form_clean_session = config.session.clean_session;
+                    self.form_keep_alive = config.session.keep_alive_secs.to_string();
+                    self.form_max_incoming_packet =
+                        config.session.max_incoming_packet_size.to_string();
+                    self.form_max_outgoing_packet =
+                        config.session.max_outgoing_packet_size.to_string();
+                    self.form_max_inflight = config.session.max_inflight.to_string();
+                    self.form_message_buffer = config.session.message_buffer_mb.to_string();
+                    let will = config.last_will.clone().unwrap_or_default();
+                    self.form_will_enabled = config.last_will.is_some();
+                    self.form_will_topic = will.topic;
+                    self.form_will_payload = will.payload;
+                    self.form_will_qos = will.qos;
+                    self.form_will_retain = will.retain;
+                    self.form_will_delay = optional_number(will.delay_interval);
+                    self.form_will_expiry = optional_number(will.message_expiry_interval);
+                    self.form_will_content_type = will.content_type.unwrap_or_default();
+                    self.form_will_response_topic = will.response_topic.unwrap_or_default();
+                    self.form_will_user_properties = will.user_properties;
+                    self.form_reconnect_enabled = config.reconnect.enabled;
+                    self.form_reconnect_initial_backoff =
+                        config.reconnect.initial_backoff_ms.to_string();
+                    self.form_reconnect_max_backoff = config.reconnect.max_backoff_ms.to_string();
+                    self.form_reconnect_jitter = config.reconnect.jitter_percent.to_string();
+                    self.form_reconnect_max_attempts =
+                        optional_number(config.reconnect.max_attempts);
+                    self.form_failover_endpoints = config
+                        .failover
+                        .endpoints
+                        .iter()
+                        .map(|e| (e.host.clone(), e.port.to_string()))
+                        .collect();
+                    self.form_failover_round_robin = config.failover.round_robin;
+                    self.form_proxy_kind = config.proxy.kind;
+                    self.form_proxy_host = config.proxy.host.clone();
+                    self.form_proxy_port = config.proxy.port.to_string();
+                    self.form_proxy_username = config.proxy.username.clone().unwrap_or_default();
+                    self.form_proxy_password = config.proxy.password.clone().unwrap_or_default();
+                    let ssh = &config.ssh;
+                    self.form_ssh_enabled = ssh.enabled;
+                    self.form_ssh_host = ssh.host.clone();
+                    self.form_ssh_port = ssh.port.to_string();
+                    self.form_ssh_user = ssh.user.clone();
+                    self.form_ssh_auth = ssh.auth;
+                    self.form_ssh_key_file = ssh.key_file.clone().unwrap_or_default();
+                    self.form_ssh_key_passphrase = ssh.key_passphrase.clone().unwrap_or_default();
+                    self.form_ssh_remote_host = ssh.remote_host.clone().unwrap_or_default();
+                    self.form_ssh_remote_port = ssh
+                        .remote_port
+                        .map(|port| port.to_string())
+                        .unwrap_or_default();
                     self.form_subscriptions = if config.subscriptions.is_empty() {
-                        vec![("#".to_string(), 0)]
+                        vec![MqttSubscription::default()]
                     } else {
-                        config
-                            .subscriptions
-                            .iter()
-                            .map(|s| (s.topic.clone(), s.qos))
-                            .collect()
+                        config.subscriptions.clone()
                     };
+                    self.form_subscribe_sys = config.subscribe_sys;
                     self.view = View::ConnectionForm {
                         editing_id: Some(id),
                     };
@@ -221,10 +746,131 @@ impl MqttUi {
             Message::FormClientIdChanged(v) => self.form_client_id = v,
             Message::FormUsernameChanged(v) => self.form_username = v,
             Message::FormPasswordChanged(v) => self.form_password = v,
-            Message::FormProtocolChanged(v) => self.form_protocol = v,
+            Message::FormCredentialKindChanged(v) => self.form_credential_kind = v,
+            Message::FormJwtAlgorithmChanged(v) => self.form_jwt_algorithm = v,
+            Message::FormJwtKeyFileChanged(v) => self.form_jwt_key_file = v,
+            Message::FormJwtClaimsChanged(v) => self.form_jwt_claims = v,
+            Message::FormJwtLifetimeChanged(v) => self.form_jwt_lifetime = v,
+            Message::FormSasKeyChanged(v) => self.form_sas_key = v,
+            Message::FormSasKeyNameChanged(v) => self.form_sas_key_name = v,
+            Message::FormSasResourceChanged(v) => self.form_sas_resource = v,
+            Message::FormSasLifetimeChanged(v) => self.form_sas_lifetime = v,
+            Message::FormProtocolChanged(v) => {
+                // Follow the protocol's default port unless a custom one was entered
+                if self.form_port == self.form_protocol.default_port().to_string() {
+                    self.form_port = v.default_port().to_string();
+                }
+                self.form_protocol = v;
+            }
+            Message::FormVersionChanged(v) => self.form_version = v,
+            Message::FormV5SessionExpiryChanged(v) => self.form_v5_session_expiry = v,
+            Message::FormV5ReceiveMaximumChanged(v) => self.form_v5_receive_maximum = v,
+            Message::FormV5TopicAliasMaxChanged(v) => self.form_v5_topic_alias_max = v,
+            Message::FormTlsSystemRootsChanged(v) => self.form_tls_use_system_roots = v,
+            Message::FormTlsCaFileChanged(v) => self.form_tls_ca_file = v,
+            Message::FormTlsClientCertChanged(v) => self.form_tls_client_cert = v,
+            Message::FormTlsClientKeyChanged(v) => self.form_tls_client_key = v,
+            Message::FormTlsServerNameChanged(v) => self.form_tls_server_name = v,
+            Message::FormTlsInsecureChanged(v) => self.form_tls_insecure = v,
+            Message::FormWsPathChanged(v) => self.form_ws_path = v,
+            Message::FormCleanSessionChanged(v) => self.form_clean_session = v,
+            Message::FormKeepAliveChanged(v) => self.form_keep_alive = v,
+            Message::FormMaxIncomingPacketChanged(v) => self.form_max_incoming_packet = v,
+            Message::FormMaxOutgoingPacketChanged(v) => self.form_max_outgoing_packet = v,
+            Message::FormMaxInflightChanged(v) => self.form_max_inflight = v,
+            Message::FormMessageBufferChanged(v) => self.form_message_buffer = v,
+            Message::FormWillEnabledChanged(v) => self.form_will_enabled = v,
+            Message::FormWillTopicChanged(v) => self.form_will_topic = v,
+            Message::FormWillPayloadChanged(v) => self.form_will_payload = v,
+            Message::FormWillQosChanged(v) => self.form_will_qos = v,
+            Message::FormWillRetainChanged(v) => self.form_will_retain = v,
+            Message::FormWillDelayChanged(v) => self.form_will_delay = v,
+            Message::FormWillExpiryChanged(v) => self.form_will_expiry = v,
+            Message::FormWillContentTypeChanged(v) => self.form_will_content_type = v,
+            Message::FormWillResponseTopicChanged(v) => self.form_will_response_topic = v,
+            Message::FormReconnectEnabledChanged(v) => self.form_reconnect_enabled = v,
+            Message::FormReconnectInitialBackoffChanged(v) => {
+                self.form_reconnect_initial_backoff = v
+            }
+            Message::FormReconnectMaxBackoffChanged(v) => self.form_reconnect_max_backoff = v,
+            Message::FormReconnectJitterChanged(v) => self.form_reconnect_jitter = v,
+            Message::FormReconnectMaxAttemptsChanged(v) => self.form_reconnect_max_attempts = v,
+            Message::FormAddFailoverEndpoint => {
+                self.form_failover_endpoints
+                    .push((String::new(), self.form_port.clone()));
+            }
+            Message::FormRemoveFailoverEndpoint(idx) => {
+                if idx < self.form_failover_endpoints.len() {
+                    self.form_failover_endpoints.remove(idx);
+                }
+            }
+            Message::FormFailoverHostChanged(idx, host) => {
+                if let Some(endpoint) = self.form_failover_endpoints.get_mut(idx) {
+                    endpoint.0 = host;
+                }
+            }
+            Message::FormFailoverPortChanged(idx, port) => {
+                if let Some(endpoint) = self.form_failover_endpoints.get_mut(idx) {
+                    endpoint.1 = port;
+                }
+            }
+            Message::FormFailoverRoundRobinChanged(v) => self.form_failover_round_robin = v,
+            Message::FormProxyKindChanged(v) => self.form_proxy_kind = v,
+            Message::FormProxyHostChanged(v) => self.form_proxy_host = v,
+            Message::FormProxyPortChanged(v) => self.form_proxy_port = v,
+            Message::FormProxyUsernameChanged(v) => self.form_proxy_username = v,
+            Message::FormProxyPasswordChanged(v) => self.form_proxy_password = v,
+            Message::FormSshEnabledChanged(v) => self.form_ssh_enabled = v,
+            Message::FormSshHostChanged(v) => self.form_ssh_host = v,
+            Message::FormSshPortChanged(v) => self.form_ssh_port = v,
+            Message::FormSshUserChanged(v) => self.form_ssh_user = v,
+            Message::FormSshAuthChanged(v) => self.form_ssh_auth = v,
+            Message::FormSshKeyFileChanged(v) => self.form_ssh_key_file = v,
+            Message::FormSshKeyPassphraseChanged(v) => self.form_ssh_key_passphrase = v,
+            Message::FormSshRemoteHostChanged(v) => self.form_ssh_remote_host = v,
+            Message::FormSshRemotePortChanged(v) => self.form_ssh_remote_port = v,
+
+            Message::FormAddWsHeader => {
+                self.form_ws_headers.push((String::new(), String::new()));
+            }
+            Message::FormRemoveWsHeader(idx) => {
+                if idx < self.form_ws_headers.len() {
+                    self.form_ws_headers.remove(idx);
+                }
+            }
+            Message::FormWsHeaderNameChanged(idx, name) => {
+                if let Some(header) = self.form_ws_headers.get_mut(idx) {
+                    header.0 = name;
+                }
+            }
+            Message::FormWsHeaderValueChanged(idx, value) => {
+                if let Some(header) = self.form_ws_headers.get_mut(idx) {
+                    header.1 = value;
+                }
+            }
+
+            Message::FormAddWillUserProperty => {
+                self.form_will_user_properties
+                    .push((String::new(), String::new()));
+            }
+            Message::FormRemoveWillUserProperty(idx) => {
+                if idx < self.form_will_user_properties.len() {
+                    self.form_will_user_properties.remove(idx);
+                }
+            }
+            Message::FormWillUserPropertyNameChanged(idx, name) => {
+                if let Some(property) = self.form_will_user_properties.get_mut(idx) {
+                    property.0 = name;
+                }
+            }
+            Message::FormWillUserPropertyValueChanged(idx, value) => {
+                if let Some(property) = self.form_will_user_properties.get_mut(idx) {
+                    property.1 = value;
+                }
+            }
 
             Message::FormAddSubscription => {
-                self.form_subscriptions.push(("#".to_string(), 0));
+                self.form_subscriptions.push(MqttSubscription::default());
             }
             Message::FormRemoveSubscription(idx) => {
                 if self.form_subscriptions.len() > 1 {
@@ -233,21 +879,59 @@ impl MqttUi {
             }
             Message::FormSubscriptionTopicChanged(idx, topic) => {
                 if let Some(sub) = self.form_subscriptions.get_mut(idx) {
-                    sub.0 = topic;
+                    sub.topic = topic;
                 }
             }
             Message::FormSubscriptionQosChanged(idx, qos) => {
                 if let Some(sub) = self.form_subscriptions.get_mut(idx) {
-                    sub.1 = qos;
+                    sub.qos = qos;
+                }
+            }
+            Message::FormSubscriptionShareGroupChanged(idx, group) => {
+                if let Some(sub) = self.form_subscriptions.get_mut(idx) {
+                    sub.share_group = Some(group).filter(|g| !g.is_empty());
+                }
+            }
+            Message::FormSubscriptionNoLocalChanged(idx, v) => {
+                if let Some(sub) = self.form_subscriptions.get_mut(idx) {
+                    sub.no_local = v;
+                }
+            }
+            Message::FormSubscriptionRetainAsPublishedChanged(idx, v) => {
+                if let Some(sub) = self.form_subscriptions.get_mut(idx) {
+                    sub.retain_as_published = v;
                 }
             }
+            Message::FormSubscriptionRetainHandlingChanged(idx, v) => {
+                if let Some(sub) = self.form_subscriptions.get_mut(idx) {
+                    sub.retain_handling = v;
+                }
+            }
+            Message::FormSubscriptionIdentifierChanged(idx, v) => {
+                if let Some(sub) = self.form_subscriptions.get_mut(idx) {
+                    // Identifiers run from 1 to 268,435,455; other input is ignored
+                    let valid = |id: &u32| (1..=268_435_455).contains(id);
+                    if v.is_empty() {
+                        sub.identifier = None;
+                    } else if let Some(id) = v.parse().ok().filter(valid) {
+                        sub.identifier = Some(id);
+                    }
+                }
+            }
+            Message::FormSubscribeSysChanged(v) => self.form_subscribe_sys = v,
 
             Message::FormSaveConnection => {
+                if self.form_keep_alive_error().is_some() {
+                    return Task::none();
+                }
                 self.save_form_connection(false);
                 self.view = View::Home;
             }
 
             Message::FormConnectAndSave => {
+                if self.form_keep_alive_error().is_some() {
+                    return Task::none();
+                }
                 let id = self.save_form_connection(true);
                 if let Some(id) = id {
                     self.open_connection(&id);
@@ -255,6 +939,27 @@ impl MqttUi {
                 }
             }
 
+            Message::FormTestConnection => {
+                self.next_test_id += 1;
+                let test_id = self.next_test_id;
+                self.connection_test = TestStage::ALL
+                    .map(|stage| (stage, StageResult::Pending))
+                    .to_vec();
+                return Task::run(
+                    mqtt_worker::test_connection(self.form_config()),
+                    move |(stage, result)| Message::ConnectionTestProgress(test_id, stage, result),
+                );
+            }
+            Message::ConnectionTestProgress(test_id, stage, result) => {
+                // Results of a test started before the form was reset or rerun
+                if test_id != self.next_test_id {
+                    return Task::none();
+                }
+                if let Some(entry) = self.connection_test.iter_mut().find(|(s, _)| *s == stage) {
+                    entry.1 = result;
+                }
+            }
+
             Message::FormCancel => {
                 self.view = View::Home;
             }
@@ -267,9 +972,24 @@ impl MqttUi {
                 self.stop_connection(&id);
             }
 
+            Message::TrustHostKey(id) => {
+                let unknown = self
+                    .connections
+                    .get_mut(&id)
+                    .and_then(|conn| conn.unknown_host_key.take());
+                if let (Some(unknown), Some(config)) =
+                    (unknown, self.config.get_connection_mut(&id))
+                {
+                    config.ssh.host_key = Some(unknown.key);
+                    self.save_config();
+                    self.start_connection(&id);
+                }
+            }
+
             Message::DeleteConnection(id) => {
                 self.config.remove_connection(&id);
                 self.connections.remove(&id);
+                self.bridges.retain(|b| b.source != id && b.target != id);
                 self.close_tab(&id);
                 self.save_config();
             }
@@ -326,42 +1046,524 @@ impl MqttUi {
 
             Message::ClearTopics(conn_id) => {
                 self.topic_trees.insert(conn_id.clone(), TopicTree::new());
+                self.retained_clears.remove(&conn_id);
+                self.retained_copies.remove(&conn_id);
                 self.cached_tree_nodes.remove(&conn_id);
                 self.selected_topics.remove(&conn_id);
                 self.selected_messages.remove(&conn_id);
             }
 
+            Message::ClearRetained(conn_id, path) => {
+                let clearing = self.retained_clears.get(&conn_id);
+                if clearing.is_some_and(|clear| !clear.pending.is_empty()) {
+                    return Task::none();
+                }
+                let topics = self
+                    .topic_trees
+                    .get(&conn_id)
+                    .and_then(|tree| tree.get_node(&path))
+                    .map(|node| node.retained_topics())
+                    .unwrap_or_default();
+                let clear = RetainedClear {
+                    path,
+                    topics,
+                    ..Default::default()
+                };
+                self.retained_clears.insert(conn_id, clear);
+            }
+            Message::CancelClearRetained(conn_id) => {
+                self.retained_clears.remove(&conn_id);
+            }
+            Message::ConfirmClearRetained(conn_id) => {
+                let Some(clear) = self.retained_clears.get_mut(&conn_id) else {
+                    return Task::none();
+                };
+                if !clear.pending.is_empty() {
+                    return Task::none();
+                }
+                let conn = self.connections.get(&conn_id);
+                let tx = conn
+                    .filter(|c| c.status.is_connected())
+                    .and_then(|c| c.command_tx.as_ref());
+                let Some(tx) = tx else {
+                    clear.error = Some("Not connected".to_string());
+                    return Task::none();
+                };
+                clear.error = None;
+                // An empty retained message deletes the one the broker holds.
+                // The tree nodes go once each publish is delivered.
+                for topic in &clear.topics {
+                    self.next_publish_id += 1;
+                    let request = PublishRequest {
+                        id: self.next_publish_id,
+                        topic: topic.clone(),
+                        payload: Vec::new(),
+                        qos: 1,
+                        retain: true,
+                        properties: None,
+                    };
+                    if tx.send(MqttCommand::Publish(request)).is_err() {
+                        clear.fail_pending("Disconnected");
+                        return Task::none();
+                    }
+                    clear.pending.insert(self.next_publish_id, topic.clone());
+                }
+            }
+
+            Message::CopyRetained(conn_id, path) => {
+                let messages = self
+                    .topic_trees
+                    .get(&conn_id)
+                    .and_then(|tree| tree.get_node(&path))
+                    .map(|node| node.retained_messages())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|msg| SnapshotMessage::from(msg.clone()))
+                    .collect();
+                let copy = RetainedCopy {
+                    prefix: path.clone(),
+                    path,
+                    messages,
+                    target: None,
+                    status: None,
+                };
+                self.retained_copies.insert(conn_id, copy);
+            }
+            Message::CopyTargetChanged(conn_id, choice) => {
+                if let Some(copy) = self.retained_copies.get_mut(&conn_id) {
+                    copy.target = Some(choice.id);
+                }
+            }
+            Message::CopyPrefixChanged(conn_id, prefix) => {
+                if let Some(copy) = self.retained_copies.get_mut(&conn_id) {
+                    copy.prefix = prefix;
+                }
+            }
+            Message::CancelCopyRetained(conn_id) => {
+                self.retained_copies.remove(&conn_id);
+            }
+            Message::ConfirmCopyRetained(conn_id) => {
+                let Some(copy) = self.retained_copies.get_mut(&conn_id) else {
+                    return Task::none();
+                };
+                let source = self.config.get_connection(&conn_id);
+                let target = copy.target.as_ref();
+                let Some(config) = target.and_then(|id| self.config.get_connection(id)) else {
+                    return Task::none();
+                };
+                if let Some(error) = copy.prefix_error() {
+                    copy.status = Some(TransferStatus::Failed(error));
+                    return Task::none();
+                }
+                let messages = copy
+                    .messages
+                    .iter()
+                    .filter_map(|msg| {
+                        Some(SnapshotMessage {
+                            topic: copy.destination(&msg.topic)?,
+                            ..msg.clone()
+                        })
+                    })
+                    .collect();
+                let broker = source
+                    .map(|c| c.endpoints()[0].to_string())
+                    .unwrap_or_default();
+                let filter = format!("{}/#", copy.path);
+                let snapshot = RetainedSnapshot::new(broker, filter, messages);
+                copy.status = Some(TransferStatus::Running("Starting".to_string()));
+                return Task::run(
+                    mqtt_worker::restore_snapshot(config.side_session("copy"), snapshot),
+                    move |status| Message::CopyProgress(conn_id.clone(), status),
+                );
+            }
+            Message::CopyProgress(conn_id, status) => {
+                if let Some(copy) = self.retained_copies.get_mut(&conn_id) {
+                    copy.status = Some(status);
+                }
+            }
+
+            Message::StartPublishJob => {
+                let Some(conn_id) = self.active_tab.clone() else {
+                    return Task::none();
+                };
+                match self.publish_job() {
+                    Ok(job) => {
+                        let Some(conn) = self.connections.get_mut(&conn_id) else {
+                            return Task::none();
+                        };
+                        self.publish_job_error = None;
+                        let (job_id, immediate) = (job.id, job.start_at.is_none());
+                        conn.jobs.push(job);
+                        if immediate {
+                            return Task::done(Message::PublishJobTick(conn_id, job_id));
+                        }
+                    }
+                    Err(e) => self.publish_job_error = Some(e),
+                }
+            }
+            Message::PublishJobTick(conn_id, job_id) => {
+                let Some(conn) = self.connections.get_mut(&conn_id) else {
+                    return Task::none();
+                };
+                let Some(job) = conn.jobs.iter_mut().find(|j| j.id == job_id) else {
+                    return Task::none();
+                };
+                // Jobs wait out disconnects rather than losing publishes
+                let tx = conn.command_tx.as_ref();
+                let Some(tx) = tx.filter(|_| conn.status.is_connected()) else {
+                    return Task::none();
+                };
+                if job.paused || job.is_done() || job.start_at.is_some_and(|at| Utc::now() < at) {
+                    return Task::none();
+                }
+                job.start_at = None;
+                if job.outstanding.is_some() {
+                    job.skipped += 1;
+                    return Task::none();
+                }
+                self.next_publish_id += 1;
+                let request = job.request(self.next_publish_id);
+                if tx.send(MqttCommand::Publish(request)).is_ok() {
+                    job.sent += 1;
+                    job.outstanding = Some(self.next_publish_id);
+                }
+            }
+            Message::PausePublishJob(conn_id, job_id) => {
+                if let Some(job) = self.publish_job_mut(&conn_id, job_id) {
+                    job.paused = true;
+                }
+            }
+            Message::ResumePublishJob(conn_id, job_id) => {
+                if let Some(job) = self.publish_job_mut(&conn_id, job_id) {
+                    job.paused = false;
+                }
+            }
+            Message::CancelPublishJob(conn_id, job_id) => {
+                if let Some(conn) = self.connections.get_mut(&conn_id) {
+                    conn.jobs.retain(|j| j.id != job_id);
+                }
+            }
+
+            Message::SubscribeTopicChanged(v) => self.subscribe_topic = v,
+            Message::SubscribeQosChanged(v) => self.subscribe_qos = v,
+
+            Message::Subscribe => {
+                let topic = self.subscribe_topic.trim().to_string();
+                let conn = self
+                    .active_tab
+                    .as_ref()
+                    .and_then(|id| self.connections.get_mut(id));
+                if let Some(conn) = conn.filter(|_| !topic.is_empty()) {
+                    if let Some(tx) = &conn.command_tx {
+                        let request = MqttSubscription::new(topic.clone(), self.subscribe_qos);
+                        let _ = tx.send(MqttCommand::Subscribe(request));
+                        let subscription = ActiveSubscription {
+                            topic: topic.clone(),
+                            qos: self.subscribe_qos,
+                            status: SubscriptionStatus::Pending,
+                        };
+                        match conn.subscriptions.iter_mut().find(|s| s.topic == topic) {
+                            Some(existing) => *existing = subscription,
+                            None => conn.subscriptions.push(subscription),
+                        }
+                        self.subscribe_topic.clear();
+                    }
+                }
+            }
+
+            Message::Unsubscribe(conn_id, topic) => {
+                if let Some(conn) = self.connections.get_mut(&conn_id) {
+                    if let Some(tx) = &conn.command_tx {
+                        let _ = tx.send(MqttCommand::Unsubscribe(topic.clone()));
+                    }
+                    conn.subscriptions.retain(|s| s.topic != topic);
+                }
+            }
+
+            Message::SaveSubscription(conn_id, topic) => {
+                let qos = self
+                    .connections
+                    .get(&conn_id)
+                    .and_then(|c| c.subscriptions.iter().find(|s| s.topic == topic))
+                    .map(|s| s.qos)
+                    .unwrap_or(0);
+                if let Some(config) = self.config.get_connection_mut(&conn_id) {
+                    // Keep the implicit "#" if nothing was configured before
+                    let mut subscriptions = config.initial_subscriptions();
+                    subscriptions.retain(|s| s.filter() != topic);
+                    subscriptions.push(MqttSubscription::new(topic, qos));
+                    config.subscriptions = subscriptions;
+                }
+                self.save_config();
+            }
+
             Message::PublishTopicChanged(v) => self.publish_topic = v,
             Message::PublishPayloadChanged(v) => self.publish_payload = v,
             Message::PublishQosChanged(v) => self.publish_qos = v,
             Message::PublishRetainChanged(v) => self.publish_retain = v,
+            Message::PublishRpcChanged(v) => {
+                self.publish_rpc = v;
+                // Jobs publish plain messages
+                self.publish_repeat &= !v;
+            }
+            Message::PublishReplyTopicChanged(v) => self.publish_reply_topic = v,
+            Message::PublishCorrelationFieldChanged(v) => self.publish_correlation_field = v,
+            Message::PublishRepeatChanged(v) => {
+                self.publish_repeat = v;
+                self.publish_rpc &= !v;
+                self.publish_job_error = None;
+            }
+            Message::PublishIntervalChanged(v) => self.publish_interval = v,
+            Message::PublishTimesChanged(v) => self.publish_times = v,
+            Message::PublishStartAtChanged(v) => self.publish_start_at = v,
 
             Message::SendMessage => {
                 if let Some(ref id) = self.active_tab {
-                    if let Some(conn) = self.connections.get(id) {
+                    if let Some(conn) = self.connections.get_mut(id) {
                         if let Some(tx) = &conn.command_tx {
-                            let _ = tx.send(MqttCommand::Publish(
-                                self.publish_topic.clone(),
-                                self.publish_payload.as_bytes().to_vec(),
-                                self.publish_qos,
-                                self.publish_retain,
-                            ));
+                            self.next_publish_id += 1;
+                            let mut request = PublishRequest {
+                                id: self.next_publish_id,
+                                topic: self.publish_topic.clone(),
+                                payload: self.publish_payload.as_bytes().to_vec(),
+                                qos: self.publish_qos,
+                                retain: self.publish_retain,
+                                properties: None,
+                            };
+                            let mut record = PublishRecord {
+                                id: request.id,
+                                topic: request.topic.clone(),
+                                qos: request.qos,
+                                status: DeliveryStatus::Pending,
+                                rpc: None,
+                            };
+                            if self.publish_rpc {
+                                let call = rpc_call(
+                                    &conn.config,
+                                    &self.publish_reply_topic,
+                                    &self.publish_correlation_field,
+                                );
+                                match call.prepare(&mut request) {
+                                    Ok(()) => {
+                                        let subscriptions = &mut conn.subscriptions;
+                                        let (topic, qos) = (&call.reply_topic, request.qos);
+                                        subscribe_unless_covered(subscriptions, tx, topic, qos);
+                                    }
+                                    Err(e) => record.status = DeliveryStatus::Failed(e),
+                                }
+                                record.rpc = Some(call);
+                            }
+                            let failed = record.status != DeliveryStatus::Pending;
+                            if failed || tx.send(MqttCommand::Publish(request)).is_ok() {
+                                conn.publishes.push(record);
+                                if conn.publishes.len() > MAX_PUBLISH_RECORDS {
+                                    conn.publishes.remove(0);
+                                }
+                            }
                         }
                     }
                 }
             }
 
+            Message::ShowSnapshots => self.view = View::Snapshots,
+            Message::SnapshotSourceChanged(choice) => self.snapshot_source = Some(choice.id),
+            Message::SnapshotFilterChanged(v) => self.snapshot_filter = v,
+            Message::SnapshotQuietChanged(v) => self.snapshot_quiet = v,
+            Message::SnapshotFileChanged(v) => self.snapshot_file = v,
+            Message::TakeSnapshot => {
+                let config = self
+                    .snapshot_source
+                    .as_ref()
+                    .and_then(|id| self.config.get_connection(id))
+                    .cloned();
+                let Some(config) = config else {
+                    return Task::none();
+                };
+                let path = match non_empty(&self.snapshot_file) {
+                    Some(path) => PathBuf::from(path),
+                    None => match RetainedSnapshot::default_path(&config.name) {
+                        Ok(path) => path,
+                        Err(e) => {
+                            self.snapshot_status = Some(TransferStatus::Failed(e.to_string()));
+                            return Task::none();
+                        }
+                    },
+                };
+                self.snapshot_file = path.display().to_string();
+                let filter = non_empty(&self.snapshot_filter).unwrap_or_else(|| "#".to_string());
+                let quiet = self
+                    .snapshot_quiet
+                    .trim()
+                    .parse()
+                    .unwrap_or(DEFAULT_SNAPSHOT_QUIET_SECS);
+                self.snapshot_status = Some(TransferStatus::Running("Starting".to_string()));
+                return Task::run(
+                    mqtt_worker::take_snapshot(
+                        config.side_session("snapshot"),
+                        filter,
+                        Duration::from_secs(quiet),
+                        path,
+                    ),
+                    Message::SnapshotProgress,
+                );
+            }
+            Message::SnapshotProgress(status) => {
+                // Offer the new snapshot for restoring
+                if matches!(status, TransferStatus::Done(_)) && self.restore_file.is_empty() {
+                    self.restore_file = self.snapshot_file.clone();
+                }
+                self.snapshot_status = Some(status);
+            }
+            Message::RestoreFileChanged(v) => {
+                self.restore_file = v;
+                self.restore_preview = None;
+            }
+            Message::RestoreTargetChanged(choice) => self.restore_target = Some(choice.id),
+            Message::PreviewRestore => {
+                let path = PathBuf::from(self.restore_file.trim());
+                match RetainedSnapshot::load(&path) {
+                    Ok(snapshot) => {
+                        self.restore_preview = Some(snapshot);
+                        self.restore_status = None;
+                    }
+                    Err(e) => {
+                        self.restore_preview = None;
+                        self.restore_status = Some(TransferStatus::Failed(format!("{:#}", e)));
+                    }
+                }
+            }
+            Message::RestoreSnapshot => {
+                let config = self
+                    .restore_target
+                    .as_ref()
+                    .and_then(|id| self.config.get_connection(id))
+                    .cloned();
+                if let (Some(config), Some(snapshot)) = (config, self.restore_preview.clone()) {
+                    self.restore_status = Some(TransferStatus::Running("Starting".to_string()));
+                    return Task::run(
+                        mqtt_worker::restore_snapshot(config.side_session("restore"), snapshot),
+                        Message::RestoreProgress,
+                    );
+                }
+            }
+            Message::RestoreProgress(status) => self.restore_status = Some(status),
+
+            Message::LocalBrokerPortChanged(v) => self.local_broker_port = v,
+            Message::LocalBrokerUsernameChanged(v) => self.local_broker_username = v,
+            Message::LocalBrokerPasswordChanged(v) => self.local_broker_password = v,
+            Message::LocalBrokerListenAllChanged(v) => self.local_broker_listen_all = v,
+            Message::StartLocalBroker => {
+                let Ok(port) = self.local_broker_port.trim().parse::<u16>() else {
+                    self.local_broker_error = Some("Port must be a number".to_string());
+                    return Task::none();
+                };
+                let settings = EmbeddedBrokerSettings {
+                    port,
+                    listen_all: self.local_broker_listen_all,
+                    username: non_empty(&self.local_broker_username),
+                    password: non_empty(&self.local_broker_password),
+                    connection_id: self.config.embedded_broker.connection_id.clone(),
+                };
+                self.local_broker_starting = true;
+                self.local_broker_error = None;
+                return Task::perform(embedded_broker::start(settings.clone()), move |result| {
+                    let result = result.map_err(|e| format!("{:#}", e));
+                    Message::LocalBrokerStarted(settings.clone(), result)
+                });
+            }
+            Message::LocalBrokerStarted(settings, result) => {
+                self.local_broker_starting = false;
+                match result {
+                    Ok(addr) => {
+                        self.local_broker = Some(addr);
+                        self.register_local_broker(settings);
+                    }
+                    Err(e) => self.local_broker_error = Some(e),
+                }
+            }
+
+            Message::ShowBridges => self.view = View::Bridges,
+            Message::BridgeSourceChanged(choice) => self.bridge_source = Some(choice.id),
+            Message::BridgeTargetChanged(choice) => self.bridge_target = Some(choice.id),
+            Message::BridgeFilterChanged(v) => self.bridge_filter = v,
+            Message::BridgeRewriteFromChanged(v) => self.bridge_rewrite_from = v,
+            Message::BridgeRewriteToChanged(v) => self.bridge_rewrite_to = v,
+            Message::BridgeQosChanged(v) => self.bridge_qos = v,
+            Message::BridgeRetainChanged(v) => self.bridge_retain = v,
+            Message::AddBridge => {
+                let filter = self.bridge_filter.trim().to_string();
+                let (Some(source), Some(target)) = (&self.bridge_source, &self.bridge_target)
+                else {
+                    return Task::none();
+                };
+                if filter.is_empty() {
+                    return Task::none();
+                }
+                self.next_bridge_id += 1;
+                self.bridges.push(Bridge {
+                    id: self.next_bridge_id,
+                    source: source.clone(),
+                    target: target.clone(),
+                    rule: BridgeRule {
+                        filter,
+                        rewrite_from: self.bridge_rewrite_from.clone(),
+                        rewrite_to: self.bridge_rewrite_to.clone(),
+                        qos: self.bridge_qos,
+                        retain: self.bridge_retain,
+                    },
+                    running: false,
+                    subscribed: false,
+                    forwarded: 0,
+                    forwarded_bytes: 0,
+                    dropped: 0,
+                    last_forwarded: None,
+                });
+                self.start_bridge(self.next_bridge_id);
+            }
+            Message::StartBridge(id) => self.start_bridge(id),
+            Message::StopBridge(id) => self.stop_bridge(id),
+            Message::RemoveBridge(id) => {
+                self.stop_bridge(id);
+                self.bridges.retain(|b| b.id != id);
+            }
+
             Message::PaneResized(pane_grid::ResizeEvent { split, ratio }) => {
                 self.panes.resize(split, ratio);
             }
 
+            Message::MqttEvent(id, event) => return self.handle_mqtt_event(id, event),
+
             Message::Tick => {
-                self.tick_counter = self.tick_counter.wrapping_add(1);
-                self.poll_connections();
+                self.rebuild_dirty_caches();
+            }
+
+            Message::RefreshStats => {
+                // Cached tree nodes carry the rates, so recompute them
+                let now = Utc::now().timestamp();
+                for (id, conn) in &self.connections {
+                    if conn.stats.is_active(now) {
+                        self.tree_cache_dirty.insert(id.clone(), true);
+                    }
+                }
+                self.rebuild_dirty_caches();
+            }
 
-                // Rebuild tree caches every 10 ticks (500ms) if dirty
-                if self.tick_counter % 10 == 0 {
-                    self.rebuild_dirty_caches();
+            Message::ExpireRpcCalls => {
+                let records = self
+                    .connections
+                    .values_mut()
+                    .flat_map(|conn| conn.publishes.iter_mut());
+                for record in records {
+                    let overdue = record
+                        .rpc
+                        .as_ref()
+                        .is_some_and(|call| call.sent_at.elapsed() >= RPC_TIMEOUT);
+                    if overdue && record.awaits_reply() {
+                        record.status = DeliveryStatus::Failed(format!(
+                            "No reply within {} s",
+                            RPC_TIMEOUT.as_secs()
+                        ));
+                    }
                 }
             }
         }
@@ -382,8 +1584,9 @@ impl MqttUi {
             if let Some(tree) = self.topic_trees.get(&id) {
                 let nodes = collect_tree_nodes_static(&tree.root, 0);
                 self.cached_tree_nodes.insert(id.clone(), nodes);
-                self.tree_cache_dirty.insert(id, false);
             }
+            // Clear even without a tree, so the rebuild timer can stop
+            self.tree_cache_dirty.insert(id, false);
         }
     }
 
@@ -392,6 +1595,8 @@ impl MqttUi {
             View::Home => self.view_home(),
             View::ConnectionForm { editing_id } => self.view_connection_form(editing_id.as_deref()),
             View::Connection(id) => self.view_connection(id),
+            View::Snapshots => self.view_snapshots(),
+            View::Bridges => self.view_bridges(),
         };
 
         let tabs = self.view_tabs();
@@ -411,16 +1616,96 @@ impl MqttUi {
         self.form_client_id = String::new();
         self.form_username = String::new();
         self.form_password = String::new();
+        self.form_credential_kind = CredentialKind::default();
+        self.form_jwt_algorithm = JwtAlgorithm::default();
+        self.form_jwt_key_file = String::new();
+        self.form_jwt_claims = DEFAULT_JWT_CLAIMS.to_string();
+        self.form_jwt_lifetime = String::new();
+        self.form_sas_key = String::new();
+        self.form_sas_key_name = String::new();
+        self.form_sas_resource = String::new();
+        self.form_sas_lifetime = String::new();
         self.form_protocol = MqttProtocol::default();
-        self.form_subscriptions = vec![("#".to_string(), 0)];
+        self.form_version = MqttVersion::default();
+        self.form_v5_session_expiry = String::new();
+        self.form_v5_receive_maximum = String::new();
+        self.form_v5_topic_alias_max = String::new();
+        self.form_tls_use_system_roots = true;
+        self.form_tls_ca_file = String::new();
+        self.form_tls_client_cert = String::new();
+        self.form_tls_client_key = String::new();
+        self.form_tls_server_name = String::new();
+        self.form_tls_insecure = false;
+        self.form_ws_path = WebSocketSettings::default().path;
+        self.form_ws_headers = Vec::new();
+        self.form_clean_session = true;
+        self.form_keep_alive = String::new();
+        self.form_max_incoming_packet = String::new();
+        self.form_max_outgoing_packet = String::new();
+        self.form_max_inflight = String::new();
+        self.form_message_buffer = String::new();
+        self.form_will_enabled = false;
+        self.form_will_topic = String::new();
+        self.form_will_payload = String::new();
+        self.form_will_qos = 0;
+        self.form_will_retain = false;
+        self.form_will_delay = String::new();
+        self.form_will_expiry = String::new();
+        self.form_will_content_type = String::new();
+        self.form_will_response_topic = String::new();
+        self.form_will_user_properties = Vec::new();
+        self.form_reconnect_enabled = true;
+        self.form_reconnect_initial_backoff = String::new();
+        self.form_reconnect_max_backoff = String::new();
+        self.form_reconnect_jitter = String::new();
+        self.form_reconnect_max_attempts = String::new();
+        self.form_failover_endpoints = Vec::new();
+        self.form_failover_round_robin = false;
+        self.form_proxy_kind = ProxyKind::default();
+        self.form_proxy_host = String::new();
+        self.form_proxy_port = String::new();
+        self.form_proxy_username = String::new();
+        self.form_proxy_password = String::new();
+        self.form_ssh_enabled = false;
+        self.form_ssh_host = String::new();
+        self.form_ssh_port = String::new();
+        self.form_ssh_user = String::new();
+        self.form_ssh_auth = SshAuth::default();
+        self.form_ssh_key_file = String::new();
+        self.form_ssh_key_passphrase = String::new();
+        self.form_ssh_remote_host = String::new();
+        self.form_ssh_remote_port = String::new();
+        self.form_subscriptions = vec![MqttSubscription::default()];
+        self.form_subscribe_sys = false;
+        self.connection_test.clear();
+        self.next_test_id += 1;
     }
 
     fn save_form_connection(&mut self, _connect: bool) -> Option<String> {
+        let config = self.form_config();
+        let id = config.id.clone();
+
+        if self.view
+            == (View::ConnectionForm {
+                editing_id: Some(id.clone()),
+            })
+        {
+            self.config.update_connection(config);
+        } else {
+            self.config.add_connection(config);
+        }
+
+        self.save_config();
+        Some(id)
+    }
+
+    /// Connection described by the form, keeping the id of the one being edited
+    fn form_config(&self) -> crate::config::ConnectionConfig {
         use crate::config::ConnectionConfig;
 
         let port = self.form_port.parse().unwrap_or(1883);
 
-        let config = if let View::ConnectionForm {
+        if let View::ConnectionForm {
             editing_id: Some(ref id),
         } = self.view
         {
@@ -444,15 +1729,27 @@ impl MqttUi {
                 Some(self.form_password.clone())
             };
             config.protocol = self.form_protocol;
+            config.version = self.form_version;
+            config.v5 = self.form_v5_settings();
+            config.tls = self.form_tls_settings();
+            config.websocket = self.form_websocket_settings();
+            config.session = self.form_session_settings();
+            config.last_will = self.form_last_will();
+            config.reconnect = self.form_reconnect_settings();
+            config.failover = self.form_failover_settings();
+            config.proxy = self.form_proxy_settings();
+            let ssh = self.form_ssh_settings();
+            // An accepted host key only holds for the same jump host
+            let host_key = config.ssh.host_key.take();
+            if ssh.jump_host() == config.ssh.jump_host() {
+                config.ssh = SshTunnelSettings { host_key, ..ssh };
+            } else {
+                config.ssh = ssh;
+            }
+            config.credentials = self.form_credential_settings();
             config.use_custom_client_id = !self.form_client_id.is_empty();
-            config.subscriptions = self
-                .form_subscriptions
-                .iter()
-                .map(|(topic, qos)| MqttSubscription {
-                    topic: topic.clone(),
-                    qos: *qos,
-                })
-                .collect();
+            config.subscriptions = self.form_subscriptions.clone();
+            config.subscribe_sys = self.form_subscribe_sys;
             config
         } else {
             ConnectionConfig {
@@ -461,7 +1758,7 @@ impl MqttUi {
                 host: self.form_host.clone(),
                 port,
                 protocol: self.form_protocol,
-                version: Default::default(),
+                version: self.form_version,
                 client_id: if self.form_client_id.is_empty() {
                     None
                 } else {
@@ -478,33 +1775,215 @@ impl MqttUi {
                     Some(self.form_password.clone())
                 },
                 use_custom_client_id: !self.form_client_id.is_empty(),
-                subscriptions: self
-                    .form_subscriptions
-                    .iter()
-                    .map(|(topic, qos)| MqttSubscription {
-                        topic: topic.clone(),
-                        qos: *qos,
-                    })
-                    .collect(),
+                tls: self.form_tls_settings(),
+                websocket: self.form_websocket_settings(),
+                v5: self.form_v5_settings(),
+                session: self.form_session_settings(),
+                last_will: self.form_last_will(),
+                reconnect: self.form_reconnect_settings(),
+                failover: self.form_failover_settings(),
+                proxy: self.form_proxy_settings(),
+                ssh: self.form_ssh_settings(),
+                credentials: self.form_credential_settings(),
+                subscriptions: self.form_subscriptions.clone(),
+                subscribe_sys: self.form_subscribe_sys,
                 created_at: Utc::now(),
                 last_connected: None,
             }
-        };
+        }
+    }
 
-        let id = config.id.clone();
+    fn form_tls_settings(&self) -> TlsSettings {
+        TlsSettings {
+            use_system_roots: self.form_tls_use_system_roots,
+            ca_file: non_empty(&self.form_tls_ca_file),
+            client_cert_file: non_empty(&self.form_tls_client_cert),
+            client_key_file: non_empty(&self.form_tls_client_key),
+            server_name: non_empty(&self.form_tls_server_name),
+            insecure: self.form_tls_insecure,
+        }
+    }
 
-        if self.view
-            == (View::ConnectionForm {
-                editing_id: Some(id.clone()),
-            })
-        {
-            self.config.update_connection(config);
-        } else {
-            self.config.add_connection(config);
+    fn form_v5_settings(&self) -> V5Settings {
+        V5Settings {
+            session_expiry_interval: self.form_v5_session_expiry.trim().parse().ok(),
+            receive_maximum: self.form_v5_receive_maximum.trim().parse().ok(),
+            topic_alias_max: self.form_v5_topic_alias_max.trim().parse().ok(),
         }
+    }
 
-        self.save_config();
-        Some(id)
+    /// Why the keep-alive in the form can't be used as entered. rumqttc's
+    /// MQTT 5 client only takes keep-alives of 5 s or more, not even 0.
+    fn form_keep_alive_error(&self) -> Option<&'static str> {
+        let keep_alive: u16 = self.form_keep_alive.trim().parse().ok()?;
+        (self.form_version == MqttVersion::V5 && keep_alive < 5)
+            .then_some("MQTT 5 connections need a keep-alive of at least 5 s")
+    }
+
+    fn form_session_settings(&self) -> SessionSettings {
+        let defaults = SessionSettings::default();
+        SessionSettings {
+            clean_session: self.form_clean_session,
+            keep_alive_secs: self
+                .form_keep_alive
+                .trim()
+                .parse()
+                .unwrap_or(defaults.keep_alive_secs),
+            max_incoming_packet_size: self
+                .form_max_incoming_packet
+                .trim()
+                .parse()
+                .unwrap_or(defaults.max_incoming_packet_size),
+            max_outgoing_packet_size: self
+                .form_max_outgoing_packet
+                .trim()
+                .parse()
+                .unwrap_or(defaults.max_outgoing_packet_size),
+            max_inflight: self
+                .form_max_inflight
+                .trim()
+                .parse()
+                .unwrap_or(defaults.max_inflight),
+            message_buffer_mb: self
+                .form_message_buffer
+                .trim()
+                .parse()
+                .unwrap_or(defaults.message_buffer_mb),
+        }
+    }
+
+    /// Will from the form, or `None` when disabled or no topic is set
+    fn form_last_will(&self) -> Option<LastWill> {
+        let topic = non_empty(&self.form_will_topic).filter(|_| self.form_will_enabled)?;
+        Some(LastWill {
+            topic,
+            payload: self.form_will_payload.clone(),
+            qos: self.form_will_qos,
+            retain: self.form_will_retain,
+            delay_interval: self.form_will_delay.trim().parse().ok(),
+            message_expiry_interval: self.form_will_expiry.trim().parse().ok(),
+            content_type: non_empty(&self.form_will_content_type),
+            response_topic: non_empty(&self.form_will_response_topic),
+            user_properties: self
+                .form_will_user_properties
+                .iter()
+                .filter(|(name, _)| !name.trim().is_empty())
+                .map(|(name, value)| (name.trim().to_string(), value.clone()))
+                .collect(),
+        })
+    }
+
+    /// Failover nodes with a host; an unparsable port falls back to the main one
+    fn form_failover_settings(&self) -> FailoverSettings {
+        let main_port = self.form_port.parse().unwrap_or(1883);
+        FailoverSettings {
+            endpoints: self
+                .form_failover_endpoints
+                .iter()
+                .filter(|(host, _)| !host.trim().is_empty())
+                .map(|(host, port)| Endpoint {
+                    host: host.trim().to_string(),
+                    port: port.trim().parse().unwrap_or(main_port),
+                })
+                .collect(),
+            round_robin: self.form_failover_round_robin,
+        }
+    }
+
+    fn form_proxy_settings(&self) -> ProxySettings {
+        ProxySettings {
+            kind: self.form_proxy_kind,
+            host: self.form_proxy_host.trim().to_string(),
+            port: self
+                .form_proxy_port
+                .trim()
+                .parse()
+                .unwrap_or(self.form_proxy_kind.default_port()),
+            username: non_empty(&self.form_proxy_username),
+            password: non_empty(&self.form_proxy_password),
+        }
+    }
+
+    fn form_credential_settings(&self) -> CredentialSettings {
+        let jwt_defaults = JwtSettings::default();
+        let sas_defaults = SasSettings::default();
+        CredentialSettings {
+            kind: self.form_credential_kind,
+            jwt: JwtSettings {
+                algorithm: self.form_jwt_algorithm,
+                key_file: self.form_jwt_key_file.trim().to_string(),
+                claims: self.form_jwt_claims.clone(),
+                lifetime_secs: self
+                    .form_jwt_lifetime
+                    .trim()
+                    .parse()
+                    .unwrap_or(jwt_defaults.lifetime_secs),
+            },
+            sas: SasSettings {
+                key: self.form_sas_key.trim().to_string(),
+                key_name: non_empty(&self.form_sas_key_name),
+                resource_uri: non_empty(&self.form_sas_resource),
+                lifetime_secs: self
+                    .form_sas_lifetime
+                    .trim()
+                    .parse()
+                    .unwrap_or(sas_defaults.lifetime_secs),
+            },
+        }
+    }
+
+    fn form_ssh_settings(&self) -> SshTunnelSettings {
+        SshTunnelSettings {
+            enabled: self.form_ssh_enabled,
+            host: self.form_ssh_host.trim().to_string(),
+            port: self.form_ssh_port.trim().parse().unwrap_or(22),
+            user: self.form_ssh_user.trim().to_string(),
+            auth: self.form_ssh_auth,
+            key_file: non_empty(&self.form_ssh_key_file),
+            key_passphrase: (!self.form_ssh_key_passphrase.is_empty())
+                .then(|| self.form_ssh_key_passphrase.clone()),
+            remote_host: non_empty(&self.form_ssh_remote_host),
+            remote_port: self.form_ssh_remote_port.trim().parse().ok(),
+            host_key: None,
+        }
+    }
+
+    fn form_reconnect_settings(&self) -> ReconnectSettings {
+        let defaults = ReconnectSettings::default();
+        ReconnectSettings {
+            enabled: self.form_reconnect_enabled,
+            initial_backoff_ms: self
+                .form_reconnect_initial_backoff
+                .trim()
+                .parse()
+                .unwrap_or(defaults.initial_backoff_ms),
+            max_backoff_ms: self
+                .form_reconnect_max_backoff
+                .trim()
+                .parse()
+                .unwrap_or(defaults.max_backoff_ms),
+            jitter_percent: self
+                .form_reconnect_jitter
+                .trim()
+                .parse()
+                .unwrap_or(defaults.jitter_percent),
+            max_attempts: self.form_reconnect_max_attempts.trim().parse().ok(),
+        }
+    }
+
+    fn form_websocket_settings(&self) -> WebSocketSettings {
+        WebSocketSettings {
+            path: self.form_ws_path.trim().to_string(),
+            headers: self
+                .form_ws_headers
+                .iter()
+                .filter(|(name, _)| !name.trim().is_empty())
+                .map(|(name, value)| HttpHeader {
+                    name: name.trim().to_string(),
+                    value: value.clone(),
+                })
+                .collect(),
+        }
     }
 
     fn open_connection(&mut self, id: &str) {
@@ -538,43 +2017,47 @@ impl MqttUi {
 
     fn start_connection(&mut self, id: &str) {
         if let Some(config) = self.config.get_connection(id).cloned() {
-            let (cmd_tx, cmd_rx) = mpsc::channel();
-            // Use sync_channel with bounded capacity to prevent memory issues with high message volume
-            let (evt_tx, evt_rx) = mpsc::sync_channel(1000);
+            let budget = config.session.message_buffer_mb as usize * 1024 * 1024;
+            self.next_worker_id += 1;
 
             let conn_state = ConnectionState {
                 config: config.clone(),
                 status: ConnectionStatus::Connecting,
+                last_reason: None,
+                node: None,
+                subscriptions: config
+                    .session_subscriptions()
+                    .into_iter()
+                    .map(|sub| ActiveSubscription {
+                        topic: sub.filter(),
+                        qos: sub.qos,
+                        status: SubscriptionStatus::Pending,
+                    })
+                    .collect(),
                 messages: Vec::new(),
-                command_tx: Some(cmd_tx),
-                event_rx: Some(evt_rx),
+                command_tx: None,
+                inbox: Arc::new(mqtt_worker::Inbox::new(budget)),
+                worker_id: Some(self.next_worker_id),
+                publishes: Vec::new(),
+                stats: TrafficStats::default(),
+                broker: BrokerInfo::default(),
+                jobs: Vec::new(),
+                unknown_host_key: None,
             };
 
+            // The worker itself is started by `subscription`
             self.connections.insert(id.to_string(), conn_state);
+            // Subscriptions end with the old worker; bridges subscribe
+            // again once the new one is ready
+            for bridge in self.bridges.iter_mut().filter(|b| b.source == id) {
+                bridge.subscribed = false;
+            }
 
             // Update last connected time
             if let Some(cfg) = self.config.get_connection_mut(id) {
                 cfg.last_connected = Some(Utc::now());
             }
             self.save_config();
-
-            // Spawn MQTT worker with panic handling
-            let evt_tx_panic = evt_tx.clone();
-            thread::spawn(move || {
-                let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
-                    mqtt_worker::run_mqtt_worker(config, cmd_rx, evt_tx);
-                }));
-                if let Err(e) = result {
-                    let msg = if let Some(s) = e.downcast_ref::<&str>() {
-                        s.to_string()
-                    } else if let Some(s) = e.downcast_ref::<String>() {
-                        s.clone()
-                    } else {
-                        "Unknown panic in MQTT worker".to_string()
-                    };
-                    let _ = evt_tx_panic.try_send(MqttEvent::Error(format!("Worker crashed: {}", msg)));
-                }
-            });
         }
     }
 
@@ -585,50 +2068,368 @@ impl MqttUi {
             }
             conn.status = ConnectionStatus::Disconnected;
             conn.command_tx = None;
-            conn.event_rx = None;
+            conn.worker_id = None;
+            fail_pending_publishes(conn);
+        }
+        if let Some(clear) = self.retained_clears.get_mut(id) {
+            clear.fail_pending("Disconnected");
         }
     }
 
-    fn poll_connections(&mut self) {
-        const MAX_MESSAGES_PER_TICK: usize = 50;
-        let ids: Vec<String> = self.connections.keys().cloned().collect();
-
-        for id in ids {
-            if let Some(conn) = self.connections.get_mut(&id) {
-                if let Some(rx) = &conn.event_rx {
-                    let mut msg_count = 0;
-                    while let Ok(event) = rx.try_recv() {
-                        match event {
-                            MqttEvent::Connected => {
-                                conn.status = ConnectionStatus::Connected;
-                            }
-                            MqttEvent::Disconnected => {
-                                conn.status = ConnectionStatus::Disconnected;
-                            }
-                            MqttEvent::Message(msg) => {
-                                msg_count += 1;
-                                // Update selected message if this topic is selected
-                                if let Some(Some(selected_topic)) = self.selected_topics.get(&id) {
-                                    if selected_topic == &msg.topic {
-                                        self.selected_messages.insert(id.clone(), Some(msg.clone()));
-                                    }
-                                }
-                                // Store in topic tree (has per-topic ring buffer of 100 msgs)
-                                let tree = self.topic_trees.entry(id.clone()).or_default();
-                                tree.insert(msg);
-                                // Limit messages per tick to prevent UI overload
-                                if msg_count >= MAX_MESSAGES_PER_TICK {
-                                    break;
-                                }
-                            }
-                            MqttEvent::Error(e) => {
-                                conn.status = ConnectionStatus::Error(e);
-                            }
+    fn handle_mqtt_event(&mut self, id: String, event: MqttEvent) -> Task<Message> {
+        let Some(conn) = self.connections.get_mut(&id) else {
+            return Task::none();
+        };
+        match event {
+            MqttEvent::Ready(tx) => {
+                conn.command_tx = Some(tx);
+                // Running bridges need their subscription on the new worker
+                self.subscribe_bridges(&id);
+            }
+            MqttEvent::Connected(node) => {
+                conn.status = ConnectionStatus::Connected;
+                conn.node = Some(node);
+            }
+            MqttEvent::Disconnected => {
+                // The worker always ends with this, also after an error
+                if !matches!(conn.status, ConnectionStatus::Error(_)) {
+                    conn.status = ConnectionStatus::Disconnected;
+                }
+                conn.node = None;
+                fail_pending_publishes(conn);
+                if let Some(clear) = self.retained_clears.get_mut(&id) {
+                    clear.fail_pending("Disconnected");
+                }
+            }
+            MqttEvent::Error(e) => {
+                if let Some(clear) = self.retained_clears.get_mut(&id) {
+                    clear.fail_pending(&e);
+                }
+                conn.status = ConnectionStatus::Error(e);
+                conn.node = None;
+                fail_pending_publishes(conn);
+            }
+            MqttEvent::Messages => {
+                if self.drain_inbox(&id) {
+                    // Let the UI redraw before taking the rest
+                    return Task::done(Message::MqttEvent(id, MqttEvent::Messages));
+                }
+            }
+            MqttEvent::Reconnecting {
+                attempt,
+                next_retry,
+            } => {
+                conn.status = ConnectionStatus::Reconnecting {
+                    attempt,
+                    next_retry,
+                };
+                conn.node = None;
+            }
+            MqttEvent::Reason(reason) => {
+                conn.last_reason = Some(reason);
+            }
+            MqttEvent::UnknownHostKey(unknown) => {
+                conn.unknown_host_key = Some(unknown);
+            }
+            MqttEvent::SubAck(topic, result) => {
+                if let Some(sub) = conn.subscriptions.iter_mut().find(|s| s.topic == topic) {
+                    sub.status = match result {
+                        Ok(qos) => SubscriptionStatus::Granted(qos),
+                        Err(reason) => SubscriptionStatus::Failed(reason),
+                    };
+                }
+            }
+            MqttEvent::Delivery(publish_id, status) => {
+                if status != DeliveryStatus::Pending {
+                    for job in &mut conn.jobs {
+                        if job.outstanding == Some(publish_id) {
+                            job.outstanding = None;
                         }
                     }
                 }
+                // A failure is final, even if an acknowledgement follows
+                let record = conn.publishes.iter_mut().find(|p| p.id == publish_id);
+                let record = record.filter(|r| !matches!(r.status, DeliveryStatus::Failed(_)));
+                if let Some(record) = record {
+                    record.status = status.clone();
+                }
+                self.retained_cleared(&id, publish_id, status);
             }
         }
+        Task::none()
+    }
+
+    /// Drop the tree node of a topic once its empty retained publish is
+    /// delivered, or report why it was not
+    fn retained_cleared(&mut self, id: &str, publish_id: u64, status: DeliveryStatus) {
+        let Some(clear) = self.retained_clears.get_mut(id) else {
+            return;
+        };
+        if status == DeliveryStatus::Pending {
+            return;
+        }
+        let Some(topic) = clear.pending.remove(&publish_id) else {
+            return;
+        };
+        match status {
+            DeliveryStatus::Failed(reason) => {
+                clear.error = Some(format!("Could not clear {topic}: {reason}"));
+            }
+            DeliveryStatus::TimedOut => {
+                clear.error = Some(format!("Could not clear {topic}: timed out"));
+            }
+            _ => {
+                if let Some(tree) = self.topic_trees.get_mut(id) {
+                    tree.remove_topics(std::slice::from_ref(&topic));
+                    self.tree_cache_dirty.insert(id.to_string(), true);
+                }
+                let selected = self.selected_topics.get(id).cloned().flatten();
+                if selected.is_some_and(|selected| selected == topic) {
+                    self.selected_topics.remove(id);
+                    self.selected_messages.remove(id);
+                }
+            }
+        }
+        if clear.pending.is_empty() && clear.error.is_none() {
+            self.retained_clears.remove(id);
+        }
+    }
+
+    /// Move buffered messages into the topic tree for a while, so the UI stays
+    /// responsive. Returns `true` if messages are still waiting.
+    fn drain_inbox(&mut self, id: &str) -> bool {
+        const INGEST_TIME: Duration = Duration::from_millis(20);
+        const INGEST_BATCH: usize = 200;
+
+        let Some(inbox) = self.connections.get(id).map(|c| c.inbox.clone()) else {
+            return false;
+        };
+        let deadline = Instant::now() + INGEST_TIME;
+        loop {
+            let batch = inbox.take(INGEST_BATCH);
+            if batch.is_empty() {
+                return false;
+            }
+            for msg in batch {
+                self.ingest_message(id, msg);
+            }
+            self.tree_cache_dirty.insert(id.to_string(), true);
+            if Instant::now() >= deadline {
+                return !inbox.is_empty();
+            }
+        }
+    }
+
+    fn ingest_message(&mut self, id: &str, msg: MqttMessage) {
+        if let Some(conn) = self.connections.get_mut(id) {
+            conn.stats.record(msg.timestamp.timestamp(), msg.size());
+            conn.broker.update(&msg);
+
+            // Show the reply to an RPC request in the message pane
+            let call = conn
+                .publishes
+                .iter_mut()
+                .filter_map(|p| p.rpc.as_mut())
+                .find(|call| call.reply.is_none() && call.matches(&msg));
+            if let Some(call) = call {
+                call.reply = Some(RpcReply {
+                    timestamp: msg.timestamp,
+                    round_trip: call.sent_at.elapsed(),
+                });
+                self.selected_topics
+                    .insert(id.to_string(), Some(msg.topic.clone()));
+            }
+        }
+        // Update selected message if this topic is selected
+        if let Some(Some(selected_topic)) = self.selected_topics.get(id) {
+            if selected_topic == &msg.topic {
+                self.selected_messages
+                    .insert(id.to_string(), Some(msg.clone()));
+            }
+        }
+        self.forward_to_bridges(id, &msg);
+        // Store in topic tree (has per-topic ring buffer of 100 msgs)
+        let tree = self.topic_trees.entry(id.to_string()).or_default();
+        tree.insert(msg);
+    }
+
+    /// Publish `msg` on the target of every running bridge from `source`
+    fn forward_to_bridges(&mut self, source: &str, msg: &MqttMessage) {
+        if self.bridge_echoes.is_echo(source, msg) {
+            return;
+        }
+        let bridges = self
+            .bridges
+            .iter_mut()
+            .filter(|b| b.running && b.source == source);
+        for bridge in bridges {
+            let Some(forward) = bridge.rule.forward(msg) else {
+                continue;
+            };
+            // A bridge back into its own filter would forward forever
+            if bridge.target == source && topic_matches(&bridge.rule.filter, &forward.topic) {
+                continue;
+            }
+            let tx = self
+                .connections
+                .get(&bridge.target)
+                .filter(|c| matches!(c.status, ConnectionStatus::Connected))
+                .and_then(|c| c.command_tx.as_ref());
+            self.next_publish_id += 1;
+            let request = PublishRequest {
+                id: self.next_publish_id,
+                topic: forward.topic.clone(),
+                payload: msg.payload.clone(),
+                qos: forward.qos,
+                retain: forward.retain,
+                properties: msg.properties.clone(),
+            };
+            if tx.is_some_and(|tx| tx.send(MqttCommand::Publish(request)).is_ok()) {
+                self.bridge_echoes
+                    .record(&bridge.target, &forward.topic, &msg.payload);
+                bridge.forwarded += 1;
+                bridge.forwarded_bytes += msg.payload.len() as u64;
+                bridge.last_forwarded = Some(msg.timestamp);
+            } else {
+                bridge.dropped += 1;
+            }
+        }
+    }
+
+    fn start_bridge(&mut self, id: u64) {
+        let Some(bridge) = self.bridges.iter_mut().find(|b| b.id == id) else {
+            return;
+        };
+        bridge.running = true;
+        let Some(conn) = self.connections.get_mut(&bridge.source) else {
+            return;
+        };
+        if let Some(tx) = &conn.command_tx {
+            // Subscribe at the highest QoS the bridge publishes with
+            let qos = match bridge.rule.qos {
+                BridgeQos::Keep => 2,
+                BridgeQos::Set(qos) => qos,
+            };
+            let filter = &bridge.rule.filter;
+            bridge.subscribed = subscribe_unless_covered(&mut conn.subscriptions, tx, filter, qos);
+        }
+    }
+
+    /// Stop forwarding, and drop the subscription the bridge added unless
+    /// another running bridge still uses it
+    fn stop_bridge(&mut self, id: u64) {
+        let Some(bridge) = self.bridges.iter_mut().find(|b| b.id == id) else {
+            return;
+        };
+        bridge.running = false;
+        if !std::mem::take(&mut bridge.subscribed) {
+            return;
+        }
+        let (source, filter) = (bridge.source.clone(), bridge.rule.filter.clone());
+        let same_filter = self
+            .bridges
+            .iter_mut()
+            .find(|b| b.running && b.source == source && b.rule.filter == filter);
+        if let Some(other) = same_filter {
+            other.subscribed = true;
+            return;
+        }
+        if let Some(conn) = self.connections.get_mut(&source) {
+            if let Some(tx) = &conn.command_tx {
+                let _ = tx.send(MqttCommand::Unsubscribe(filter.clone()));
+            }
+            conn.subscriptions.retain(|s| s.topic != filter);
+        }
+        // Bridges the dropped subscription covered need one of their own
+        self.subscribe_bridges(&source);
+    }
+
+    /// Subscribe for running bridges from `source` that have no
+    /// subscription of their own
+    fn subscribe_bridges(&mut self, source: &str) {
+        let uncovered: Vec<u64> = self
+            .bridges
+            .iter()
+            .filter(|b| b.running && b.source == source && !b.subscribed)
+            .map(|b| b.id)
+            .collect();
+        for id in uncovered {
+            self.start_bridge(id);
+        }
+    }
+
+    /// Point the saved connection for the embedded broker at it, adding the
+    /// connection the first time
+    fn register_local_broker(&mut self, mut settings: EmbeddedBrokerSettings) {
+        let connections = &mut self.config.connections;
+        let existing = settings
+            .connection_id
+            .as_ref()
+            .and_then(|id| connections.iter().position(|c| &c.id == id));
+        let index = existing.unwrap_or_else(|| {
+            let config = ConnectionConfig::new("Local broker", "localhost", settings.port);
+            settings.connection_id = Some(config.id.clone());
+            connections.push(config);
+            connections.len() - 1
+        });
+        let config = &mut connections[index];
+        config.protocol = MqttProtocol::Mqtt;
+        config.host = "localhost".to_string();
+        config.port = settings.port;
+        config.version = MqttVersion::V311;
+        config.username = settings.username.clone();
+        config.password = settings.password.clone();
+        self.config.embedded_broker = settings;
+        self.save_config();
+    }
+
+    /// A job from the publish panel's topic, payload and schedule
+    fn publish_job(&mut self) -> Result<PublishJob, String> {
+        let topic = self.publish_topic.trim();
+        if topic.is_empty() {
+            return Err("Topic is required".to_string());
+        }
+        let interval = match self.publish_interval.trim().parse::<u64>() {
+            Ok(ms) if ms >= MIN_JOB_INTERVAL_MS => Duration::from_millis(ms),
+            _ => {
+                return Err(format!(
+                    "Interval must be at least {} ms",
+                    MIN_JOB_INTERVAL_MS
+                ))
+            }
+        };
+        let count = match non_empty(&self.publish_times) {
+            None => None,
+            Some(times) => match times.parse::<u64>() {
+                Ok(times) if times > 0 => Some(times),
+                _ => return Err("Times must be a positive number".to_string()),
+            },
+        };
+        let start_at = match non_empty(&self.publish_start_at) {
+            None => None,
+            Some(time) => Some(parse_start_time(&time)?),
+        };
+
+        self.next_job_id += 1;
+        Ok(PublishJob {
+            id: self.next_job_id,
+            topic: topic.to_string(),
+            payload: self.publish_payload.as_bytes().to_vec(),
+            qos: self.publish_qos,
+            retain: self.publish_retain,
+            interval,
+            count,
+            start_at,
+            sent: 0,
+            paused: false,
+            outstanding: None,
+            skipped: 0,
+        })
+    }
+
+    fn publish_job_mut(&mut self, conn_id: &str, job_id: u64) -> Option<&mut PublishJob> {
+        let conn = self.connections.get_mut(conn_id)?;
+        conn.jobs.iter_mut().find(|j| j.id == job_id)
     }
 
     fn save_config(&mut self) {
@@ -636,3 +2437,104 @@ impl MqttUi {
         let _ = self.config.save();
     }
 }
+
+/// Publishes the worker can no longer report on once it has stopped
+fn fail_pending_publishes(conn: &mut ConnectionState) {
+    for record in &mut conn.publishes {
+        if record.status == DeliveryStatus::Pending {
+            record.status = DeliveryStatus::Failed("Disconnected".to_string());
+        }
+    }
+    for job in &mut conn.jobs {
+        job.outstanding = None;
+    }
+}
+
+/// A new RPC request with a fresh correlation id. MQTT 3.1.1 has no
+/// correlation data property, so the id goes in a payload field instead.
+fn rpc_call(
+    config: &crate::config::ConnectionConfig,
+    reply_topic: &str,
+    correlation_field: &str,
+) -> RpcCall {
+    let correlation_field = (config.version == MqttVersion::V311).then(|| {
+        non_empty(correlation_field).unwrap_or_else(|| DEFAULT_CORRELATION_FIELD.to_string())
+    });
+    RpcCall {
+        reply_topic: non_empty(reply_topic)
+            .unwrap_or_else(|| format!("mqttui/reply/{}", config.id)),
+        correlation_id: uuid::Uuid::new_v4().to_string(),
+        correlation_field,
+        sent_at: Instant::now(),
+        reply: None,
+    }
+}
+
+/// Subscribe to `topic` unless a subscription that hasn't failed already
+/// covers it. Returns `true` if a subscription was added.
+fn subscribe_unless_covered(
+    subscriptions: &mut Vec<ActiveSubscription>,
+    tx: &mpsc::UnboundedSender<MqttCommand>,
+    topic: &str,
+    qos: u8,
+) -> bool {
+    let covered = subscriptions.iter().any(|s| {
+        !matches!(s.status, SubscriptionStatus::Failed(_)) && filter_covers(&s.topic, topic)
+    });
+    if covered {
+        return false;
+    }
+    let subscription = MqttSubscription::new(topic, qos);
+    if tx.send(MqttCommand::Subscribe(subscription)).is_err() {
+        return false;
+    }
+    subscriptions.push(ActiveSubscription {
+        topic: topic.to_string(),
+        qos,
+        status: SubscriptionStatus::Pending,
+    });
+    true
+}
+
+/// Local `YYYY-MM-DD HH:MM[:SS]`, or a time of day for its next occurrence
+fn parse_start_time(value: &str) -> Result<DateTime<Utc>, String> {
+    let now = Local::now();
+    let date_time = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok());
+    let date_time = match date_time {
+        Some(date_time) => date_time,
+        None => {
+            let time = ["%H:%M:%S", "%H:%M"]
+                .iter()
+                .find_map(|format| NaiveTime::parse_from_str(value, format).ok())
+                .ok_or_else(|| format!("Can't read start time \"{}\"", value))?;
+            let today = now.date_naive().and_time(time);
+            if today > now.naive_local() {
+                today
+            } else {
+                today + chrono::Duration::days(1)
+            }
+        }
+    };
+    date_time
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|at| at.with_timezone(&Utc))
+        .ok_or_else(|| format!("{} doesn't exist in the local time zone", value))
+}
+
+/// Form text for an optional numeric setting
+fn optional_number<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// Trimmed form value, or `None` if the field was left blank
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
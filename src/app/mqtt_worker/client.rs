@@ -0,0 +1,504 @@
+//! Thin wrappers hiding whether a connection speaks MQTT 3.1.1 or 5.0
+
+use std::fmt::Debug;
+use std::time::Duration;
+
+use anyhow::bail;
+use http::{HeaderName, HeaderValue};
+use rumqttc::v5::mqttbytes::v5::{
+    ConnectProperties, Filter, LastWill as LastWillV5, LastWillProperties, Packet as PacketV5,
+    PublishProperties, RetainForwardRule, SubscribeProperties,
+    SubscribeReasonCode as SubscribeReasonCodeV5,
+};
+use rumqttc::v5::{ConnectionError as ConnectionErrorV5, StateError as StateErrorV5};
+use rumqttc::{Outgoing, Packet, SubscribeReasonCode, Transport};
+
+use crate::config::{ConnectionConfig, MqttVersion, RetainHandling, Subscription};
+use crate::mqtt::{MessageProperties, MqttMessage};
+
+/// Request channel capacity between the client handles and the event loop
+const REQUEST_CAPACITY: usize = 500;
+
+/// Everything needed to open one connection attempt
+pub struct ConnectTarget<'a> {
+    pub client_id: &'a str,
+    /// Host name, or the full URL for WebSocket transports
+    pub broker_addr: String,
+    pub port: u16,
+    pub transport: Transport,
+    pub proxy: Option<rumqttc::Proxy>,
+    /// Username and password, generated afresh for each attempt
+    pub credentials: Option<(String, String)>,
+    pub upgrade_headers: &'a [(HeaderName, HeaderValue)],
+}
+
+/// Connection events the worker reacts to, independent of protocol version
+pub enum Event {
+    /// `reason` is only reported by v5 brokers
+    ConnAck {
+        session_present: bool,
+        reason: Option<String>,
+    },
+    Publish(MqttMessage),
+    /// A queued PUBLISH went out with this packet id, 0 for QoS 0
+    PublishSent(u16),
+    /// Successful acknowledgement of one of our publishes
+    PublishAck(Ack, u16),
+    /// A queued SUBSCRIBE went out with this packet id
+    SubscribeSent(u16),
+    /// Granted QoS or failure reason per filter of the SUBSCRIBE, in request order
+    SubAck {
+        pkid: u16,
+        results: Vec<Result<u8, String>>,
+    },
+    Disconnect {
+        reason: Option<String>,
+    },
+    Other,
+}
+
+/// Acknowledgements for QoS 1 (PUBACK) and QoS 2 (PUBREC, then PUBCOMP)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
+pub enum Ack {
+    PubAck,
+    PubRec,
+    PubComp,
+}
+
+/// Why polling the event loop failed
+pub struct PollError {
+    pub message: String,
+    /// Set when a v5 broker answered one of our publishes with a failure
+    /// reason code, which rumqttc reports as a connection error
+    pub rejected: Option<Ack>,
+}
+
+#[derive(Clone)]
+pub enum Client {
+    V311(rumqttc::AsyncClient),
+    V5(rumqttc::v5::AsyncClient),
+}
+
+pub enum Connection {
+    V311(Box<rumqttc::EventLoop>),
+    /// With the configured limit on outgoing packet sizes, which rumqttc's
+    /// v5 client only takes from the broker
+    V5(Box<rumqttc::v5::EventLoop>, u32),
+}
+
+/// Create a client for the configured protocol version. Nothing is sent
+/// until the connection is polled.
+pub fn create(config: &ConnectionConfig, target: ConnectTarget<'_>) -> (Client, Connection) {
+    match config.version {
+        MqttVersion::V311 => {
+            let options = options_v311(config, target);
+            let (client, connection) = rumqttc::AsyncClient::new(options, REQUEST_CAPACITY);
+            (Client::V311(client), Connection::V311(Box::new(connection)))
+        }
+        MqttVersion::V5 => {
+            let options = options_v5(config, target);
+            let (client, connection) = rumqttc::v5::AsyncClient::new(options, REQUEST_CAPACITY);
+            let connection = Connection::V5(
+                Box::new(connection),
+                config.session.max_outgoing_packet_size,
+            );
+            (Client::V5(client), connection)
+        }
+    }
+}
+
+fn options_v311(config: &ConnectionConfig, target: ConnectTarget<'_>) -> rumqttc::MqttOptions {
+    let session = &config.session;
+    let mut options = rumqttc::MqttOptions::new(target.client_id, target.broker_addr, target.port);
+    options.set_keep_alive(Duration::from_secs(session.keep_alive_secs.into()));
+    options.set_clean_session(session.clean_session);
+    options.set_max_packet_size(
+        session.max_incoming_packet_size as usize,
+        session.max_outgoing_packet_size as usize,
+    );
+    options.set_inflight(session.max_inflight.max(1));
+    options.set_transport(target.transport);
+    if let Some(proxy) = target.proxy {
+        options.set_proxy(proxy);
+    }
+    if !target.upgrade_headers.is_empty() {
+        let headers = target.upgrade_headers.to_vec();
+        options.set_request_modifier(move |mut request| {
+            request.headers_mut().extend(headers.clone());
+            async move { request }
+        });
+    }
+    if let Some((username, password)) = target.credentials {
+        options.set_credentials(username, password);
+    }
+    if let Some(will) = &config.last_will {
+        options.set_last_will(rumqttc::LastWill::new(
+            &will.topic,
+            will.payload.as_bytes(),
+            qos_v311(will.qos),
+            will.retain,
+        ));
+    }
+    options
+}
+
+fn options_v5(config: &ConnectionConfig, target: ConnectTarget<'_>) -> rumqttc::v5::MqttOptions {
+    let session = &config.session;
+    let mut options =
+        rumqttc::v5::MqttOptions::new(target.client_id, target.broker_addr, target.port);
+    // rumqttc's v5 client panics on keep-alives under 5 s. The connection
+    // form refuses them, so only hand-edited configs are raised here.
+    options.set_keep_alive(Duration::from_secs(session.keep_alive_secs.max(5).into()));
+    options.set_clean_start(session.clean_session);
+    options.set_outgoing_inflight_upper_limit(session.max_inflight.max(1));
+    options.set_transport(target.transport);
+    if let Some(proxy) = target.proxy {
+        options.set_proxy(proxy);
+    }
+    if !target.upgrade_headers.is_empty() {
+        let headers = target.upgrade_headers.to_vec();
+        options.set_request_modifier(move |mut request| {
+            request.headers_mut().extend(headers.clone());
+            async move { request }
+        });
+    }
+    if let Some((username, password)) = target.credentials {
+        options.set_credentials(username, password);
+    }
+    if let Some(will) = &config.last_will {
+        let properties = LastWillProperties {
+            delay_interval: will.delay_interval,
+            payload_format_indicator: None,
+            message_expiry_interval: will.message_expiry_interval,
+            content_type: will.content_type.clone(),
+            response_topic: will.response_topic.clone(),
+            correlation_data: None,
+            user_properties: will.user_properties.clone(),
+        };
+        options.set_last_will(LastWillV5::new(
+            &will.topic,
+            will.payload.as_bytes(),
+            qos_v5(will.qos),
+            will.retain,
+            Some(properties),
+        ));
+    }
+
+    let mut properties = ConnectProperties::new();
+    properties.session_expiry_interval = config.v5.session_expiry_interval;
+    properties.receive_maximum = config.v5.receive_maximum;
+    properties.max_packet_size = Some(session.max_incoming_packet_size);
+    properties.topic_alias_max = config.v5.topic_alias_max;
+    options.set_connect_properties(properties);
+    options
+}
+
+impl Client {
+    /// Queue a subscribe without blocking when the request channel is full
+    pub fn try_subscribe(&self, subscription: &Subscription) -> anyhow::Result<()> {
+        let topic = subscription.filter();
+        let qos = subscription.qos;
+        match self {
+            Client::V311(c) => c.try_subscribe(topic, qos_v311(qos))?,
+            Client::V5(c) => {
+                let filter = Filter {
+                    path: topic,
+                    qos: qos_v5(qos),
+                    nolocal: subscription.no_local,
+                    preserve_retain: subscription.retain_as_published,
+                    retain_forward_rule: match subscription.retain_handling {
+                        RetainHandling::SendOnSubscribe => RetainForwardRule::OnEverySubscribe,
+                        RetainHandling::SendIfNew => RetainForwardRule::OnNewSubscribe,
+                        RetainHandling::DoNotSend => RetainForwardRule::Never,
+                    },
+                };
+                match subscription.identifier {
+                    Some(id) => {
+                        let properties = SubscribeProperties {
+                            id: Some(id as usize),
+                            user_properties: Vec::new(),
+                        };
+                        c.try_subscribe_many_with_properties([filter], properties)?
+                    }
+                    None => c.try_subscribe_many([filter])?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn try_unsubscribe(&self, topic: &str) -> anyhow::Result<()> {
+        match self {
+            Client::V311(c) => c.try_unsubscribe(topic)?,
+            Client::V5(c) => c.try_unsubscribe(topic)?,
+        }
+        Ok(())
+    }
+
+    /// Queue a publish, waiting while the request channel is full
+    pub async fn publish(
+        &self,
+        topic: &str,
+        payload: Vec<u8>,
+        qos: u8,
+        retain: bool,
+        properties: Option<MessageProperties>,
+    ) -> anyhow::Result<()> {
+        match self {
+            Client::V311(c) => c.publish(topic, qos_v311(qos), retain, payload).await?,
+            Client::V5(c) => match properties {
+                Some(p) => {
+                    c.publish_with_properties(topic, qos_v5(qos), retain, payload, p.into())
+                        .await?
+                }
+                None => c.publish(topic, qos_v5(qos), retain, payload).await?,
+            },
+        }
+        Ok(())
+    }
+
+    /// Queue a publish without waiting. Fails if the request channel is
+    /// full, i.e. publishes come in faster than the connection sends them.
+    pub fn try_publish(
+        &self,
+        topic: &str,
+        payload: Vec<u8>,
+        qos: u8,
+        retain: bool,
+        properties: Option<MessageProperties>,
+    ) -> anyhow::Result<()> {
+        // rumqttc reports invalid topics like a full channel
+        if topic.contains(['+', '#']) {
+            bail!("Can't publish to {}, it contains a wildcard", topic);
+        }
+        let queued = match self {
+            Client::V311(c) => c.try_publish(topic, qos_v311(qos), retain, payload).is_ok(),
+            Client::V5(c) => match properties {
+                Some(p) => c
+                    .try_publish_with_properties(topic, qos_v5(qos), retain, payload, p.into())
+                    .is_ok(),
+                None => c.try_publish(topic, qos_v5(qos), retain, payload).is_ok(),
+            },
+        };
+        if !queued {
+            bail!("Publishes are backed up, the connection can't keep up");
+        }
+        Ok(())
+    }
+
+    pub async fn disconnect(&self) -> anyhow::Result<()> {
+        match self {
+            Client::V311(c) => c.disconnect().await?,
+            Client::V5(c) => c.disconnect().await?,
+        }
+        Ok(())
+    }
+}
+
+impl Connection {
+    /// Set up the next reconnect, e.g. for another node or with fresh
+    /// credentials. Pending publishes and other session state carry over.
+    pub fn retarget(&mut self, config: &ConnectionConfig, target: ConnectTarget<'_>) {
+        match self {
+            Connection::V311(c) => c.mqtt_options = options_v311(config, target),
+            Connection::V5(c, _) => c.options = options_v5(config, target),
+        }
+    }
+
+    /// Wait for the next broker event. Polling again after an error
+    /// reconnects.
+    pub async fn next_event(&mut self) -> Result<Event, PollError> {
+        match self {
+            Connection::V311(c) => c
+                .poll()
+                .await
+                .map(|event| match event {
+                    rumqttc::Event::Incoming(packet) => incoming_v311(packet),
+                    rumqttc::Event::Outgoing(outgoing) => outgoing_event(outgoing),
+                })
+                .map_err(|e| PollError {
+                    message: e.to_string(),
+                    rejected: None,
+                }),
+            Connection::V5(c, max_outgoing) => c
+                .poll()
+                .await
+                .map(|event| match event {
+                    rumqttc::v5::Event::Incoming(packet) => {
+                        // rumqttc has just applied the broker's limit, if
+                        // it announced one; keep whichever is smaller
+                        if matches!(packet, PacketV5::ConnAck(_)) {
+                            let broker = c.state.max_outgoing_packet_size;
+                            let limit = broker.map_or(*max_outgoing, |b| b.min(*max_outgoing));
+                            c.state.max_outgoing_packet_size = Some(limit);
+                        }
+                        incoming_v5(packet)
+                    }
+                    rumqttc::v5::Event::Outgoing(outgoing) => outgoing_event(outgoing),
+                })
+                .map_err(|e| {
+                    let rejected = match &e {
+                        ConnectionErrorV5::MqttState(StateErrorV5::PubAckFail { .. }) => {
+                            Some(Ack::PubAck)
+                        }
+                        ConnectionErrorV5::MqttState(StateErrorV5::PubRecFail { .. }) => {
+                            Some(Ack::PubRec)
+                        }
+                        ConnectionErrorV5::MqttState(StateErrorV5::PubCompFail { .. }) => {
+                            Some(Ack::PubComp)
+                        }
+                        _ => None,
+                    };
+                    PollError {
+                        message: e.to_string(),
+                        rejected,
+                    }
+                }),
+        }
+    }
+}
+
+fn outgoing_event(outgoing: Outgoing) -> Event {
+    match outgoing {
+        Outgoing::Publish(pkid) => Event::PublishSent(pkid),
+        Outgoing::Subscribe(pkid) => Event::SubscribeSent(pkid),
+        _ => Event::Other,
+    }
+}
+
+fn incoming_v311(packet: Packet) -> Event {
+    match packet {
+        Packet::ConnAck(connack) => Event::ConnAck {
+            session_present: connack.session_present,
+            reason: None,
+        },
+        Packet::Publish(publish) => Event::Publish(MqttMessage::new(
+            publish.topic,
+            publish.payload.to_vec(),
+            publish.qos as u8,
+            publish.retain,
+        )),
+        Packet::PubAck(puback) => Event::PublishAck(Ack::PubAck, puback.pkid),
+        Packet::PubRec(pubrec) => Event::PublishAck(Ack::PubRec, pubrec.pkid),
+        Packet::PubComp(pubcomp) => Event::PublishAck(Ack::PubComp, pubcomp.pkid),
+        Packet::SubAck(suback) => Event::SubAck {
+            pkid: suback.pkid,
+            results: suback
+                .return_codes
+                .iter()
+                .map(|code| match code {
+                    SubscribeReasonCode::Success(qos) => Ok(*qos as u8),
+                    SubscribeReasonCode::Failure => Err("Failure".to_string()),
+                })
+                .collect(),
+        },
+        Packet::Disconnect => Event::Disconnect { reason: None },
+        _ => Event::Other,
+    }
+}
+
+fn incoming_v5(packet: PacketV5) -> Event {
+    match packet {
+        PacketV5::ConnAck(connack) => Event::ConnAck {
+            session_present: connack.session_present,
+            reason: Some(reason_text(
+                connack.code,
+                connack.properties.and_then(|p| p.reason_string),
+            )),
+        },
+        PacketV5::Publish(publish) => {
+            let message = MqttMessage::new(
+                String::from_utf8_lossy(&publish.topic).to_string(),
+                publish.payload.to_vec(),
+                publish.qos as u8,
+                publish.retain,
+            );
+            match publish.properties {
+                Some(properties) => Event::Publish(message.with_properties(properties.into())),
+                None => Event::Publish(message),
+            }
+        }
+        // Failure reason codes surface as connection errors instead
+        PacketV5::PubAck(puback) => Event::PublishAck(Ack::PubAck, puback.pkid),
+        PacketV5::PubRec(pubrec) => Event::PublishAck(Ack::PubRec, pubrec.pkid),
+        PacketV5::PubComp(pubcomp) => Event::PublishAck(Ack::PubComp, pubcomp.pkid),
+        PacketV5::SubAck(suback) => {
+            let reason_string = suback.properties.and_then(|p| p.reason_string);
+            Event::SubAck {
+                pkid: suback.pkid,
+                results: suback
+                    .return_codes
+                    .iter()
+                    .map(|code| match code {
+                        SubscribeReasonCodeV5::Success(qos) => Ok(*qos as u8),
+                        failure => Err(reason_text(failure, reason_string.clone())),
+                    })
+                    .collect(),
+            }
+        }
+        PacketV5::Disconnect(disconnect) => Event::Disconnect {
+            reason: Some(reason_text(
+                disconnect.reason_code,
+                disconnect.properties.and_then(|p| p.reason_string),
+            )),
+        },
+        _ => Event::Other,
+    }
+}
+
+/// Reason code name, followed by the broker's reason string if it sent one
+fn reason_text(code: impl Debug, reason_string: Option<String>) -> String {
+    match reason_string {
+        Some(reason) => format!("{:?} ({})", code, reason),
+        None => format!("{:?}", code),
+    }
+}
+
+impl From<PublishProperties> for MessageProperties {
+    fn from(p: PublishProperties) -> Self {
+        Self {
+            content_type: p.content_type,
+            response_topic: p.response_topic,
+            correlation_data: p.correlation_data.map(|d| d.to_vec()),
+            message_expiry_interval: p.message_expiry_interval,
+            payload_format_indicator: p.payload_format_indicator,
+            user_properties: p.user_properties,
+            subscription_identifiers: p
+                .subscription_identifiers
+                .into_iter()
+                .map(|id| id as u32)
+                .collect(),
+        }
+    }
+}
+
+impl From<MessageProperties> for PublishProperties {
+    fn from(p: MessageProperties) -> Self {
+        Self {
+            payload_format_indicator: p.payload_format_indicator,
+            message_expiry_interval: p.message_expiry_interval,
+            response_topic: p.response_topic,
+            correlation_data: p.correlation_data.map(Into::into),
+            user_properties: p.user_properties,
+            content_type: p.content_type,
+            ..Default::default()
+        }
+    }
+}
+
+fn qos_v311(qos: u8) -> rumqttc::QoS {
+    match qos {
+        0 => rumqttc::QoS::AtMostOnce,
+        1 => rumqttc::QoS::AtLeastOnce,
+        _ => rumqttc::QoS::ExactlyOnce,
+    }
+}
+
+fn qos_v5(qos: u8) -> rumqttc::v5::mqttbytes::QoS {
+    match qos {
+        0 => rumqttc::v5::mqttbytes::QoS::AtMostOnce,
+        1 => rumqttc::v5::mqttbytes::QoS::AtLeastOnce,
+        _ => rumqttc::v5::mqttbytes::QoS::ExactlyOnce,
+    }
+}
@@ -0,0 +1,282 @@
+//! Connection test that runs each layer of the connection as its own timed
+//! stage, so a failure points at DNS, TCP, TLS, the broker or permissions
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context};
+use iced::futures::channel::mpsc::Sender;
+use iced::futures::{SinkExt, Stream};
+use rumqttc::tokio_rustls::rustls::pki_types::ServerName;
+use rumqttc::tokio_rustls::TlsConnector;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+
+use crate::app::types::{StageResult, TestStage};
+use crate::config::{ConnectionConfig, Endpoint};
+use crate::mqtt::{credentials, tls};
+
+use super::client::{self, Client, ConnectTarget, Connection, Event};
+use super::{build_transport, proxy, ssh, upgrade_headers, Route};
+
+/// A connected client, with the route it may depend on
+pub type Session = (Client, Connection, Route);
+
+/// The link to the broker as far as it has been set up
+trait Link: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Link for T {}
+
+/// Longest any single stage may take
+const STAGE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Results for each stage as it starts and finishes. Stages after a failure
+/// are reported as skipped.
+pub fn test_connection(config: ConnectionConfig) -> impl Stream<Item = (TestStage, StageResult)> {
+    iced::stream::channel(10, move |mut output| async move {
+        if let Err(failed) = run_stages(&config, &mut output).await {
+            for stage in TestStage::ALL.into_iter().filter(|stage| *stage > failed) {
+                let result = StageResult::Skipped("Not run".to_string());
+                let _ = output.send((stage, result)).await;
+            }
+        }
+    })
+}
+
+async fn run_stages(
+    config: &ConnectionConfig,
+    output: &mut Sender<(TestStage, StageResult)>,
+) -> Result<(), TestStage> {
+    // Only the first hop is resolved and connected to directly: the proxy,
+    // else the SSH jump host, else the broker
+    let endpoint = &config.endpoints()[0];
+    let proxy = &config.proxy;
+    let ssh = &config.ssh;
+    let next_hop = if ssh.is_enabled() {
+        ssh.jump_host()
+    } else {
+        endpoint.clone()
+    };
+    let first_hop = if proxy.is_enabled() {
+        Endpoint {
+            host: proxy.host.clone(),
+            port: proxy.port,
+        }
+    } else {
+        next_hop.clone()
+    };
+
+    let addrs = stage(output, TestStage::Dns, resolve(&first_hop)).await?;
+    let mut tcp = stage(output, TestStage::Tcp, connect_tcp(addrs)).await?;
+
+    if proxy.is_enabled() {
+        let tunnel = async {
+            let stream = proxy::open_tunnel(proxy, tcp, &next_hop.host, next_hop.port).await?;
+            Ok((stream, format!("{} tunnel to {}", proxy.kind, next_hop)))
+        };
+        tcp = stage(output, TestStage::Proxy, tunnel).await?;
+    } else {
+        let result = StageResult::Skipped("No proxy configured".to_string());
+        let _ = output.send((TestStage::Proxy, result)).await;
+    }
+
+    // The SSH session has to outlive the TLS handshake through it
+    let (link, _session): (Box<dyn Link>, _) = if ssh.is_enabled() {
+        let tunnel = async {
+            let session = ssh::login(ssh, tcp).await?;
+            let target = ssh.forward_target(endpoint);
+            let channel = ssh::forward(&session, &target).await?;
+            let detail = format!("Forwarding to {} as {}", target, ssh.user);
+            Ok(((Box::new(channel) as Box<dyn Link>, Some(session)), detail))
+        };
+        stage(output, TestStage::Ssh, tunnel).await?
+    } else {
+        let result = StageResult::Skipped("No SSH tunnel configured".to_string());
+        let _ = output.send((TestStage::Ssh, result)).await;
+        (Box::new(tcp), None)
+    };
+
+    if config.protocol.is_tls() {
+        stage(output, TestStage::Tls, handshake(config, link)).await?;
+    } else {
+        drop(link);
+        let result = StageResult::Skipped(format!("Not used by {}://", config.protocol.as_str()));
+        let _ = output.send((TestStage::Tls, result)).await;
+    }
+
+    let test_config = config.side_session("test");
+    let (client, mut connection, route) =
+        stage(output, TestStage::Connect, connect(&test_config)).await?;
+    let subscribed = stage(
+        output,
+        TestStage::Subscribe,
+        subscribe(config, &client, &mut connection),
+    )
+    .await;
+    close((client, connection, route)).await;
+
+    subscribed
+}
+
+/// Disconnect and take down the route
+pub async fn close((client, mut connection, route): Session) {
+    // Give the DISCONNECT a moment to go out before dropping the connection
+    let _ = client.disconnect().await;
+    let _ = tokio::time::timeout(Duration::from_secs(1), async {
+        while connection.next_event().await.is_ok() {}
+    })
+    .await;
+    route.close().await;
+}
+
+/// Run one stage under `STAGE_TIMEOUT`, reporting its progress and latency
+async fn stage<T>(
+    output: &mut Sender<(TestStage, StageResult)>,
+    stage: TestStage,
+    run: impl Future<Output = anyhow::Result<(T, String)>>,
+) -> Result<T, TestStage> {
+    let _ = output.send((stage, StageResult::Running)).await;
+
+    let started = Instant::now();
+    let outcome = match tokio::time::timeout(STAGE_TIMEOUT, run).await {
+        Ok(outcome) => outcome,
+        Err(_) => Err(anyhow::anyhow!(
+            "Timed out after {}s",
+            STAGE_TIMEOUT.as_secs()
+        )),
+    };
+    let elapsed = started.elapsed();
+
+    match outcome {
+        Ok((value, detail)) => {
+            let _ = output
+                .send((stage, StageResult::Passed { elapsed, detail }))
+                .await;
+            Ok(value)
+        }
+        Err(e) => {
+            let error = format!("{:#}", e);
+            let _ = output
+                .send((stage, StageResult::Failed { elapsed, error }))
+                .await;
+            Err(stage)
+        }
+    }
+}
+
+async fn resolve(endpoint: &Endpoint) -> anyhow::Result<(Vec<SocketAddr>, String)> {
+    let host = &endpoint.host;
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), endpoint.port))
+        .await
+        .with_context(|| format!("Could not resolve {}", host))?
+        .collect();
+    if addrs.is_empty() {
+        bail!("{} has no addresses", host);
+    }
+    let detail = addrs
+        .iter()
+        .map(|addr| addr.ip().to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    Ok((addrs, detail))
+}
+
+/// Connect to the first address that accepts, like the MQTT client does
+async fn connect_tcp(addrs: Vec<SocketAddr>) -> anyhow::Result<(TcpStream, String)> {
+    let mut last_error = None;
+    for addr in addrs {
+        match TcpStream::connect(addr).await {
+            Ok(stream) => return Ok((stream, format!("Connected to {}", addr))),
+            Err(e) => last_error = Some(anyhow::Error::new(e).context(format!("{}", addr))),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No address to connect to")))
+}
+
+async fn handshake(config: &ConnectionConfig, link: Box<dyn Link>) -> anyhow::Result<((), String)> {
+    let connector = TlsConnector::from(tls::client_config(&config.tls)?);
+    let host = config.tls_server_name().unwrap_or(&config.host);
+    let name = ServerName::try_from(host.to_string())
+        .with_context(|| format!("Invalid server name {}", host))?;
+    let stream = connector.connect(name, link).await?;
+
+    let (_, session) = stream.get_ref();
+    let version = session
+        .protocol_version()
+        .and_then(|v| v.as_str())
+        .unwrap_or("TLS");
+    let detail = match session.negotiated_cipher_suite() {
+        Some(suite) => format!("{}, {:?}", version, suite.suite()),
+        None => version.to_string(),
+    };
+    Ok(((), detail))
+}
+
+/// Open a full MQTT session with the connection's settings, up to the CONNACK
+pub async fn connect(config: &ConnectionConfig) -> anyhow::Result<(Session, String)> {
+    let transport = build_transport(config)?;
+    let upgrade_headers = upgrade_headers(config)?;
+    let route = Route::open(config).await?;
+    let client_id = config.effective_client_id();
+    let credentials = credentials::generate(config, &client_id)?;
+    let endpoint = route.pin(&config.endpoints()[0]);
+    let broker_addr = if config.protocol.is_websocket() {
+        config.websocket_url(&endpoint)
+    } else {
+        endpoint.host.clone()
+    };
+
+    let (client, mut connection) = client::create(
+        config,
+        ConnectTarget {
+            client_id: &client_id,
+            broker_addr,
+            port: endpoint.port,
+            transport,
+            proxy: route.proxy.clone(),
+            credentials,
+            upgrade_headers: &upgrade_headers,
+        },
+    );
+    loop {
+        match connection.next_event().await {
+            Ok(Event::ConnAck {
+                session_present,
+                reason,
+            }) => {
+                let mut detail = reason.unwrap_or_else(|| "Accepted".to_string());
+                if session_present {
+                    detail.push_str(", session present");
+                }
+                return Ok(((client, connection, route), detail));
+            }
+            Ok(_) => {}
+            Err(e) => bail!(e.message),
+        }
+    }
+}
+
+/// Subscribe to the first configured filter and wait for the SUBACK
+async fn subscribe(
+    config: &ConnectionConfig,
+    client: &Client,
+    connection: &mut Connection,
+) -> anyhow::Result<((), String)> {
+    let subscription = config.session_subscriptions().remove(0);
+    client.try_subscribe(&subscription)?;
+    let topic = subscription.filter();
+    loop {
+        match connection.next_event().await {
+            Ok(Event::SubAck { results, .. }) => {
+                return match results.into_iter().next() {
+                    Some(Ok(qos)) => Ok(((), format!("{} granted QoS {}", topic, qos))),
+                    Some(Err(reason)) => bail!("{} refused: {}", topic, reason),
+                    None => bail!("Empty SUBACK"),
+                };
+            }
+            Ok(_) => {}
+            Err(e) => bail!(e.message),
+        }
+    }
+}
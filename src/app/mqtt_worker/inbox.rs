@@ -0,0 +1,76 @@
+//! Received messages waiting for the UI, bounded by memory rather than count
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::mqtt::MqttMessage;
+
+pub struct Inbox {
+    /// Bytes the queued messages may take up before new ones are dropped
+    budget: usize,
+    state: Mutex<State>,
+    dropped: AtomicU64,
+}
+
+#[derive(Default)]
+struct State {
+    messages: VecDeque<MqttMessage>,
+    bytes: usize,
+}
+
+impl Inbox {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            state: Mutex::new(State::default()),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Queue a message, or count it as dropped once the budget is used up.
+    /// Returns `true` if the inbox was empty, so the UI needs waking.
+    pub fn push(&self, message: MqttMessage) -> bool {
+        let size = message.approx_size();
+        let mut state = self.lock();
+        if state.bytes + size > self.budget {
+            drop(state);
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            tracing::trace!("Message buffer full, dropping message on {}", message.topic);
+            return false;
+        }
+        state.bytes += size;
+        state.messages.push_back(message);
+        state.messages.len() == 1
+    }
+
+    /// Take up to `max` of the oldest queued messages
+    pub fn take(&self, max: usize) -> Vec<MqttMessage> {
+        let mut state = self.lock();
+        let count = max.min(state.messages.len());
+        let taken: Vec<MqttMessage> = state.messages.drain(..count).collect();
+        state.bytes -= taken.iter().map(MqttMessage::approx_size).sum::<usize>();
+        taken
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().messages.is_empty()
+    }
+
+    /// Messages discarded because the buffer was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Share of the budget currently in use, from 0 to 1
+    pub fn usage(&self) -> f32 {
+        if self.budget == 0 {
+            return 0.0;
+        }
+        self.lock().bytes as f32 / self.budget as f32
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
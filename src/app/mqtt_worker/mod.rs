@@ -0,0 +1,551 @@
+//! MQTT worker task for handling broker connections
+
+mod client;
+mod diagnostics;
+mod inbox;
+mod proxy;
+mod publishes;
+mod snapshot;
+mod ssh;
+mod subscriptions;
+
+use std::any::Any;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use chrono::Utc;
+use http::{HeaderName, HeaderValue};
+use iced::futures::channel::mpsc::Sender;
+use iced::futures::{SinkExt, Stream};
+use rumqttc::{TlsConfiguration, Transport};
+use tokio::sync::{mpsc, watch};
+
+use crate::config::{ConnectionConfig, Endpoint, MqttProtocol, MqttVersion};
+use crate::mqtt::{credentials, tls};
+
+use super::types::{DeliveryStatus, MqttCommand, MqttEvent, UnknownHostKey};
+use client::{Client, ConnectTarget, Event};
+pub use diagnostics::test_connection;
+pub use inbox::Inbox;
+use proxy::Relay;
+use publishes::{Publishes, Sent};
+pub use snapshot::{restore_snapshot, take_snapshot};
+use ssh::SshTunnel;
+use subscriptions::Subscriptions;
+
+/// How long a QoS 1/2 publish may wait for its acknowledgement
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long the connection keeps running after a disconnect is requested
+const DISCONNECT_GRACE: Duration = Duration::from_secs(1);
+
+/// Events for one connection, starting with `MqttEvent::Ready`. Received
+/// messages go to `inbox`, with `MqttEvent::Messages` sent when it fills up
+/// from empty.
+///
+/// The worker runs as its own task, so dropping the stream does not cut the
+/// connection; it disconnects once the command sender is dropped.
+pub fn run(config: ConnectionConfig, inbox: Arc<Inbox>) -> impl Stream<Item = MqttEvent> {
+    iced::stream::channel(100, move |mut output| async move {
+        let worker = tokio::spawn(run_mqtt_worker(config, inbox, output.clone()));
+        if let Err(e) = worker.await {
+            if e.is_panic() {
+                let msg = panic_message(e.into_panic());
+                let _ = output
+                    .send(MqttEvent::Error(format!("Worker crashed: {}", msg)))
+                    .await;
+            }
+        }
+    })
+}
+
+/// Run the MQTT worker - handles connection, subscriptions, and message routing
+async fn run_mqtt_worker(
+    config: ConnectionConfig,
+    inbox: Arc<Inbox>,
+    mut evt_tx: Sender<MqttEvent>,
+) {
+    let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
+    let _ = evt_tx.send(MqttEvent::Ready(cmd_tx)).await;
+
+    let result = match Route::open(&config).await {
+        Ok(mut route) => {
+            let result = run_connection(&config, &mut route, &inbox, cmd_rx, &mut evt_tx).await;
+            route.close().await;
+            result
+        }
+        Err(e) => Err(e),
+    };
+
+    // Every way out ends here, so the UI always learns the worker stopped
+    if let Err(e) = result {
+        if let Some(unknown) = e.downcast_ref::<UnknownHostKey>() {
+            let _ = evt_tx
+                .send(MqttEvent::UnknownHostKey(unknown.clone()))
+                .await;
+        }
+        let _ = evt_tx.send(MqttEvent::Error(format!("{:#}", e))).await;
+    }
+    let _ = evt_tx.send(MqttEvent::Disconnected).await;
+}
+
+/// Connect through `route` and serve the connection until it is closed or
+/// gives up. The first connection attempt is retried like any other.
+async fn run_connection(
+    config: &ConnectionConfig,
+    route: &mut Route,
+    inbox: &Inbox,
+    cmd_rx: mpsc::UnboundedReceiver<MqttCommand>,
+    evt_tx: &mut Sender<MqttEvent>,
+) -> anyhow::Result<()> {
+    let client_id = config.effective_client_id();
+    let transport = build_transport(config)?;
+    let upgrade_headers = upgrade_headers(config)?;
+
+    let endpoints = endpoints_to_try(config);
+    let target = |route: &Route, endpoint: &Endpoint, credentials| {
+        let endpoint = route.pin(endpoint);
+        ConnectTarget {
+            client_id: &client_id,
+            // WebSocket transports take the full URL in place of the host
+            broker_addr: if config.protocol.is_websocket() {
+                config.websocket_url(&endpoint)
+            } else {
+                endpoint.host.clone()
+            },
+            port: endpoint.port,
+            transport: transport.clone(),
+            proxy: route.proxy.clone(),
+            credentials,
+            upgrade_headers: &upgrade_headers,
+        }
+    };
+
+    let mut current = 0;
+    let credentials = credentials::generate(config, &client_id)?;
+    let (client, mut connection) =
+        client::create(config, target(route, &endpoints[current], credentials));
+
+    // Set once the connection is being closed, so dropped links are not retried
+    let (stop_tx, mut stop_rx) = watch::channel(false);
+
+    let initial = config.session_subscriptions();
+    let subscriptions = Arc::new(Subscriptions::new(client.clone(), initial));
+    let publishes = Arc::new(Publishes::default());
+
+    tokio::spawn(handle_commands(
+        cmd_rx,
+        client,
+        subscriptions.clone(),
+        publishes.clone(),
+        evt_tx.clone(),
+        stop_tx,
+    ));
+
+    // Event loop with error recovery. rumqttc reconnects on the next poll
+    // after an error, so retrying is just a matter of waiting and polling again.
+    let mut attempt = 0;
+    let mut connected = false;
+    let mut connected_before = false;
+    // Node to try when the connection next drops
+    let mut next_endpoint = 1 % endpoints.len();
+    loop {
+        // Closing shouldn't wait for an attempt to time out or a backlog of
+        // publishes to drain
+        let next = tokio::select! {
+            next = connection.next_event() => Some(next),
+            _ = stop_rx.wait_for(|stopped| *stopped) => None,
+        };
+        let Some(next) = next else {
+            if connected {
+                // Give the DISCONNECT a moment to go out
+                let _ = tokio::time::timeout(DISCONNECT_GRACE, async {
+                    while connection.next_event().await.is_ok() {}
+                })
+                .await;
+            }
+            break;
+        };
+        match next {
+            Ok(event) => {
+                if let Event::ConnAck {
+                    session_present, ..
+                } = &event
+                {
+                    if attempt > 0 {
+                        tracing::info!("Connected after {} attempt(s)", attempt + 1);
+                    }
+                    tracing::info!("Connected to MQTT broker at {}", endpoints[current]);
+                    let node = endpoints[current].to_string();
+                    let _ = evt_tx.send(MqttEvent::Connected(node)).await;
+                    next_endpoint = if config.failover.round_robin {
+                        (current + 1) % endpoints.len()
+                    } else {
+                        0
+                    };
+                    // A resumed session still has our subscriptions
+                    if !connected_before || !session_present {
+                        for (topic, error) in subscriptions.subscribe_all() {
+                            tracing::warn!("Failed to subscribe to {}: {}", topic, error);
+                            let _ = evt_tx.send(MqttEvent::SubAck(topic, Err(error))).await;
+                        }
+                    }
+                    connected = true;
+                    connected_before = true;
+                    attempt = 0;
+                }
+                let keep_going =
+                    process_event(event, config, inbox, &subscriptions, &publishes, evt_tx).await;
+                if !keep_going {
+                    break;
+                }
+            }
+            Err(error) => {
+                connected = false;
+                if let Some(id) = error.rejected.and_then(|ack| publishes.rejected(ack)) {
+                    let status = DeliveryStatus::Failed(error.message.clone());
+                    let _ = evt_tx.send(MqttEvent::Delivery(id, status)).await;
+                }
+                let error_msg = error.message;
+                if *stop_rx.borrow() {
+                    break;
+                }
+                tracing::error!("MQTT connection error: {}", error_msg);
+
+                let policy = &config.reconnect;
+                attempt += 1;
+                // Every node gets a first try, even without reconnects
+                let untried = !connected_before && (attempt as usize) < endpoints.len();
+                if !untried
+                    && (!policy.enabled || policy.max_attempts.is_some_and(|max| attempt > max))
+                {
+                    bail!(error_msg);
+                }
+
+                if endpoints.len() > 1 {
+                    current = next_endpoint;
+                    next_endpoint = (current + 1) % endpoints.len();
+                    tracing::info!("Next attempt goes to {}", endpoints[current]);
+                }
+                // Every attempt through a dead SSH session would fail
+                if route.tunnel.as_ref().is_some_and(SshTunnel::is_closed) {
+                    tracing::info!("SSH tunnel dropped, opening it again");
+                    match Route::open(config).await {
+                        Ok(reopened) => std::mem::replace(route, reopened).close().await,
+                        Err(e) => tracing::warn!("Failed to reopen route: {:#}", e),
+                    }
+                }
+                // Generated passwords may have expired since the last attempt
+                let credentials = credentials::generate(config, &client_id)?;
+                connection.retarget(config, target(route, &endpoints[current], credentials));
+
+                let delay = if policy.enabled {
+                    policy.delay(attempt)
+                } else {
+                    Duration::ZERO
+                };
+                let next_retry = Utc::now() + chrono::Duration::from_std(delay).unwrap_or_default();
+                tracing::info!("Reconnecting in {:?} (attempt {})", delay, attempt);
+                let _ = evt_tx
+                    .send(MqttEvent::Reconnecting {
+                        attempt,
+                        next_retry,
+                    })
+                    .await;
+                if !wait_for_retry(delay, &mut stop_rx).await {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Configured endpoints in order. Try localhost fallback to 127.0.0.1 on Windows.
+fn endpoints_to_try(config: &ConnectionConfig) -> Vec<Endpoint> {
+    config
+        .endpoints()
+        .into_iter()
+        .flat_map(|endpoint| {
+            let fallback = endpoint
+                .host
+                .eq_ignore_ascii_case("localhost")
+                .then(|| Endpoint {
+                    host: "127.0.0.1".to_string(),
+                    port: endpoint.port,
+                });
+            std::iter::once(endpoint).chain(fallback)
+        })
+        .collect()
+}
+
+/// Forward commands from the UI to the client until a disconnect is
+/// requested or the UI drops this connection
+async fn handle_commands(
+    mut cmd_rx: mpsc::UnboundedReceiver<MqttCommand>,
+    client: Client,
+    subscriptions: Arc<Subscriptions>,
+    publishes: Arc<Publishes>,
+    mut evt_tx: Sender<MqttEvent>,
+    stop_tx: watch::Sender<bool>,
+) {
+    while let Some(cmd) = cmd_rx.recv().await {
+        match cmd {
+            MqttCommand::Connect => {}
+            MqttCommand::Disconnect => break,
+            MqttCommand::Publish(request) => {
+                let id = request.id;
+                publishes.queue(id, request.qos);
+                // Waiting for room would hold up a Disconnect behind the backlog
+                let result = client.try_publish(
+                    &request.topic,
+                    request.payload,
+                    request.qos,
+                    request.retain,
+                    request.properties,
+                );
+                match result {
+                    Ok(()) => {
+                        tokio::spawn(expire_publish(id, publishes.clone(), evt_tx.clone()));
+                    }
+                    Err(e) => {
+                        publishes.cancel(id);
+                        let status = DeliveryStatus::Failed(e.to_string());
+                        let _ = evt_tx.send(MqttEvent::Delivery(id, status)).await;
+                    }
+                }
+            }
+            MqttCommand::Subscribe(subscription) => {
+                let topic = subscription.filter();
+                if let Err(e) = subscriptions.subscribe(subscription) {
+                    tracing::warn!("Failed to subscribe to {}: {}", topic, e);
+                    let _ = evt_tx
+                        .send(MqttEvent::SubAck(topic, Err(e.to_string())))
+                        .await;
+                }
+            }
+            MqttCommand::Unsubscribe(topic) => {
+                if let Err(e) = subscriptions.unsubscribe(&topic) {
+                    tracing::warn!("Failed to unsubscribe from {}: {}", topic, e);
+                }
+            }
+        }
+    }
+
+    let _ = stop_tx.send(true);
+    let _ = client.disconnect().await;
+}
+
+/// Handle a single broker event. Returns `false` once the worker should stop.
+async fn process_event(
+    event: Event,
+    config: &ConnectionConfig,
+    inbox: &Inbox,
+    subscriptions: &Subscriptions,
+    publishes: &Publishes,
+    evt_tx: &mut Sender<MqttEvent>,
+) -> bool {
+    match event {
+        Event::ConnAck {
+            session_present,
+            reason,
+        } => {
+            if let Some(reason) = reason {
+                let session = if session_present {
+                    ", session present"
+                } else {
+                    ""
+                };
+                let _ = evt_tx
+                    .send(MqttEvent::Reason(format!("CONNACK: {}{}", reason, session)))
+                    .await;
+            }
+        }
+        Event::Publish(msg) => {
+            if inbox.push(msg) {
+                let _ = evt_tx.send(MqttEvent::Messages).await;
+            }
+        }
+        Event::PublishSent(pkid) => {
+            if let Sent::Done(id) = publishes.sent(pkid) {
+                let _ = evt_tx
+                    .send(MqttEvent::Delivery(id, DeliveryStatus::Sent))
+                    .await;
+            }
+        }
+        Event::PublishAck(ack, pkid) => {
+            if let Some((id, rtt)) = publishes.acked(ack, pkid) {
+                let _ = evt_tx
+                    .send(MqttEvent::Delivery(id, DeliveryStatus::Acked(rtt)))
+                    .await;
+            }
+        }
+        Event::SubscribeSent(pkid) => subscriptions.sent(pkid),
+        Event::SubAck { pkid, results } => {
+            let Some(topic) = subscriptions.acked(pkid) else {
+                return true;
+            };
+            for result in results {
+                if config.version == MqttVersion::V5 {
+                    let reason = match &result {
+                        Ok(qos) => format!("Success (QoS {})", qos),
+                        Err(reason) => reason.clone(),
+                    };
+                    let _ = evt_tx
+                        .send(MqttEvent::Reason(format!("SUBACK {}: {}", topic, reason)))
+                        .await;
+                }
+                let _ = evt_tx.send(MqttEvent::SubAck(topic.clone(), result)).await;
+            }
+        }
+        Event::Disconnect { reason } => {
+            if let Some(reason) = reason {
+                let _ = evt_tx
+                    .send(MqttEvent::Reason(format!("DISCONNECT: {}", reason)))
+                    .await;
+            }
+            // With reconnect enabled, the next poll fails and triggers a retry
+            return config.reconnect.enabled;
+        }
+        Event::Other => {}
+    }
+    true
+}
+
+/// Report a publish as timed out if it is still unacknowledged after
+/// `PUBLISH_TIMEOUT`
+async fn expire_publish(id: u64, publishes: Arc<Publishes>, mut evt_tx: Sender<MqttEvent>) {
+    tokio::time::sleep(PUBLISH_TIMEOUT).await;
+    if publishes.expire(id) {
+        let _ = evt_tx
+            .send(MqttEvent::Delivery(id, DeliveryStatus::TimedOut))
+            .await;
+    }
+}
+
+/// Sleep until the next reconnect attempt. Returns `false` if the connection
+/// was closed while waiting.
+async fn wait_for_retry(delay: Duration, stop: &mut watch::Receiver<bool>) -> bool {
+    tokio::select! {
+        _ = tokio::time::sleep(delay) => true,
+        _ = stop.wait_for(|stopped| *stopped) => false,
+    }
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    if let Some(s) = panic.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = panic.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Unknown panic in MQTT worker".to_string()
+    }
+}
+
+/// Proxy or SSH tunnel between the client and the broker, kept for as long
+/// as the connection since it may rely on a local relay
+struct Route {
+    proxy: Option<rumqttc::Proxy>,
+    relay: Option<Relay>,
+    tunnel: Option<SshTunnel>,
+    /// TLS server name rumqttc connects to in place of the broker host
+    server_name: Option<String>,
+}
+
+impl Route {
+    /// The SSH tunnel takes the place of the proxy, which it goes through itself
+    async fn open(config: &ConnectionConfig) -> anyhow::Result<Self> {
+        let server_name = config.tls_server_name().map(str::to_string);
+        if config.ssh.is_enabled() {
+            let tunnel = SshTunnel::open(&config.ssh, &config.proxy)
+                .await
+                .context("SSH tunnel")?;
+            return Ok(Self {
+                proxy: Some(tunnel.proxy()),
+                relay: None,
+                tunnel: Some(tunnel),
+                server_name,
+            });
+        }
+        let (proxy, relay) = match proxy::client_proxy(&config.proxy).await? {
+            // rumqttc sends the host it connects to as SNI, so with a server
+            // name override a relay takes it to the broker instead
+            Some((_, None)) | None if server_name.is_some() => {
+                let relay = proxy::proxy_relay("TLS", &config.proxy).await?;
+                (Some(relay.proxy()), Some(relay))
+            }
+            Some((proxy, relay)) => (Some(proxy), relay),
+            None => (None, None),
+        };
+        Ok(Self {
+            proxy,
+            relay,
+            tunnel: None,
+            server_name,
+        })
+    }
+
+    /// Let a relay in the route open tunnels to `endpoint` only, for the
+    /// next connection attempt. Returns the endpoint for rumqttc to connect
+    /// to, which is the TLS server name if one overrides the host.
+    fn pin(&self, endpoint: &Endpoint) -> Endpoint {
+        let requested = match &self.server_name {
+            Some(name) => Endpoint {
+                host: name.clone(),
+                port: endpoint.port,
+            },
+            None => endpoint.clone(),
+        };
+        if let Some(relay) = &self.relay {
+            relay.pin(&requested, endpoint);
+        }
+        if let Some(tunnel) = &self.tunnel {
+            tunnel.pin(&requested, endpoint);
+        }
+        requested
+    }
+
+    /// Tear down the SSH tunnel, if any
+    async fn close(self) {
+        if let Some(tunnel) = self.tunnel {
+            tunnel.close().await;
+        }
+    }
+}
+
+/// Select the rumqttc transport matching the configured protocol
+fn build_transport(config: &ConnectionConfig) -> anyhow::Result<Transport> {
+    let tls_config = || -> anyhow::Result<TlsConfiguration> {
+        let client_config = tls::client_config(&config.tls).context("TLS")?;
+        Ok(TlsConfiguration::Rustls(client_config))
+    };
+
+    Ok(match config.protocol {
+        MqttProtocol::Mqtt => Transport::tcp(),
+        MqttProtocol::Mqtts => Transport::tls_with_config(tls_config()?),
+        MqttProtocol::MqttWs => Transport::ws(),
+        MqttProtocol::MqttsWs => Transport::wss_with_config(tls_config()?),
+    })
+}
+
+/// Parse the custom headers added to the WebSocket upgrade request
+fn upgrade_headers(config: &ConnectionConfig) -> anyhow::Result<Vec<(HeaderName, HeaderValue)>> {
+    if !config.protocol.is_websocket() {
+        return Ok(Vec::new());
+    }
+
+    config
+        .websocket
+        .headers
+        .iter()
+        .filter(|h| !h.name.trim().is_empty())
+        .map(|h| {
+            let name = HeaderName::from_bytes(h.name.trim().as_bytes())
+                .with_context(|| format!("Invalid header name {:?}", h.name))?;
+            let value = HeaderValue::from_str(&h.value)
+                .with_context(|| format!("Invalid value for header {}", h.name))?;
+            Ok((name, value))
+        })
+        .collect()
+}
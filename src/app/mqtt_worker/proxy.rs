@@ -0,0 +1,262 @@
+//! Proxied broker connections
+//!
+//! rumqttc tunnels every transport through an HTTP CONNECT proxy itself. For
+//! SOCKS5 and SSH it is pointed at a local relay instead, which accepts the
+//! CONNECT request and opens the tunnel on its behalf. The relay only opens
+//! tunnels to the broker endpoint being connected to, so other programs
+//! can't use it to reach arbitrary hosts.
+//!
+//! A relay is also used when the TLS server name is overridden: rumqttc
+//! sends its broker address as SNI, so it is given the override and the
+//! relay maps it to the actual endpoint.
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use anyhow::{bail, Context};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio_socks::tcp::Socks5Stream;
+
+use crate::config::{Endpoint, ProxyKind, ProxySettings};
+
+/// Longest request or response head accepted for a CONNECT
+const MAX_HEAD: usize = 8 * 1024;
+
+/// Local HTTP CONNECT endpoint that opens each tunnel some other way. Stops
+/// accepting when dropped.
+pub struct Relay {
+    pub addr: SocketAddr,
+    /// The only endpoint rumqttc may ask for, and where its tunnel goes
+    pinned: Arc<Mutex<Option<(Endpoint, Endpoint)>>>,
+    task: JoinHandle<()>,
+}
+
+impl Drop for Relay {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl Relay {
+    /// Proxy settings pointing rumqttc at the relay
+    pub fn proxy(&self) -> rumqttc::Proxy {
+        rumqttc::Proxy {
+            ty: rumqttc::ProxyType::Http,
+            auth: rumqttc::ProxyAuth::None,
+            addr: self.addr.ip().to_string(),
+            port: self.addr.port(),
+        }
+    }
+
+    /// Open tunnels to `endpoint` only, for requests for `requested`, until
+    /// pinned to another
+    pub fn pin(&self, requested: &Endpoint, endpoint: &Endpoint) {
+        let mut pinned = self.pinned.lock().unwrap_or_else(PoisonError::into_inner);
+        *pinned = Some((requested.clone(), endpoint.clone()));
+    }
+}
+
+/// Proxy for rumqttc to connect through, with the relay it relies on for SOCKS5
+pub async fn client_proxy(
+    settings: &ProxySettings,
+) -> anyhow::Result<Option<(rumqttc::Proxy, Option<Relay>)>> {
+    if !settings.is_enabled() {
+        return Ok(None);
+    }
+    Ok(Some(match settings.kind {
+        ProxyKind::None => return Ok(None),
+        ProxyKind::Http => {
+            let auth = match &settings.username {
+                Some(username) => rumqttc::ProxyAuth::Basic {
+                    username: username.clone(),
+                    password: settings.password.clone().unwrap_or_default(),
+                },
+                None => rumqttc::ProxyAuth::None,
+            };
+            let proxy = rumqttc::Proxy {
+                ty: rumqttc::ProxyType::Http,
+                auth,
+                addr: settings.host.clone(),
+                port: settings.port,
+            };
+            (proxy, None)
+        }
+        ProxyKind::Socks5 => {
+            let relay = proxy_relay("SOCKS5", settings).await?;
+            (relay.proxy(), Some(relay))
+        }
+    }))
+}
+
+/// Relay opening each tunnel through the proxy, or directly if none is enabled
+pub async fn proxy_relay(name: &'static str, settings: &ProxySettings) -> anyhow::Result<Relay> {
+    let settings = settings.clone();
+    start_relay(name, move |target| {
+        let settings = settings.clone();
+        async move {
+            if !settings.is_enabled() {
+                return TcpStream::connect((target.host.as_str(), target.port))
+                    .await
+                    .with_context(|| format!("Connecting to {}", target));
+            }
+            let stream = TcpStream::connect((settings.host.as_str(), settings.port))
+                .await
+                .with_context(|| {
+                    format!("Connecting to proxy {}:{}", settings.host, settings.port)
+                })?;
+            open_tunnel(&settings, stream, &target.host, target.port).await
+        }
+    })
+    .await
+}
+
+/// Ask the proxy on the other end of `stream` for a tunnel to `host:port`
+pub async fn open_tunnel(
+    settings: &ProxySettings,
+    mut stream: TcpStream,
+    host: &str,
+    port: u16,
+) -> anyhow::Result<TcpStream> {
+    match settings.kind {
+        ProxyKind::None => Ok(stream),
+        ProxyKind::Socks5 => {
+            let tunnel = match &settings.username {
+                Some(username) => {
+                    let password = settings.password.as_deref().unwrap_or_default();
+                    Socks5Stream::connect_with_password_and_socket(
+                        stream,
+                        (host, port),
+                        username,
+                        password,
+                    )
+                    .await
+                }
+                None => Socks5Stream::connect_with_socket(stream, (host, port)).await,
+            };
+            Ok(tunnel.context("SOCKS5")?.into_inner())
+        }
+        ProxyKind::Http => {
+            let authority = Endpoint {
+                host: host.to_string(),
+                port,
+            }
+            .to_string();
+            let mut request = format!("CONNECT {} HTTP/1.1\r\nHost: {}\r\n", authority, authority);
+            if let Some(username) = &settings.username {
+                let password = settings.password.as_deref().unwrap_or_default();
+                let credentials = BASE64.encode(format!("{}:{}", username, password));
+                request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", credentials));
+            }
+            request.push_str("\r\n");
+            stream.write_all(request.as_bytes()).await?;
+
+            let head = read_head(&mut stream).await?;
+            let status = head.lines().next().unwrap_or_default();
+            if status.split_whitespace().nth(1) != Some("200") {
+                bail!("HTTP proxy refused the tunnel: {}", status);
+            }
+            Ok(stream)
+        }
+    }
+}
+
+/// Start a relay on a free local port, opening each tunnel with `open`.
+/// Tunnels are refused until the relay is pinned to an endpoint.
+pub async fn start_relay<F, Fut, S>(name: &'static str, open: F) -> anyhow::Result<Relay>
+where
+    F: Fn(Endpoint) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = anyhow::Result<S>> + Send,
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .with_context(|| format!("Starting {} relay", name))?;
+    let addr = listener.local_addr()?;
+    let open = Arc::new(open);
+    let pinned = Arc::new(Mutex::new(None));
+    let task = {
+        let pinned = pinned.clone();
+        tokio::spawn(async move {
+            while let Ok((client, _)) = listener.accept().await {
+                let open = open.clone();
+                let pinned = pinned
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clone();
+                tokio::spawn(
+                    async move { relay(name, client, pinned, |target| open(target)).await },
+                );
+            }
+        })
+    };
+    Ok(Relay { addr, pinned, task })
+}
+
+/// Serve one CONNECT request from rumqttc, if it is for the pinned endpoint
+async fn relay<Fut, S>(
+    name: &str,
+    mut client: TcpStream,
+    pinned: Option<(Endpoint, Endpoint)>,
+    open: impl FnOnce(Endpoint) -> Fut,
+) where
+    Fut: Future<Output = anyhow::Result<S>>,
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let upstream = async {
+        let head = read_head(&mut client).await?;
+        let target = head
+            .lines()
+            .next()
+            .and_then(|line| line.strip_prefix("CONNECT "))
+            .and_then(|rest| rest.split_whitespace().next())
+            .context("Expected a CONNECT request")?;
+        let (host, port) = target
+            .rsplit_once(':')
+            .and_then(|(host, port)| Some((host, port.parse::<u16>().ok()?)))
+            .with_context(|| format!("Invalid CONNECT target {}", target))?;
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let asked = Endpoint {
+            host: host.to_string(),
+            port,
+        };
+        match pinned {
+            Some((requested, endpoint)) if requested == asked => open(endpoint).await,
+            _ => bail!("Refused a tunnel to {}, which is not the broker", asked),
+        }
+    };
+
+    match upstream.await {
+        Ok(mut upstream) => {
+            let established = b"HTTP/1.1 200 Connection established\r\n\r\n";
+            if client.write_all(established).await.is_ok() {
+                let _ = tokio::io::copy_bidirectional(&mut client, &mut upstream).await;
+            }
+        }
+        Err(e) => {
+            tracing::warn!("{} relay failed: {:#}", name, e);
+            let _ = client.write_all(b"HTTP/1.1 502 Bad Gateway\r\n\r\n").await;
+        }
+    }
+}
+
+/// Read up to the blank line ending an HTTP head, without consuming anything
+/// after it
+async fn read_head<S: AsyncRead + Unpin>(stream: &mut S) -> anyhow::Result<String> {
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_HEAD {
+            bail!("HTTP head too long");
+        }
+        let byte = stream
+            .read_u8()
+            .await
+            .context("Connection closed mid-request")?;
+        head.push(byte);
+    }
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
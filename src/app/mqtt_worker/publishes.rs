@@ -0,0 +1,120 @@
+//! Outgoing publishes still waiting to go out or to be acknowledged
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use super::client::Ack;
+
+#[derive(Default)]
+pub struct Publishes {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Queued on the client but not sent yet, oldest first
+    queued: VecDeque<(u64, u8)>,
+    /// Sent and waiting for the final ack, by packet id
+    sent: HashMap<u16, InFlight>,
+}
+
+struct InFlight {
+    id: u64,
+    qos: u8,
+    sent_at: Instant,
+    /// QoS 2 publish answered by a PUBREC, waiting for the PUBCOMP
+    received: bool,
+}
+
+/// What became of a publish once it left the client
+pub enum Sent {
+    /// QoS 0, nothing more will come back
+    Done(u64),
+    /// Waiting for an acknowledgement
+    Awaiting,
+    /// Not ours, or a retransmission after reconnecting
+    Unknown,
+}
+
+impl Publishes {
+    /// Record a publish about to be queued on the client
+    pub fn queue(&self, id: u64, qos: u8) {
+        self.lock().queued.push_back((id, qos));
+    }
+
+    /// Forget a publish the client refused to queue
+    pub fn cancel(&self, id: u64) {
+        self.lock().queued.retain(|(queued, _)| *queued != id);
+    }
+
+    /// The oldest queued publish went out with `pkid`, which is 0 for QoS 0
+    pub fn sent(&self, pkid: u16) -> Sent {
+        let mut state = self.lock();
+        // Unacked publishes are sent again with the same packet id
+        if state.sent.contains_key(&pkid) {
+            return Sent::Unknown;
+        }
+        let Some((id, qos)) = state.queued.pop_front() else {
+            return Sent::Unknown;
+        };
+        if pkid == 0 {
+            return Sent::Done(id);
+        }
+        let in_flight = InFlight {
+            id,
+            qos,
+            sent_at: Instant::now(),
+            received: false,
+        };
+        state.sent.insert(pkid, in_flight);
+        Sent::Awaiting
+    }
+
+    /// Publish completed by this ack, with its round-trip time. A PUBREC
+    /// only moves a QoS 2 publish on to waiting for the PUBCOMP.
+    pub fn acked(&self, ack: Ack, pkid: u16) -> Option<(u64, Duration)> {
+        let mut state = self.lock();
+        if ack == Ack::PubRec {
+            if let Some(in_flight) = state.sent.get_mut(&pkid) {
+                in_flight.received = true;
+            }
+            return None;
+        }
+        state
+            .sent
+            .remove(&pkid)
+            .map(|in_flight| (in_flight.id, in_flight.sent_at.elapsed()))
+    }
+
+    /// Publish a failed ack of this kind refers to. rumqttc drops the packet
+    /// id, but brokers acknowledge in the order publishes arrive, so it is the
+    /// oldest one waiting for that ack.
+    pub fn rejected(&self, ack: Ack) -> Option<u64> {
+        let mut state = self.lock();
+        let pkid = state
+            .sent
+            .iter()
+            .filter(|(_, in_flight)| match ack {
+                Ack::PubAck => in_flight.qos == 1,
+                Ack::PubRec => in_flight.qos == 2 && !in_flight.received,
+                Ack::PubComp => in_flight.qos == 2 && in_flight.received,
+            })
+            .min_by_key(|(_, in_flight)| in_flight.sent_at)
+            .map(|(pkid, _)| *pkid)?;
+        state.sent.remove(&pkid).map(|in_flight| in_flight.id)
+    }
+
+    /// Give up on a publish. Returns `false` if it already completed.
+    pub fn expire(&self, id: u64) -> bool {
+        let mut state = self.lock();
+        let before = state.queued.len() + state.sent.len();
+        state.queued.retain(|(queued, _)| *queued != id);
+        state.sent.retain(|_, in_flight| in_flight.id != id);
+        state.queued.len() + state.sent.len() != before
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
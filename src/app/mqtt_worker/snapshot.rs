@@ -0,0 +1,204 @@
+//! One-off sessions that back up a broker's retained messages to a snapshot
+//! file, or publish a snapshot back as retained
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use iced::futures::channel::mpsc::Sender;
+use iced::futures::{SinkExt, Stream};
+use tokio::time::Instant;
+
+use crate::app::types::TransferStatus;
+use crate::config::{ConnectionConfig, MqttVersion, Subscription};
+use crate::mqtt::snapshot::SnapshotMessage;
+use crate::mqtt::RetainedSnapshot;
+
+use super::client::{Ack, Client, Connection, Event};
+use super::diagnostics;
+
+/// Longest to wait for the CONNACK, the SUBACK or the next acknowledgement
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Report progress every this many messages
+const PROGRESS_EVERY: usize = 100;
+
+/// Subscribe to `filter` and collect retained messages until none arrived for
+/// `quiet`, then write them to `path`
+pub fn take_snapshot(
+    config: ConnectionConfig,
+    filter: String,
+    quiet: Duration,
+    path: PathBuf,
+) -> impl Stream<Item = TransferStatus> {
+    iced::stream::channel(10, move |mut output| async move {
+        let result = async {
+            let mut session = open(&config, &mut output).await?;
+            let (client, connection, _) = &mut session;
+            let collected = collect(client, connection, &filter, quiet, &mut output).await;
+            diagnostics::close(session).await;
+
+            let messages = collected?;
+            let count = messages.len();
+            let broker = config.endpoints()[0].to_string();
+            RetainedSnapshot::new(broker, filter.clone(), messages).save(&path)?;
+            anyhow::Ok(format!(
+                "Saved {} retained messages to {}",
+                count,
+                path.display()
+            ))
+        }
+        .await;
+        let _ = output.send(finished(result)).await;
+    })
+}
+
+/// Publish every message of `snapshot` to the broker as retained
+pub fn restore_snapshot(
+    config: ConnectionConfig,
+    snapshot: RetainedSnapshot,
+) -> impl Stream<Item = TransferStatus> {
+    iced::stream::channel(10, move |mut output| async move {
+        let result = async {
+            let mut session = open(&config, &mut output).await?;
+            let (client, connection, _) = &mut session;
+            let restored = restore(&config, client, connection, snapshot, &mut output).await;
+            diagnostics::close(session).await;
+            restored
+        }
+        .await;
+        let _ = output.send(finished(result)).await;
+    })
+}
+
+async fn open(
+    config: &ConnectionConfig,
+    output: &mut Sender<TransferStatus>,
+) -> anyhow::Result<diagnostics::Session> {
+    progress(output, format!("Connecting to {}", config.name)).await;
+    let (session, _) = tokio::time::timeout(RESPONSE_TIMEOUT, diagnostics::connect(config))
+        .await
+        .context("Timed out connecting")??;
+    Ok(session)
+}
+
+async fn collect(
+    client: &Client,
+    connection: &mut Connection,
+    filter: &str,
+    quiet: Duration,
+    output: &mut Sender<TransferStatus>,
+) -> anyhow::Result<Vec<SnapshotMessage>> {
+    // QoS 2 so messages arrive with the QoS they were published at
+    client.try_subscribe(&Subscription::new(filter, 2))?;
+
+    let mut retained: HashMap<String, SnapshotMessage> = HashMap::new();
+    let mut subscribed = false;
+    let mut last_retained = Instant::now();
+    loop {
+        let deadline = if subscribed {
+            last_retained + quiet
+        } else {
+            last_retained + RESPONSE_TIMEOUT
+        };
+        let event = match tokio::time::timeout_at(deadline, connection.next_event()).await {
+            Ok(event) => event,
+            Err(_) if subscribed => break,
+            Err(_) => bail!("No SUBACK for {}", filter),
+        };
+        match event {
+            Ok(Event::SubAck { results, .. }) => match results.into_iter().next() {
+                Some(Ok(_)) => subscribed = true,
+                Some(Err(reason)) => bail!("{} refused: {}", filter, reason),
+                None => bail!("Empty SUBACK"),
+            },
+            // Live traffic is not part of the retained state
+            Ok(Event::Publish(msg)) if msg.retain => {
+                last_retained = Instant::now();
+                if msg.payload.is_empty() {
+                    retained.remove(&msg.topic);
+                } else if retained.insert(msg.topic.clone(), msg.into()).is_none()
+                    && retained.len().is_multiple_of(PROGRESS_EVERY)
+                {
+                    progress(
+                        output,
+                        format!("Collected {} retained messages", retained.len()),
+                    )
+                    .await;
+                }
+            }
+            Ok(_) => {}
+            Err(e) => bail!(e.message),
+        }
+    }
+    Ok(retained.into_values().collect())
+}
+
+async fn restore(
+    config: &ConnectionConfig,
+    client: &Client,
+    connection: &mut Connection,
+    snapshot: RetainedSnapshot,
+    output: &mut Sender<TransferStatus>,
+) -> anyhow::Result<String> {
+    let total = snapshot.messages.len();
+    let v5 = config.version == MqttVersion::V5;
+
+    // Publishing waits while the request channel is full, so it runs
+    // alongside the event loop
+    let publisher = client.clone();
+    let mut sending = tokio::spawn(async move {
+        for msg in snapshot.messages {
+            let properties = msg.properties.filter(|_| v5);
+            publisher
+                .publish(&msg.topic, msg.payload, msg.qos, true, properties)
+                .await?;
+        }
+        anyhow::Ok(())
+    });
+
+    let mut done = 0;
+    let result = loop {
+        if done == total {
+            break Ok(());
+        }
+        let event = match tokio::time::timeout(RESPONSE_TIMEOUT, connection.next_event()).await {
+            Ok(event) => event,
+            Err(_) if sending.is_finished() => match (&mut sending).await {
+                Ok(Err(e)) => break Err(e),
+                _ => break Err(anyhow::anyhow!("Timed out after {} of {}", done, total)),
+            },
+            Err(_) => break Err(anyhow::anyhow!("Timed out after {} of {}", done, total)),
+        };
+        match event {
+            // QoS 0 is done once sent, QoS 1 on PUBACK and QoS 2 on PUBCOMP
+            Ok(Event::PublishSent(0)) | Ok(Event::PublishAck(Ack::PubAck | Ack::PubComp, _)) => {
+                done += 1;
+                if done.is_multiple_of(PROGRESS_EVERY) {
+                    progress(output, format!("Published {} of {}", done, total)).await;
+                }
+            }
+            Ok(_) => {}
+            Err(e) => break Err(anyhow::anyhow!(e.message)),
+        }
+    };
+    sending.abort();
+    result?;
+
+    Ok(format!(
+        "Restored {} retained messages to {}",
+        total, config.name
+    ))
+}
+
+async fn progress(output: &mut Sender<TransferStatus>, status: String) {
+    let _ = output.send(TransferStatus::Running(status)).await;
+}
+
+fn finished(result: anyhow::Result<String>) -> TransferStatus {
+    match result {
+        Ok(detail) => TransferStatus::Done(detail),
+        Err(e) => TransferStatus::Failed(format!("{:#}", e)),
+    }
+}
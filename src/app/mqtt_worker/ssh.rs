@@ -0,0 +1,223 @@
+//! SSH tunnels to brokers only reachable from a jump host, like `ssh -L`
+//!
+//! One SSH session is kept per connection. rumqttc connects through a local
+//! relay, which forwards each connection over its own direct-tcpip channel.
+
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use russh::client::{self, Handle, Msg};
+use russh::keys::agent::client::{AgentClient, AgentStream};
+use russh::keys::{self, HashAlg, PrivateKeyWithHashAlg, PublicKey, PublicKeyOrCertificate};
+use russh::{ChannelStream, Disconnect};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+
+use crate::app::types::UnknownHostKey;
+use crate::config::{Endpoint, ProxySettings, SshAuth, SshTunnelSettings};
+
+use super::proxy::{self, Relay};
+
+/// An authenticated session with the jump host
+pub type Session = Handle<HostKeyCheck>;
+
+type Agent = AgentClient<Box<dyn AgentStream + Send + Unpin>>;
+
+/// Forwarding relay for one connection, closed with `close` on disconnect
+pub struct SshTunnel {
+    relay: Relay,
+    session: Arc<Session>,
+}
+
+impl SshTunnel {
+    /// Log in to the jump host, through the proxy if one is enabled
+    pub async fn open(settings: &SshTunnelSettings, proxy: &ProxySettings) -> anyhow::Result<Self> {
+        let jump_host = settings.jump_host();
+        let stream = if proxy.is_enabled() {
+            let stream = TcpStream::connect((proxy.host.as_str(), proxy.port))
+                .await
+                .with_context(|| format!("Connecting to proxy {}:{}", proxy.host, proxy.port))?;
+            proxy::open_tunnel(proxy, stream, &jump_host.host, jump_host.port).await?
+        } else {
+            TcpStream::connect((jump_host.host.as_str(), jump_host.port))
+                .await
+                .with_context(|| format!("Connecting to SSH host {}", jump_host))?
+        };
+        let session = Arc::new(login(settings, stream).await?);
+
+        let forward_session = session.clone();
+        let settings = settings.clone();
+        let relay = proxy::start_relay("SSH", move |endpoint| {
+            let session = forward_session.clone();
+            let target = settings.forward_target(&endpoint);
+            async move { forward(&session, &target).await }
+        })
+        .await?;
+        tracing::info!("SSH tunnel open through {}", jump_host);
+        Ok(Self { relay, session })
+    }
+
+    pub fn proxy(&self) -> rumqttc::Proxy {
+        self.relay.proxy()
+    }
+
+    /// Forward connections for `requested` to `endpoint` only
+    pub fn pin(&self, requested: &Endpoint, endpoint: &Endpoint) {
+        self.relay.pin(requested, endpoint);
+    }
+
+    /// Whether the SSH session has ended, e.g. because the jump host dropped it
+    pub fn is_closed(&self) -> bool {
+        self.session.is_closed()
+    }
+
+    /// Stop forwarding and end the SSH session
+    pub async fn close(self) {
+        drop(self.relay);
+        let _ = self
+            .session
+            .disconnect(Disconnect::ByApplication, "", "en")
+            .await;
+        tracing::info!("SSH tunnel closed");
+    }
+}
+
+/// Accepts the host key accepted in the connection's settings, else keys
+/// found in `~/.ssh/known_hosts`. Unknown keys fail with `UnknownHostKey`
+/// for the user to accept; changed ones are refused.
+pub struct HostKeyCheck {
+    jump_host: Endpoint,
+    accepted: Option<String>,
+}
+
+impl client::Handler for HostKeyCheck {
+    type Error = anyhow::Error;
+
+    async fn check_server_key(
+        &mut self,
+        server_key: &PublicKeyOrCertificate,
+    ) -> Result<bool, Self::Error> {
+        let key = match server_key {
+            PublicKeyOrCertificate::PublicKey { key, .. } => key.clone(),
+            PublicKeyOrCertificate::Certificate(cert) => {
+                PublicKey::new(cert.public_key().clone(), "")
+            }
+        };
+        let openssh = key.to_openssh().context("Encoding host key")?;
+        if let Some(accepted) = &self.accepted {
+            if *accepted != openssh {
+                bail!(
+                    "Host key for {} changed since it was accepted",
+                    self.jump_host
+                );
+            }
+            return Ok(true);
+        }
+
+        let Endpoint { host, port } = &self.jump_host;
+        match keys::check_known_hosts(host, *port, &key) {
+            Ok(true) => Ok(true),
+            Ok(false) => Err(UnknownHostKey {
+                key: openssh,
+                fingerprint: key.fingerprint(HashAlg::Sha256).to_string(),
+            }
+            .into()),
+            Err(keys::Error::KeyChanged { line }) => bail!(
+                "Host key for {} does not match known_hosts line {}",
+                self.jump_host,
+                line
+            ),
+            Err(e) => Err(e).context("Reading known_hosts"),
+        }
+    }
+}
+
+/// Run the SSH handshake over `stream` and authenticate
+pub async fn login<S>(settings: &SshTunnelSettings, stream: S) -> anyhow::Result<Session>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let config = Arc::new(client::Config {
+        keepalive_interval: Some(std::time::Duration::from_secs(30)),
+        ..Default::default()
+    });
+    let check = HostKeyCheck {
+        jump_host: settings.jump_host(),
+        accepted: settings.host_key.clone(),
+    };
+    let mut session = client::connect_stream(config, stream, check)
+        .await
+        .context("SSH handshake")?;
+
+    let user = settings.user.as_str();
+    // RSA keys sign with the best hash the server announces
+    let rsa_hash = session.best_supported_rsa_hash().await?.flatten();
+    let authenticated = match settings.auth {
+        SshAuth::KeyFile => {
+            let path = settings
+                .key_file
+                .as_deref()
+                .filter(|path| !path.is_empty())
+                .context("No SSH key file set")?;
+            let key = keys::load_secret_key(expand_home(path), settings.key_passphrase.as_deref())
+                .with_context(|| format!("Loading SSH key {}", path))?;
+            let key = PrivateKeyWithHashAlg::new(Arc::new(key), rsa_hash);
+            session.authenticate_publickey(user, key).await?.success()
+        }
+        SshAuth::Agent => {
+            let mut agent = connect_agent().await.context("Connecting to SSH agent")?;
+            let identities = agent.request_identities().await?;
+            if identities.is_empty() {
+                bail!("SSH agent has no keys");
+            }
+            let mut authenticated = false;
+            for identity in identities {
+                let key = identity.public_key().into_owned();
+                let result = session
+                    .authenticate_publickey_with(user, key, rsa_hash, &mut agent)
+                    .await?;
+                if result.success() {
+                    authenticated = true;
+                    break;
+                }
+            }
+            authenticated
+        }
+    };
+    if !authenticated {
+        bail!(
+            "{} refused {} authentication for {}",
+            settings.jump_host(),
+            settings.auth,
+            user
+        );
+    }
+    Ok(session)
+}
+
+/// Open a channel to `target`, as seen from the jump host
+pub async fn forward(session: &Session, target: &Endpoint) -> anyhow::Result<ChannelStream<Msg>> {
+    let channel = session
+        .channel_open_direct_tcpip(target.host.as_str(), u32::from(target.port), "127.0.0.1", 0)
+        .await
+        .with_context(|| format!("SSH forward to {}", target))?;
+    Ok(channel.into_stream())
+}
+
+#[cfg(unix)]
+async fn connect_agent() -> anyhow::Result<Agent> {
+    Ok(AgentClient::connect_env().await?.dynamic())
+}
+
+#[cfg(windows)]
+async fn connect_agent() -> anyhow::Result<Agent> {
+    Ok(AgentClient::connect_pageant().await?.dynamic())
+}
+
+/// Resolve a leading `~` to the home directory, as in `~/.ssh/id_ed25519`
+fn expand_home(path: &str) -> std::path::PathBuf {
+    match (path.strip_prefix("~/"), directories::BaseDirs::new()) {
+        (Some(rest), Some(dirs)) => dirs.home_dir().join(rest),
+        _ => path.into(),
+    }
+}
@@ -0,0 +1,87 @@
+//! Filters a connection stays subscribed to, and the SUBSCRIBE packets
+//! still waiting for their SUBACK
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::config::Subscription;
+
+use super::client::Client;
+
+pub struct Subscriptions {
+    client: Client,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Filters restored after a reconnect
+    active: Vec<Subscription>,
+    /// Queued on the client but not sent yet, oldest first
+    queued: VecDeque<String>,
+    /// Sent and waiting for a SUBACK, by packet id
+    sent: HashMap<u16, String>,
+}
+
+impl Subscriptions {
+    pub fn new(client: Client, initial: Vec<Subscription>) -> Self {
+        Self {
+            client,
+            state: Mutex::new(State {
+                active: initial,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Subscribe and keep the subscription across reconnects
+    pub fn subscribe(&self, subscription: Subscription) -> anyhow::Result<()> {
+        let mut state = self.lock();
+        let filter = subscription.filter();
+        // Queue while holding the lock so `sent` can't run before we record it
+        self.client.try_subscribe(&subscription)?;
+        state.queued.push_back(filter.clone());
+        match state.active.iter_mut().find(|s| s.filter() == filter) {
+            Some(active) => *active = subscription,
+            None => state.active.push(subscription),
+        }
+        Ok(())
+    }
+
+    pub fn unsubscribe(&self, topic: &str) -> anyhow::Result<()> {
+        let mut state = self.lock();
+        state.active.retain(|s| s.filter() != topic);
+        self.client.try_unsubscribe(topic)
+    }
+
+    /// Subscribe to every active filter, after connecting without a resumed
+    /// session. Returns the filters that could not be queued.
+    pub fn subscribe_all(&self) -> Vec<(String, String)> {
+        let mut state = self.lock();
+        let mut failed = Vec::new();
+        for subscription in state.active.clone() {
+            match self.client.try_subscribe(&subscription) {
+                Ok(()) => state.queued.push_back(subscription.filter()),
+                Err(e) => failed.push((subscription.filter(), e.to_string())),
+            }
+        }
+        failed
+    }
+
+    /// The oldest queued SUBSCRIBE went out with `pkid`
+    pub fn sent(&self, pkid: u16) {
+        let mut state = self.lock();
+        if let Some(topic) = state.queued.pop_front() {
+            state.sent.insert(pkid, topic);
+        }
+    }
+
+    /// Filter answered by the SUBACK for `pkid`
+    pub fn acked(&self, pkid: u16) -> Option<String> {
+        self.lock().sent.remove(&pkid)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
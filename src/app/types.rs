@@ -1,17 +1,27 @@
 //! Internal types for the MQTT UI application
 
-use std::sync::mpsc;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use chrono::{DateTime, Utc};
 use iced::widget::pane_grid;
+use tokio::sync::mpsc;
 
-use crate::config::ConnectionConfig;
-use crate::mqtt::{ConnectionStatus, MqttMessage};
+use crate::config::{ConnectionConfig, Subscription};
+use crate::mqtt::bridge::BridgeRule;
+use crate::mqtt::snapshot::SnapshotMessage;
+use crate::mqtt::stats::Rate;
+use crate::mqtt::{BrokerInfo, ConnectionStatus, MessageProperties, MqttMessage, TrafficStats};
+
+use super::mqtt_worker::Inbox;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Pane {
     Publish,
     Topics,
     Message,
+    Broker,
 }
 
 #[derive(Default, PartialEq, Clone)]
@@ -22,15 +32,52 @@ pub enum View {
         editing_id: Option<String>,
     },
     Connection(String),
+    /// Retained message snapshots and restores
+    Snapshots,
+    /// Live forwarding between open connections
+    Bridges,
+}
+
+/// A saved connection offered in a pick list
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectionChoice {
+    pub id: String,
+    pub name: String,
+}
+
+impl std::fmt::Display for ConnectionChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
 }
 
 #[allow(dead_code)]
 pub struct ConnectionState {
     pub config: ConnectionConfig,
     pub status: ConnectionStatus,
+    /// Latest reason code reported by a v5 broker
+    pub last_reason: Option<String>,
+    /// Broker node (host:port) the connection is attached to
+    pub node: Option<String>,
+    pub subscriptions: Vec<ActiveSubscription>,
     pub messages: Vec<MqttMessage>,
-    pub command_tx: Option<mpsc::Sender<MqttCommand>>,
-    pub event_rx: Option<mpsc::Receiver<MqttEvent>>,
+    /// Set once the worker is ready for commands
+    pub command_tx: Option<mpsc::UnboundedSender<MqttCommand>>,
+    /// Received messages the UI has not taken yet
+    pub inbox: Arc<Inbox>,
+    /// Identifies the worker subscription while it runs. Clearing it drops
+    /// the subscription, and the worker disconnects in the background.
+    pub worker_id: Option<u64>,
+    /// Recent publishes and how far their delivery got, newest last
+    pub publishes: Vec<PublishRecord>,
+    /// Traffic received on this connection, kept across topic clears
+    pub stats: TrafficStats,
+    /// Broker statistics from `$SYS` topics
+    pub broker: BrokerInfo,
+    /// Repeated and scheduled publishes
+    pub jobs: Vec<PublishJob>,
+    /// SSH host key the last attempt stopped at, for the user to accept
+    pub unknown_host_key: Option<UnknownHostKey>,
 }
 
 #[derive(Debug)]
@@ -38,15 +85,353 @@ pub struct ConnectionState {
 pub enum MqttCommand {
     Connect,
     Disconnect,
-    Publish(String, Vec<u8>, u8, bool),
+    Publish(PublishRequest),
+    Subscribe(Subscription),
+    Unsubscribe(String),
 }
 
+/// An outgoing message, tagged so its delivery can be reported back
 #[derive(Debug)]
+pub struct PublishRequest {
+    pub id: u64,
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub qos: u8,
+    pub retain: bool,
+    /// PUBLISH properties, sent on MQTT 5 connections only
+    pub properties: Option<MessageProperties>,
+}
+
+#[derive(Debug, Clone)]
 pub enum MqttEvent {
-    Connected,
+    /// First event from a worker, with the sender for its commands
+    Ready(mpsc::UnboundedSender<MqttCommand>),
+    /// CONNACK received from this node (host:port)
+    Connected(String),
     Disconnected,
-    Message(MqttMessage),
     Error(String),
+    /// New messages are waiting in the connection's inbox
+    Messages,
+    /// Connection dropped; the worker retries at `next_retry`
+    Reconnecting {
+        attempt: u32,
+        next_retry: DateTime<Utc>,
+    },
+    /// Reason code from a v5 CONNACK, SUBACK or DISCONNECT
+    Reason(String),
+    /// Granted QoS, or why subscribing to the filter failed
+    SubAck(String, Result<u8, String>),
+    /// Delivery progress of the publish with this id
+    Delivery(u64, DeliveryStatus),
+    /// The SSH jump host presented a key that isn't trusted yet
+    UnknownHostKey(UnknownHostKey),
+}
+
+/// Host key of an SSH jump host that neither `~/.ssh/known_hosts` nor the
+/// connection's settings trust
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownHostKey {
+    /// The key in OpenSSH format, as stored once accepted
+    pub key: String,
+    /// SHA-256 fingerprint, as shown by `ssh`
+    pub fingerprint: String,
+}
+
+impl std::fmt::Display for UnknownHostKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown SSH host key {}", self.fingerprint)
+    }
+}
+
+impl std::error::Error for UnknownHostKey {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeliveryStatus {
+    /// Waiting to be sent or acknowledged
+    Pending,
+    /// Sent at QoS 0, which is never acknowledged
+    Sent,
+    /// Acknowledged after this round trip
+    Acked(Duration),
+    Failed(String),
+    TimedOut,
+}
+
+/// A publish from this session, as shown in the publish panel
+#[derive(Debug, Clone)]
+pub struct PublishRecord {
+    pub id: u64,
+    pub topic: String,
+    pub qos: u8,
+    pub status: DeliveryStatus,
+    /// Set when the publish was a request sent in RPC mode
+    pub rpc: Option<RpcCall>,
+}
+
+impl PublishRecord {
+    /// Whether this is a request still waiting for its reply
+    pub fn awaits_reply(&self) -> bool {
+        let failed = matches!(
+            self.status,
+            DeliveryStatus::Failed(_) | DeliveryStatus::TimedOut
+        );
+        !failed && self.rpc.as_ref().is_some_and(|call| call.reply.is_none())
+    }
+}
+
+/// How often to check whether a scheduled job may start
+const START_POLL: Duration = Duration::from_millis(100);
+
+/// The same message published on a timer, optionally from a wall-clock time
+#[derive(Debug, Clone)]
+pub struct PublishJob {
+    pub id: u64,
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub qos: u8,
+    pub retain: bool,
+    pub interval: Duration,
+    /// Publishes to send in total; `None` repeats until cancelled
+    pub count: Option<u64>,
+    /// Time of the first publish, until it is reached
+    pub start_at: Option<DateTime<Utc>>,
+    pub sent: u64,
+    pub paused: bool,
+    /// Last publish while it waits to be sent or acknowledged. Ticks are
+    /// skipped until it completes, so the job can't queue up publishes
+    /// faster than the connection delivers them.
+    pub outstanding: Option<u64>,
+    /// Ticks skipped because the last publish was still outstanding
+    pub skipped: u64,
+}
+
+impl PublishJob {
+    pub fn is_done(&self) -> bool {
+        self.count.is_some_and(|count| self.sent >= count)
+    }
+
+    /// Timer period for the job, shorter while it waits for its start time
+    pub fn tick(&self) -> Duration {
+        match self.start_at {
+            Some(_) => self.interval.min(START_POLL),
+            None => self.interval,
+        }
+    }
+
+    pub fn request(&self, id: u64) -> PublishRequest {
+        PublishRequest {
+            id,
+            topic: self.topic.clone(),
+            payload: self.payload.clone(),
+            qos: self.qos,
+            retain: self.retain,
+            properties: None,
+        }
+    }
+}
+
+/// A request waiting for its reply on `reply_topic`
+#[derive(Debug, Clone)]
+pub struct RpcCall {
+    pub reply_topic: String,
+    pub correlation_id: String,
+    /// Payload field carrying the correlation id on MQTT 3.1.1, which has
+    /// no correlation data property
+    pub correlation_field: Option<String>,
+    pub sent_at: Instant,
+    pub reply: Option<RpcReply>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RpcReply {
+    /// Receive time of the reply, to recognise it in the message pane
+    pub timestamp: DateTime<Utc>,
+    pub round_trip: Duration,
+}
+
+impl RpcCall {
+    /// Whether `msg` answers this request
+    pub fn matches(&self, msg: &MqttMessage) -> bool {
+        if msg.topic != self.reply_topic {
+            return false;
+        }
+        match &self.correlation_field {
+            None => msg
+                .properties
+                .as_ref()
+                .and_then(|p| p.correlation_data.as_deref())
+                .is_some_and(|id| id == self.correlation_id.as_bytes()),
+            Some(field) => serde_json::from_slice::<serde_json::Value>(&msg.payload)
+                .ok()
+                .and_then(|payload| payload.get(field)?.as_str().map(str::to_string))
+                .is_some_and(|id| id == self.correlation_id),
+        }
+    }
+
+    /// Set the response topic and correlation data on `request`, or the
+    /// correlation field in its JSON payload on MQTT 3.1.1
+    pub fn prepare(&self, request: &mut PublishRequest) -> Result<(), String> {
+        let Some(field) = &self.correlation_field else {
+            request.properties = Some(MessageProperties {
+                response_topic: Some(self.reply_topic.clone()),
+                correlation_data: Some(self.correlation_id.clone().into_bytes()),
+                ..Default::default()
+            });
+            return Ok(());
+        };
+        let mut payload = if request.payload.iter().all(u8::is_ascii_whitespace) {
+            serde_json::json!({})
+        } else {
+            serde_json::from_slice(&request.payload)
+                .map_err(|e| format!("Payload is not JSON: {}", e))?
+        };
+        let Some(object) = payload.as_object_mut() else {
+            return Err("Payload must be a JSON object".to_string());
+        };
+        object.insert(field.clone(), self.correlation_id.clone().into());
+        request.payload = payload.to_string().into_bytes();
+        Ok(())
+    }
+
+    /// Whether `msg` is the reply already matched to this request
+    pub fn is_reply(&self, msg: &MqttMessage) -> bool {
+        self.reply
+            .as_ref()
+            .is_some_and(|reply| reply.timestamp == msg.timestamp && msg.topic == self.reply_topic)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubscriptionStatus {
+    /// Waiting for the SUBACK
+    Pending,
+    Granted(u8),
+    Failed(String),
+}
+
+/// A topic filter on a live connection, as shown in the subscription manager
+#[derive(Debug, Clone)]
+pub struct ActiveSubscription {
+    pub topic: String,
+    /// QoS we asked for; the broker may grant less
+    pub qos: u8,
+    pub status: SubscriptionStatus,
+}
+
+/// Steps of a connection test, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TestStage {
+    Dns,
+    Tcp,
+    Proxy,
+    Ssh,
+    Tls,
+    Connect,
+    Subscribe,
+}
+
+impl TestStage {
+    pub const ALL: [TestStage; 7] = [
+        TestStage::Dns,
+        TestStage::Tcp,
+        TestStage::Proxy,
+        TestStage::Ssh,
+        TestStage::Tls,
+        TestStage::Connect,
+        TestStage::Subscribe,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TestStage::Dns => "DNS lookup",
+            TestStage::Tcp => "TCP connect",
+            TestStage::Proxy => "Proxy tunnel",
+            TestStage::Ssh => "SSH tunnel",
+            TestStage::Tls => "TLS handshake",
+            TestStage::Connect => "MQTT connect",
+            TestStage::Subscribe => "Subscribe",
+        }
+    }
+}
+
+/// Retained messages under a tree node, to be copied to another connection
+#[derive(Debug, Clone)]
+pub struct RetainedCopy {
+    pub path: String,
+    pub messages: Vec<SnapshotMessage>,
+    /// Saved connection to copy to
+    pub target: Option<String>,
+    /// Replaces `path` at the start of each topic
+    pub prefix: String,
+    pub status: Option<TransferStatus>,
+}
+
+impl RetainedCopy {
+    /// Topic `topic` is written to on the target. Without a prefix, the
+    /// node's own topic has none.
+    pub fn destination(&self, topic: &str) -> Option<String> {
+        let rest = topic.strip_prefix(self.path.as_str()).unwrap_or(topic);
+        let prefix = self.prefix.trim().trim_end_matches('/');
+        if prefix.is_empty() {
+            let rest = rest.trim_start_matches('/');
+            (!rest.is_empty()).then(|| rest.to_string())
+        } else {
+            Some(format!("{}{}", prefix, rest))
+        }
+    }
+
+    /// Why the copy can't go ahead with the current prefix
+    pub fn prefix_error(&self) -> Option<String> {
+        let own_topic = self.messages.iter().any(|msg| msg.topic == self.path);
+        (own_topic && self.destination(&self.path).is_none()).then(|| {
+            format!(
+                "{} holds a retained message itself, so it needs a prefix",
+                self.path
+            )
+        })
+    }
+}
+
+/// Traffic forwarded from one open connection to another
+#[derive(Debug, Clone)]
+pub struct Bridge {
+    pub id: u64,
+    pub source: String,
+    pub target: String,
+    pub rule: BridgeRule,
+    pub running: bool,
+    /// Whether starting the bridge added the subscription on the source
+    pub subscribed: bool,
+    pub forwarded: u64,
+    pub forwarded_bytes: u64,
+    /// Matching messages not published because the target was offline
+    pub dropped: u64,
+    pub last_forwarded: Option<DateTime<Utc>>,
+}
+
+/// Progress of taking or restoring a retained snapshot
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransferStatus {
+    Running(String),
+    Done(String),
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StageResult {
+    /// Not started yet
+    Pending,
+    Running,
+    Passed {
+        elapsed: Duration,
+        detail: String,
+    },
+    Failed {
+        elapsed: Duration,
+        error: String,
+    },
+    /// Not applicable, or an earlier stage failed
+    Skipped(String),
 }
 
 /// Info about a tree node for rendering
@@ -58,20 +443,50 @@ pub struct TreeNodeInfo {
     pub has_children: bool,
     pub has_messages: bool,
     pub message_count: usize,
+    pub total_bytes: u64,
+    /// Subtree rates over each of `stats::WINDOWS`
+    pub rates: [Rate; 3],
+    /// Retained topics at or below this node
+    pub retained_count: usize,
     pub is_expanded: bool,
 }
 
-/// Create the initial 3-pane layout
+/// Retained topics under a tree node, waiting for the user to confirm
+/// clearing them
+#[derive(Debug, Clone, Default)]
+pub struct RetainedClear {
+    pub path: String,
+    pub topics: Vec<String>,
+    /// Empty publishes sent but not yet delivered, by publish id
+    pub pending: HashMap<u64, String>,
+    pub error: Option<String>,
+}
+
+impl RetainedClear {
+    /// Publishes whose delivery can no longer be reported
+    pub fn fail_pending(&mut self, reason: &str) {
+        if !self.pending.is_empty() {
+            self.pending.clear();
+            self.error = Some(format!("Clearing stopped: {reason}"));
+        }
+    }
+}
+
+/// Create the initial 4-pane layout
 pub fn create_pane_layout() -> pane_grid::State<Pane> {
     let (mut panes, publish_pane) = pane_grid::State::new(Pane::Publish);
     let (topics_pane, split1) = panes
         .split(pane_grid::Axis::Vertical, publish_pane, Pane::Topics)
         .unwrap();
-    let (_, split2) = panes
+    let (message_pane, split2) = panes
         .split(pane_grid::Axis::Vertical, topics_pane, Pane::Message)
         .unwrap();
-    // Resize to approximate 20% | 35% | 45%
+    let (_, split3) = panes
+        .split(pane_grid::Axis::Horizontal, message_pane, Pane::Broker)
+        .unwrap();
+    // Resize to approximate 20% | 35% | 45%, with the broker pane below the message
     panes.resize(split1, 0.2);
     panes.resize(split2, 0.55);
+    panes.resize(split3, 0.65);
     panes
 }
@@ -0,0 +1,243 @@
+//! Bridges: forward live traffic from one open connection to another
+
+use iced::widget::{
+    button, column, container, horizontal_rule, horizontal_space, pick_list, row, scrollable, text,
+    Column,
+};
+use iced::{Element, Length};
+
+use crate::mqtt::bridge::{BridgeQos, BridgeRetain};
+use crate::mqtt::stats::format_bytes;
+use crate::mqtt::ConnectionStatus;
+use crate::styles::{self, colors, icons, spacing, typography};
+
+use super::connection_form::form_field;
+use crate::app::types::{Bridge, ConnectionChoice};
+use crate::app::{Message, MqttUi};
+
+impl MqttUi {
+    pub fn view_bridges(&self) -> Element<'_, Message> {
+        // Only open connections can forward
+        let choices: Vec<ConnectionChoice> = self
+            .open_tabs
+            .iter()
+            .filter_map(|id| self.connections.get(id))
+            .map(|c| ConnectionChoice {
+                id: c.config.id.clone(),
+                name: c.config.name.clone(),
+            })
+            .collect();
+        let selected =
+            |id: &Option<String>| choices.iter().find(|c| Some(&c.id) == id.as_ref()).cloned();
+
+        let can_add = self.bridge_source.is_some()
+            && self.bridge_target.is_some()
+            && !self.bridge_filter.trim().is_empty();
+
+        let form = column![
+            text("New bridge")
+                .size(typography::SIZE_MD)
+                .color(colors::TEXT_PRIMARY),
+            text("Publishes every message matching the filter on the target connection")
+                .size(typography::SIZE_XS)
+                .color(colors::TEXT_MUTED),
+            row![
+                column![
+                    text("From")
+                        .size(typography::SIZE_SM)
+                        .color(colors::TEXT_SECONDARY),
+                    pick_list(
+                        choices.clone(),
+                        selected(&self.bridge_source),
+                        Message::BridgeSourceChanged
+                    )
+                    .placeholder("Open connection")
+                    .padding(spacing::SM)
+                    .width(Length::Fill),
+                ]
+                .spacing(spacing::XS),
+                column![
+                    text("To")
+                        .size(typography::SIZE_SM)
+                        .color(colors::TEXT_SECONDARY),
+                    pick_list(
+                        choices.clone(),
+                        selected(&self.bridge_target),
+                        Message::BridgeTargetChanged
+                    )
+                    .placeholder("Open connection")
+                    .padding(spacing::SM)
+                    .width(Length::Fill),
+                ]
+                .spacing(spacing::XS),
+            ]
+            .spacing(spacing::MD),
+            form_field(
+                "Filter",
+                "sensors/#",
+                &self.bridge_filter,
+                Message::BridgeFilterChanged
+            ),
+            row![
+                form_field(
+                    "Replace prefix",
+                    "sensors",
+                    &self.bridge_rewrite_from,
+                    Message::BridgeRewriteFromChanged
+                ),
+                form_field(
+                    "With",
+                    "field/sensors",
+                    &self.bridge_rewrite_to,
+                    Message::BridgeRewriteToChanged
+                ),
+            ]
+            .spacing(spacing::MD),
+            row![
+                pick_list(
+                    BridgeQos::all(),
+                    Some(self.bridge_qos),
+                    Message::BridgeQosChanged
+                )
+                .padding(spacing::SM)
+                .width(Length::Fill),
+                pick_list(
+                    BridgeRetain::all(),
+                    Some(self.bridge_retain),
+                    Message::BridgeRetainChanged
+                )
+                .padding(spacing::SM)
+                .width(Length::Fill),
+            ]
+            .spacing(spacing::MD),
+            row![
+                horizontal_space(),
+                button(text("Start bridge").size(typography::SIZE_MD))
+                    .padding([spacing::SM, spacing::LG])
+                    .style(styles::button_primary)
+                    .on_press_maybe(can_add.then_some(Message::AddBridge)),
+            ],
+        ]
+        .spacing(spacing::MD);
+
+        let mut bridges = Column::new().spacing(spacing::SM);
+        if self.bridges.is_empty() {
+            bridges = bridges.push(
+                text("No bridges yet")
+                    .size(typography::SIZE_SM)
+                    .color(colors::TEXT_MUTED),
+            );
+        }
+        for bridge in &self.bridges {
+            bridges = bridges.push(self.bridge_card(bridge));
+        }
+
+        let page = column![
+            text("Bridges")
+                .size(typography::SIZE_2XL)
+                .color(colors::CYAN),
+            horizontal_rule(1),
+            form,
+            horizontal_rule(1),
+            bridges,
+        ]
+        .spacing(spacing::MD)
+        .padding(spacing::LG)
+        .max_width(600);
+
+        scrollable(
+            container(container(page).style(styles::container_panel))
+                .width(Length::Fill)
+                .center_x(Length::Fill)
+                .padding(spacing::LG),
+        )
+        .height(Length::Fill)
+        .into()
+    }
+
+    fn bridge_card<'a>(&'a self, bridge: &'a Bridge) -> Element<'a, Message> {
+        let name = |id: &str| {
+            self.config
+                .get_connection(id)
+                .map(|c| c.name.clone())
+                .unwrap_or_else(|| id.to_string())
+        };
+        let connected = |id: &str| {
+            self.connections
+                .get(id)
+                .is_some_and(|c| matches!(c.status, ConnectionStatus::Connected))
+        };
+
+        let (state, state_color) = if !bridge.running {
+            ("Stopped", colors::TEXT_MUTED)
+        } else if !connected(&bridge.source) {
+            ("Source offline", colors::AMBER)
+        } else if !connected(&bridge.target) {
+            ("Target offline", colors::AMBER)
+        } else {
+            ("Forwarding", colors::GREEN)
+        };
+
+        let toggle = if bridge.running {
+            button(text("Stop").size(typography::SIZE_SM))
+                .style(styles::button_secondary)
+                .on_press(Message::StopBridge(bridge.id))
+        } else {
+            button(text("Start").size(typography::SIZE_SM))
+                .style(styles::button_primary)
+                .on_press(Message::StartBridge(bridge.id))
+        };
+
+        let rule = &bridge.rule;
+        let mut details = format!("{}, {}, {}", rule.filter, rule.qos, rule.retain);
+        if !rule.rewrite_from.is_empty() || !rule.rewrite_to.is_empty() {
+            details.push_str(&format!(
+                ", \"{}\" \u{2192} \"{}\"",
+                rule.rewrite_from, rule.rewrite_to
+            ));
+        }
+
+        let mut counters = format!(
+            "{} forwarded ({}), {} dropped",
+            bridge.forwarded,
+            format_bytes(bridge.forwarded_bytes as f64),
+            bridge.dropped
+        );
+        if let Some(last) = bridge.last_forwarded {
+            counters.push_str(&format!(", last at {}", last.format("%H:%M:%S")));
+        }
+
+        container(
+            column![
+                row![
+                    text(format!(
+                        "{} \u{2192} {}",
+                        name(&bridge.source),
+                        name(&bridge.target)
+                    ))
+                    .size(typography::SIZE_MD)
+                    .color(colors::TEXT_PRIMARY),
+                    text(state).size(typography::SIZE_SM).color(state_color),
+                    horizontal_space(),
+                    toggle.padding([spacing::XS, spacing::SM]),
+                    button(text(icons::TRASH).size(typography::SIZE_SM))
+                        .padding(spacing::XS)
+                        .style(styles::button_text)
+                        .on_press(Message::RemoveBridge(bridge.id)),
+                ]
+                .spacing(spacing::SM)
+                .align_y(iced::Alignment::Center),
+                text(details)
+                    .size(typography::SIZE_XS)
+                    .color(colors::TEXT_SECONDARY),
+                text(counters)
+                    .size(typography::SIZE_XS)
+                    .color(colors::TEXT_MUTED),
+            ]
+            .spacing(spacing::XS),
+        )
+        .padding(spacing::SM)
+        .style(styles::container_card)
+        .into()
+    }
+}
@@ -0,0 +1,145 @@
+//! Broker dashboard view, built from `$SYS` topics
+
+use iced::widget::{column, horizontal_rule, row, scrollable, text, Column};
+use iced::{Color, Element, Length};
+
+use crate::mqtt::broker_info::Counter;
+use crate::mqtt::stats::{format_bytes, format_rate};
+use crate::styles::{colors, icons, spacing, typography};
+
+use crate::app::types::ConnectionState;
+use crate::app::views::chart::line_chart;
+use crate::app::{Message, MqttUi};
+
+impl MqttUi {
+    pub fn view_broker_panel<'a>(&'a self, conn: &'a ConnectionState) -> Element<'a, Message> {
+        let broker = &conn.broker;
+
+        let mut content = Column::new().spacing(spacing::SM).padding(spacing::MD);
+        content = content.push(
+            row![
+                text(icons::SERVER)
+                    .size(typography::SIZE_MD)
+                    .color(colors::CYAN),
+                text(" Broker")
+                    .size(typography::SIZE_LG)
+                    .color(colors::CYAN)
+            ]
+            .spacing(spacing::XS),
+        );
+        content = content.push(horizontal_rule(1));
+
+        if !broker.has_data() {
+            let hint = if conn.config.subscribe_sys {
+                "Waiting for $SYS messages..."
+            } else {
+                "Enable $SYS/# in the connection settings to see broker statistics"
+            };
+            return content
+                .push(
+                    text(hint)
+                        .size(typography::SIZE_MD)
+                        .color(colors::TEXT_MUTED),
+                )
+                .into();
+        }
+
+        let number = |n: Option<u64>| n.map(|n| n.to_string());
+        let rows = [
+            ("Version:", broker.version.clone()),
+            ("Uptime:", broker.uptime.clone()),
+            ("Clients:", number(broker.clients_connected.value)),
+            ("Clients total:", number(broker.clients_total)),
+            ("Clients max:", number(broker.clients_maximum)),
+            ("Subscriptions:", number(broker.subscriptions)),
+            ("Retained:", number(broker.retained)),
+        ];
+        for (label, value) in rows {
+            if let Some(value) = value {
+                content = content.push(info_row(label, value));
+            }
+        }
+
+        if !broker.clients_connected.history.is_empty() {
+            content = content
+                .push(chart_title("Clients connected".to_string()))
+                .push(line_chart(vec![(
+                    broker.clients_connected.history.iter().copied().collect(),
+                    colors::CYAN,
+                )]));
+        }
+        content = content.push_maybe(counter_chart(
+            "Messages",
+            &broker.messages_received,
+            &broker.messages_sent,
+            |rate| format!("{}/s", format_rate(rate)),
+        ));
+        content = content.push_maybe(counter_chart(
+            "Bytes",
+            &broker.bytes_received,
+            &broker.bytes_sent,
+            |rate| format!("{}/s", format_bytes(rate)),
+        ));
+
+        scrollable(content).height(Length::Fill).into()
+    }
+}
+
+fn info_row<'a>(label: &'a str, value: String) -> Element<'a, Message> {
+    row![
+        text(label)
+            .size(typography::SIZE_SM)
+            .color(colors::TEXT_SECONDARY),
+        text(value)
+            .size(typography::SIZE_SM)
+            .color(colors::TEXT_PRIMARY),
+    ]
+    .spacing(spacing::SM)
+    .into()
+}
+
+fn chart_title<'a>(title: String) -> Element<'a, Message> {
+    text(title)
+        .size(typography::SIZE_SM)
+        .color(colors::TEXT_SECONDARY)
+        .into()
+}
+
+/// Received and sent rates of a pair of counters, once either has a rate
+fn counter_chart<'a>(
+    title: &str,
+    received: &Counter,
+    sent: &Counter,
+    format: impl Fn(f64) -> String,
+) -> Option<Element<'a, Message>> {
+    if received.rates.is_empty() && sent.rates.is_empty() {
+        return None;
+    }
+    let legend = |label: &str, counter: &Counter, color: Color| {
+        let rate = counter
+            .rate()
+            .map(&format)
+            .unwrap_or_else(|| "-".to_string());
+        text(format!("{} {}", label, rate))
+            .size(typography::SIZE_XS)
+            .color(color)
+    };
+
+    Some(
+        column![
+            row![
+                chart_title(title.to_string()),
+                legend("in", received, colors::GREEN),
+                legend("out", sent, colors::MAGENTA),
+            ]
+            .spacing(spacing::SM)
+            .align_y(iced::Alignment::Center),
+            line_chart(vec![
+                (received.rates.iter().copied().collect(), colors::GREEN),
+                (sent.rates.iter().copied().collect(), colors::MAGENTA),
+            ]),
+        ]
+        .spacing(spacing::XS)
+        .into(),
+    )
+}
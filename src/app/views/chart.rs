@@ -0,0 +1,75 @@
+//! Small line charts drawn on a canvas
+
+use iced::widget::canvas::{self, Canvas, Frame, Geometry, Path, Stroke};
+use iced::{mouse, Color, Element, Length, Point, Rectangle, Renderer, Theme};
+
+use crate::mqtt::broker_info::MAX_SAMPLES;
+use crate::styles::colors;
+
+use crate::app::Message;
+
+/// Chart height in pixels
+const HEIGHT: f32 = 60.0;
+
+/// Series sharing one y axis that starts at zero, newest sample on the right
+struct LineChart {
+    series: Vec<(Vec<f64>, Color)>,
+}
+
+impl<M> canvas::Program<M> for LineChart {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &(),
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        let (width, height) = (bounds.width, bounds.height - 1.0);
+
+        let baseline = Path::line(Point::new(0.0, height), Point::new(width, height));
+        frame.stroke(
+            &baseline,
+            Stroke::default().with_color(colors::BORDER_DEFAULT),
+        );
+
+        let max = self
+            .series
+            .iter()
+            .flat_map(|(samples, _)| samples.iter().copied())
+            .fold(0.0, f64::max);
+        if max > 0.0 {
+            let step = width / (MAX_SAMPLES - 1) as f32;
+            for (samples, color) in &self.series {
+                let line = Path::new(|builder| {
+                    let offset = (MAX_SAMPLES - samples.len()) as f32;
+                    for (i, value) in samples.iter().enumerate() {
+                        let point = Point::new(
+                            (offset + i as f32) * step,
+                            height - (value / max) as f32 * height * 0.9,
+                        );
+                        if i == 0 {
+                            builder.move_to(point);
+                        } else {
+                            builder.line_to(point);
+                        }
+                    }
+                });
+                frame.stroke(&line, Stroke::default().with_color(*color).with_width(1.5));
+            }
+        }
+
+        vec![frame.into_geometry()]
+    }
+}
+
+/// Chart of one or more series, scaled to the largest value shown
+pub fn line_chart<'a>(series: Vec<(Vec<f64>, Color)>) -> Element<'a, Message> {
+    Canvas::new(LineChart { series })
+        .width(Length::Fill)
+        .height(HEIGHT)
+        .into()
+}
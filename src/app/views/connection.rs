@@ -1,11 +1,14 @@
 //! Connection view (pane grid layout)
 
-use iced::widget::{button, column, container, pane_grid, row, text};
+use chrono::Utc;
+use iced::widget::{button, column, container, horizontal_space, pane_grid, row, scrollable, text};
 use iced::{Element, Length};
 
+use crate::mqtt::stats::{format_bytes, format_rate};
+use crate::mqtt::{ConnectionStatus, WINDOWS};
 use crate::styles::{self, colors, icons, spacing, typography};
 
-use crate::app::types::Pane;
+use crate::app::types::{ConnectionState, Pane};
 use crate::app::{Message, MqttUi};
 
 impl MqttUi {
@@ -17,11 +20,16 @@ impl MqttUi {
         let is_connected = conn.status.is_connected();
         let id_owned = id.to_string();
 
-        pane_grid::PaneGrid::new(&self.panes, move |_pane_id, pane, _is_maximized| {
+        let panes = pane_grid::PaneGrid::new(&self.panes, move |_pane_id, pane, _is_maximized| {
             let content: Element<Message> = match pane {
-                Pane::Publish => self.view_publish_panel(&id_owned, is_connected),
+                Pane::Publish => scrollable(column![
+                    self.view_publish_panel(&id_owned, is_connected),
+                    self.view_subscriptions_panel(conn, is_connected),
+                ])
+                .into(),
                 Pane::Topics => self.view_topic_tree(&id_owned),
                 Pane::Message => self.view_message_panel(&id_owned),
+                Pane::Broker => self.view_broker_panel(conn),
             };
 
             pane_grid::Content::new(
@@ -31,7 +39,119 @@ impl MqttUi {
                     .height(Length::Fill),
             )
         })
-        .on_resize(10, Message::PaneResized)
+        .on_resize(10, Message::PaneResized);
+
+        column![panes, self.view_status_bar(conn)].into()
+    }
+
+    /// Button accepting the SSH host key a connection stopped at, if any
+    pub fn view_trust_host_key(&self, conn: &ConnectionState) -> Option<Element<'_, Message>> {
+        conn.unknown_host_key.as_ref()?;
+        let button = button(text("Trust host key").size(typography::SIZE_SM))
+            .padding([spacing::XS, spacing::SM])
+            .style(styles::button_primary)
+            .on_press(Message::TrustHostKey(conn.config.id.clone()));
+        Some(button.into())
+    }
+
+    pub fn view_status_bar(&self, conn: &ConnectionState) -> Element<'_, Message> {
+        let status_color = match conn.status {
+            ConnectionStatus::Connected => colors::GREEN,
+            ConnectionStatus::Connecting | ConnectionStatus::Reconnecting { .. } => colors::AMBER,
+            ConnectionStatus::Error(_) => colors::RED,
+            _ => colors::TEXT_MUTED,
+        };
+
+        // Errors and retry progress take precedence over the last broker reason code
+        let (detail, detail_color) = match &conn.status {
+            ConnectionStatus::Error(e) => (e.clone(), colors::RED),
+            ConnectionStatus::Reconnecting { .. } => {
+                (conn.status.retry_text().unwrap_or_default(), colors::AMBER)
+            }
+            _ => (
+                conn.last_reason.clone().unwrap_or_default(),
+                colors::TEXT_SECONDARY,
+            ),
+        };
+
+        // A non-zero drop count means the topic tree is missing messages
+        let dropped = conn.inbox.dropped();
+        let buffer_usage = conn.inbox.usage();
+        let buffer_text = if dropped > 0 {
+            Some((format!("{} dropped", dropped), colors::RED))
+        } else if buffer_usage >= 0.01 {
+            Some((
+                format!("Buffer {:.0}%", buffer_usage * 100.0),
+                colors::AMBER,
+            ))
+        } else {
+            None
+        };
+
+        // e.g. "1s 12 msg/s 3.4 KB/s · 10s ... · 60s ... · 1.2 MB total"
+        let rates = conn.stats.rates(Utc::now().timestamp());
+        let mut traffic: Vec<String> = WINDOWS
+            .iter()
+            .zip(rates)
+            .map(|(secs, rate)| {
+                format!(
+                    "{}s {} msg/s {}/s",
+                    secs,
+                    format_rate(rate.messages),
+                    format_bytes(rate.bytes)
+                )
+            })
+            .collect();
+        traffic.push(format!(
+            "{} total",
+            format_bytes(conn.stats.total_bytes as f64)
+        ));
+
+        // Only worth showing when there is more than one node to pick from
+        let node = conn
+            .node
+            .as_ref()
+            .filter(|_| !conn.config.failover.endpoints.is_empty())
+            .map(|node| {
+                text(format!("Node {}", node))
+                    .size(typography::SIZE_SM)
+                    .color(colors::CYAN)
+            });
+
+        container(
+            row![
+                text(icons::CIRCLE_FILLED)
+                    .size(typography::SIZE_XS)
+                    .color(status_color),
+                text(conn.status.text().to_string())
+                    .size(typography::SIZE_SM)
+                    .color(status_color),
+                text(conn.config.version.to_string())
+                    .size(typography::SIZE_SM)
+                    .color(colors::TEXT_SECONDARY),
+                text(conn.config.uri())
+                    .size(typography::SIZE_SM)
+                    .color(colors::TEXT_MUTED),
+            ]
+            .push_maybe(node)
+            .push(
+                text(traffic.join(" · "))
+                    .size(typography::SIZE_SM)
+                    .color(colors::TEXT_SECONDARY),
+            )
+            .push(horizontal_space())
+            .push_maybe(
+                buffer_text
+                    .map(|(buffer, color)| text(buffer).size(typography::SIZE_SM).color(color)),
+            )
+            .push(text(detail).size(typography::SIZE_SM).color(detail_color))
+            .push_maybe(self.view_trust_host_key(conn))
+            .spacing(spacing::MD)
+            .align_y(iced::Alignment::Center),
+        )
+        .padding([spacing::XS, spacing::MD])
+        .width(Length::Fill)
+        .style(styles::container_panel)
         .into()
     }
 
@@ -1,15 +1,17 @@
 //! Connection form views
 
 use iced::widget::{
-    button, column, container, horizontal_rule, horizontal_space, pick_list, row, text,
-    text_input, Column,
+    button, column, container, horizontal_rule, horizontal_space, pick_list, row, scrollable, text,
+    text_input, toggler, Column,
 };
 use iced::{Element, Length};
 
-use crate::config::MqttProtocol;
+use crate::config::{
+    CredentialKind, JwtAlgorithm, MqttProtocol, MqttVersion, ProxyKind, RetainHandling, SshAuth,
+};
 use crate::styles::{self, colors, icons, spacing, typography};
 
-use crate::app::{Message, MqttUi};
+use crate::app::{Message, MqttUi, StageResult};
 
 impl MqttUi {
     pub fn view_connection_form(&self, editing_id: Option<&str>) -> Element<'_, Message> {
@@ -18,6 +20,7 @@ impl MqttUi {
         } else {
             "New Connection"
         };
+        let valid = self.form_keep_alive_error().is_none();
 
         let protocols: Vec<MqttProtocol> = vec![
             MqttProtocol::Mqtt,
@@ -68,20 +71,38 @@ impl MqttUi {
                 .width(Length::FillPortion(1)),
             ]
             .spacing(spacing::MD),
-            // Protocol
-            column![
-                text("Protocol")
-                    .size(typography::SIZE_SM)
-                    .color(colors::TEXT_SECONDARY),
-                pick_list(
-                    protocols,
-                    Some(self.form_protocol),
-                    Message::FormProtocolChanged
-                )
-                .padding(spacing::SM)
-                .width(Length::Fill)
+            // Protocol and MQTT version
+            row![
+                column![
+                    text("Protocol")
+                        .size(typography::SIZE_SM)
+                        .color(colors::TEXT_SECONDARY),
+                    pick_list(
+                        protocols,
+                        Some(self.form_protocol),
+                        Message::FormProtocolChanged
+                    )
+                    .padding(spacing::SM)
+                    .width(Length::Fill)
+                ]
+                .spacing(spacing::XS)
+                .width(Length::FillPortion(1)),
+                column![
+                    text("Version")
+                        .size(typography::SIZE_SM)
+                        .color(colors::TEXT_SECONDARY),
+                    pick_list(
+                        MqttVersion::all(),
+                        Some(self.form_version),
+                        Message::FormVersionChanged
+                    )
+                    .padding(spacing::SM)
+                    .width(Length::Fill)
+                ]
+                .spacing(spacing::XS)
+                .width(Length::FillPortion(1)),
             ]
-            .spacing(spacing::XS),
+            .spacing(spacing::MD),
             // Client ID
             column![
                 text("Client ID (optional)")
@@ -105,32 +126,54 @@ impl MqttUi {
             ]
             .spacing(spacing::XS),
             // Password
-            column![
-                text("Password (optional)")
-                    .size(typography::SIZE_SM)
-                    .color(colors::TEXT_SECONDARY),
-                text_input("Password", &self.form_password)
-                    .padding(spacing::SM)
-                    .secure(true)
-                    .style(styles::text_input_default)
-                    .on_input(Message::FormPasswordChanged)
-            ]
-            .spacing(spacing::XS),
-            horizontal_rule(1),
-            // Subscriptions
-            self.view_subscriptions_form(),
-            horizontal_rule(1),
-            // Buttons
+            self.view_password_form(),
+        ]
+        // MQTT 5 connect properties
+        .push_maybe((self.form_version == MqttVersion::V5).then(|| self.view_v5_form()))
+        // TLS
+        .push_maybe(self.form_protocol.is_tls().then(|| self.view_tls_form()))
+        // WebSocket
+        .push_maybe(
+            self.form_protocol
+                .is_websocket()
+                .then(|| self.view_websocket_form()),
+        )
+        // Last will
+        .push(self.view_last_will_form())
+        // Reconnect
+        .push(self.view_reconnect_form())
+        // Failover nodes
+        .push(self.view_failover_form())
+        // Proxy
+        .push(self.view_proxy_form())
+        // SSH tunnel
+        .push(self.view_ssh_form())
+        // Advanced session options
+        .push(self.view_advanced_form())
+        .push(horizontal_rule(1))
+        // Subscriptions
+        .push(self.view_subscriptions_form())
+        // Connection test results
+        .push_maybe(self.view_connection_test())
+        .push(horizontal_rule(1))
+        // Buttons
+        .push(
             row![
                 button(text("Cancel").size(typography::SIZE_MD))
                     .padding([spacing::SM, spacing::LG])
                     .style(styles::button_secondary)
                     .on_press(Message::FormCancel),
                 horizontal_space(),
+                button(text("Test").size(typography::SIZE_MD))
+                    .padding([spacing::SM, spacing::LG])
+                    .style(styles::button_secondary)
+                    .on_press_maybe(
+                        (!self.connection_test_running()).then_some(Message::FormTestConnection)
+                    ),
                 button(text("Save").size(typography::SIZE_MD))
                     .padding([spacing::SM, spacing::LG])
                     .style(styles::button_secondary)
-                    .on_press(Message::FormSaveConnection),
+                    .on_press_maybe(valid.then_some(Message::FormSaveConnection)),
                 button(
                     row![
                         text(icons::CONNECT).size(typography::SIZE_MD),
@@ -140,21 +183,789 @@ impl MqttUi {
                 )
                 .padding([spacing::SM, spacing::LG])
                 .style(styles::button_primary)
-                .on_press(Message::FormConnectAndSave),
+                .on_press_maybe(valid.then_some(Message::FormConnectAndSave)),
             ]
-            .spacing(spacing::MD)
-        ]
+            .spacing(spacing::MD),
+        )
         .spacing(spacing::MD)
         .padding(spacing::LG)
         .max_width(500);
 
-        container(
-            container(form).style(styles::container_panel),
+        scrollable(
+            container(container(form).style(styles::container_panel))
+                .width(Length::Fill)
+                .center_x(Length::Fill)
+                .padding(spacing::LG),
         )
-        .width(Length::Fill)
         .height(Length::Fill)
-        .center_x(Length::Fill)
-        .padding(spacing::LG)
+        .into()
+    }
+
+    pub fn view_v5_form(&self) -> Element<'_, Message> {
+        column![
+            horizontal_rule(1),
+            text("MQTT 5 properties")
+                .size(typography::SIZE_MD)
+                .color(colors::TEXT_PRIMARY),
+            row![
+                form_field(
+                    "Session expiry (s)",
+                    "0",
+                    &self.form_v5_session_expiry,
+                    Message::FormV5SessionExpiryChanged,
+                )
+                .width(Length::FillPortion(1)),
+                form_field(
+                    "Receive maximum",
+                    "65535",
+                    &self.form_v5_receive_maximum,
+                    Message::FormV5ReceiveMaximumChanged,
+                )
+                .width(Length::FillPortion(1)),
+                form_field(
+                    "Topic alias maximum",
+                    "0",
+                    &self.form_v5_topic_alias_max,
+                    Message::FormV5TopicAliasMaxChanged,
+                )
+                .width(Length::FillPortion(1)),
+            ]
+            .spacing(spacing::MD),
+        ]
+        .spacing(spacing::MD)
+        .into()
+    }
+
+    pub fn view_tls_form(&self) -> Element<'_, Message> {
+        column![
+            horizontal_rule(1),
+            text("TLS")
+                .size(typography::SIZE_MD)
+                .color(colors::TEXT_PRIMARY),
+            toggler(self.form_tls_use_system_roots)
+                .label("Trust system root certificates")
+                .text_size(typography::SIZE_SM)
+                .on_toggle(Message::FormTlsSystemRootsChanged),
+            form_field(
+                "CA certificate file (optional)",
+                "/path/to/ca.pem",
+                &self.form_tls_ca_file,
+                Message::FormTlsCaFileChanged,
+            ),
+            row![
+                form_field(
+                    "Client certificate (optional)",
+                    "/path/to/client.crt",
+                    &self.form_tls_client_cert,
+                    Message::FormTlsClientCertChanged,
+                )
+                .width(Length::FillPortion(1)),
+                form_field(
+                    "Client key (optional)",
+                    "/path/to/client.key",
+                    &self.form_tls_client_key,
+                    Message::FormTlsClientKeyChanged,
+                )
+                .width(Length::FillPortion(1)),
+            ]
+            .spacing(spacing::MD),
+            form_field(
+                "Server name (SNI, optional)",
+                "Defaults to host",
+                &self.form_tls_server_name,
+                Message::FormTlsServerNameChanged,
+            ),
+            toggler(self.form_tls_insecure)
+                .label("Skip certificate verification (insecure)")
+                .text_size(typography::SIZE_SM)
+                .on_toggle(Message::FormTlsInsecureChanged),
+        ]
+        .spacing(spacing::MD)
+        .into()
+    }
+
+    pub fn view_websocket_form(&self) -> Element<'_, Message> {
+        let mut content = column![
+            horizontal_rule(1),
+            text("WebSocket")
+                .size(typography::SIZE_MD)
+                .color(colors::TEXT_PRIMARY),
+            form_field(
+                "Path",
+                "/mqtt",
+                &self.form_ws_path,
+                Message::FormWsPathChanged
+            ),
+            row![
+                text("Upgrade headers")
+                    .size(typography::SIZE_SM)
+                    .color(colors::TEXT_SECONDARY),
+                horizontal_space(),
+                button(text(icons::PLUS).size(typography::SIZE_SM).center())
+                    .padding([spacing::XS, spacing::SM])
+                    .style(styles::button_secondary)
+                    .on_press(Message::FormAddWsHeader)
+            ]
+            .align_y(iced::Alignment::Center),
+        ]
+        .spacing(spacing::MD);
+
+        for (idx, (name, value)) in self.form_ws_headers.iter().enumerate() {
+            content = content.push(
+                row![
+                    text_input("Header", name)
+                        .padding(spacing::SM)
+                        .style(styles::text_input_default)
+                        .on_input(move |v| Message::FormWsHeaderNameChanged(idx, v))
+                        .width(Length::FillPortion(2)),
+                    text_input("Value", value)
+                        .padding(spacing::SM)
+                        .style(styles::text_input_default)
+                        .on_input(move |v| Message::FormWsHeaderValueChanged(idx, v))
+                        .width(Length::FillPortion(3)),
+                    button(text(icons::TIMES).size(typography::SIZE_SM).center())
+                        .padding([spacing::XS, spacing::SM])
+                        .style(styles::button_text)
+                        .on_press(Message::FormRemoveWsHeader(idx))
+                ]
+                .spacing(spacing::SM)
+                .align_y(iced::Alignment::Center),
+            );
+        }
+
+        content.into()
+    }
+
+    pub fn view_last_will_form(&self) -> Element<'_, Message> {
+        let mut content = column![
+            horizontal_rule(1),
+            text("Last will")
+                .size(typography::SIZE_MD)
+                .color(colors::TEXT_PRIMARY),
+            toggler(self.form_will_enabled)
+                .label("Publish a will message if the connection drops")
+                .text_size(typography::SIZE_SM)
+                .on_toggle(Message::FormWillEnabledChanged),
+        ]
+        .spacing(spacing::MD);
+
+        if !self.form_will_enabled {
+            return content.into();
+        }
+
+        content = content
+            .push(form_field(
+                "Topic",
+                "devices/mqttui/status",
+                &self.form_will_topic,
+                Message::FormWillTopicChanged,
+            ))
+            .push(form_field(
+                "Payload",
+                "offline",
+                &self.form_will_payload,
+                Message::FormWillPayloadChanged,
+            ))
+            .push(
+                row![
+                    text("QoS")
+                        .size(typography::SIZE_SM)
+                        .color(colors::TEXT_SECONDARY),
+                    pick_list(
+                        vec![0u8, 1, 2],
+                        Some(self.form_will_qos),
+                        Message::FormWillQosChanged
+                    )
+                    .padding(spacing::XS)
+                    .width(60),
+                    horizontal_space(),
+                    toggler(self.form_will_retain)
+                        .label("Retain")
+                        .text_size(typography::SIZE_SM)
+                        .on_toggle(Message::FormWillRetainChanged),
+                ]
+                .spacing(spacing::SM)
+                .align_y(iced::Alignment::Center),
+            );
+
+        if self.form_version != MqttVersion::V5 {
+            return content.into();
+        }
+
+        content = content
+            .push(
+                row![
+                    form_field(
+                        "Will delay (s)",
+                        "0",
+                        &self.form_will_delay,
+                        Message::FormWillDelayChanged,
+                    )
+                    .width(Length::FillPortion(1)),
+                    form_field(
+                        "Message expiry (s)",
+                        "Never",
+                        &self.form_will_expiry,
+                        Message::FormWillExpiryChanged,
+                    )
+                    .width(Length::FillPortion(1)),
+                ]
+                .spacing(spacing::MD),
+            )
+            .push(
+                row![
+                    form_field(
+                        "Content type (optional)",
+                        "text/plain",
+                        &self.form_will_content_type,
+                        Message::FormWillContentTypeChanged,
+                    )
+                    .width(Length::FillPortion(1)),
+                    form_field(
+                        "Response topic (optional)",
+                        "",
+                        &self.form_will_response_topic,
+                        Message::FormWillResponseTopicChanged,
+                    )
+                    .width(Length::FillPortion(1)),
+                ]
+                .spacing(spacing::MD),
+            )
+            .push(
+                row![
+                    text("User properties")
+                        .size(typography::SIZE_SM)
+                        .color(colors::TEXT_SECONDARY),
+                    horizontal_space(),
+                    button(text(icons::PLUS).size(typography::SIZE_SM).center())
+                        .padding([spacing::XS, spacing::SM])
+                        .style(styles::button_secondary)
+                        .on_press(Message::FormAddWillUserProperty)
+                ]
+                .align_y(iced::Alignment::Center),
+            );
+
+        for (idx, (name, value)) in self.form_will_user_properties.iter().enumerate() {
+            content = content.push(
+                row![
+                    text_input("Name", name)
+                        .padding(spacing::SM)
+                        .style(styles::text_input_default)
+                        .on_input(move |v| Message::FormWillUserPropertyNameChanged(idx, v))
+                        .width(Length::FillPortion(2)),
+                    text_input("Value", value)
+                        .padding(spacing::SM)
+                        .style(styles::text_input_default)
+                        .on_input(move |v| Message::FormWillUserPropertyValueChanged(idx, v))
+                        .width(Length::FillPortion(3)),
+                    button(text(icons::TIMES).size(typography::SIZE_SM).center())
+                        .padding([spacing::XS, spacing::SM])
+                        .style(styles::button_text)
+                        .on_press(Message::FormRemoveWillUserProperty(idx))
+                ]
+                .spacing(spacing::SM)
+                .align_y(iced::Alignment::Center),
+            );
+        }
+
+        content.into()
+    }
+
+    pub fn view_reconnect_form(&self) -> Element<'_, Message> {
+        let settings = self.form_reconnect_enabled.then(|| {
+            column![
+                row![
+                    form_field(
+                        "Initial backoff (ms)",
+                        "1000",
+                        &self.form_reconnect_initial_backoff,
+                        Message::FormReconnectInitialBackoffChanged,
+                    )
+                    .width(Length::FillPortion(1)),
+                    form_field(
+                        "Max backoff (ms)",
+                        "30000",
+                        &self.form_reconnect_max_backoff,
+                        Message::FormReconnectMaxBackoffChanged,
+                    )
+                    .width(Length::FillPortion(1)),
+                ]
+                .spacing(spacing::MD),
+                row![
+                    form_field(
+                        "Jitter (%)",
+                        "20",
+                        &self.form_reconnect_jitter,
+                        Message::FormReconnectJitterChanged,
+                    )
+                    .width(Length::FillPortion(1)),
+                    form_field(
+                        "Max attempts (optional)",
+                        "Unlimited",
+                        &self.form_reconnect_max_attempts,
+                        Message::FormReconnectMaxAttemptsChanged,
+                    )
+                    .width(Length::FillPortion(1)),
+                ]
+                .spacing(spacing::MD),
+            ]
+            .spacing(spacing::MD)
+        });
+
+        column![
+            horizontal_rule(1),
+            text("Reconnect")
+                .size(typography::SIZE_MD)
+                .color(colors::TEXT_PRIMARY),
+            toggler(self.form_reconnect_enabled)
+                .label("Reconnect automatically when the connection drops")
+                .text_size(typography::SIZE_SM)
+                .on_toggle(Message::FormReconnectEnabledChanged),
+        ]
+        .push_maybe(settings)
+        .spacing(spacing::MD)
+        .into()
+    }
+
+    /// Password field, or the settings of the provider generating it
+    pub fn view_password_form(&self) -> Element<'_, Message> {
+        let kind = self.form_credential_kind;
+        let label = match kind {
+            CredentialKind::Static => "Password (optional)",
+            CredentialKind::Jwt => "Password: JWT signed for each connection attempt",
+            CredentialKind::Sas => "Password: SAS token generated for each connection attempt",
+        };
+        let header = row![
+            text(label)
+                .size(typography::SIZE_SM)
+                .color(colors::TEXT_SECONDARY),
+            horizontal_space(),
+            pick_list(
+                CredentialKind::all(),
+                Some(kind),
+                Message::FormCredentialKindChanged
+            )
+            .text_size(typography::SIZE_SM)
+            .padding(spacing::XS),
+        ]
+        .align_y(iced::Alignment::Center);
+
+        let settings: Element<'_, Message> = match kind {
+            CredentialKind::Static => text_input("Password", &self.form_password)
+                .padding(spacing::SM)
+                .secure(true)
+                .style(styles::text_input_default)
+                .on_input(Message::FormPasswordChanged)
+                .into(),
+            CredentialKind::Jwt => column![
+                row![
+                    form_field(
+                        "Private key file (PEM)",
+                        "/path/to/device-key.pem",
+                        &self.form_jwt_key_file,
+                        Message::FormJwtKeyFileChanged,
+                    )
+                    .width(Length::FillPortion(3)),
+                    column![
+                        text("Algorithm")
+                            .size(typography::SIZE_SM)
+                            .color(colors::TEXT_SECONDARY),
+                        pick_list(
+                            JwtAlgorithm::all(),
+                            Some(self.form_jwt_algorithm),
+                            Message::FormJwtAlgorithmChanged
+                        )
+                        .padding(spacing::SM)
+                        .width(Length::Fill),
+                    ]
+                    .spacing(spacing::XS)
+                    .width(Length::FillPortion(1)),
+                    form_field(
+                        "Lifetime (s)",
+                        "3600",
+                        &self.form_jwt_lifetime,
+                        Message::FormJwtLifetimeChanged,
+                    )
+                    .width(Length::FillPortion(1)),
+                ]
+                .spacing(spacing::MD),
+                form_field(
+                    "Claims template (JSON)",
+                    "{}",
+                    &self.form_jwt_claims,
+                    Message::FormJwtClaimsChanged,
+                ),
+                text("{{iat}}, {{exp}}, {{client_id}} and {{host}} are filled in per token")
+                    .size(typography::SIZE_XS)
+                    .color(colors::TEXT_MUTED),
+            ]
+            .spacing(spacing::MD)
+            .into(),
+            CredentialKind::Sas => column![
+                row![
+                    column![
+                        text("Shared access key")
+                            .size(typography::SIZE_SM)
+                            .color(colors::TEXT_SECONDARY),
+                        text_input("Base64 key", &self.form_sas_key)
+                            .padding(spacing::SM)
+                            .secure(true)
+                            .style(styles::text_input_default)
+                            .on_input(Message::FormSasKeyChanged)
+                    ]
+                    .spacing(spacing::XS)
+                    .width(Length::FillPortion(3)),
+                    form_field(
+                        "Lifetime (s)",
+                        "3600",
+                        &self.form_sas_lifetime,
+                        Message::FormSasLifetimeChanged,
+                    )
+                    .width(Length::FillPortion(1)),
+                ]
+                .spacing(spacing::MD),
+                row![
+                    form_field(
+                        "Resource URI (optional)",
+                        "<host>/devices/<client id>",
+                        &self.form_sas_resource,
+                        Message::FormSasResourceChanged,
+                    )
+                    .width(Length::FillPortion(3)),
+                    form_field(
+                        "Policy name (optional)",
+                        "",
+                        &self.form_sas_key_name,
+                        Message::FormSasKeyNameChanged,
+                    )
+                    .width(Length::FillPortion(1)),
+                ]
+                .spacing(spacing::MD),
+            ]
+            .spacing(spacing::MD)
+            .into(),
+        };
+
+        column![header, settings].spacing(spacing::XS).into()
+    }
+
+    pub fn view_failover_form(&self) -> Element<'_, Message> {
+        let mut content = column![
+            horizontal_rule(1),
+            row![
+                text("Failover nodes")
+                    .size(typography::SIZE_MD)
+                    .color(colors::TEXT_PRIMARY),
+                horizontal_space(),
+                button(text(icons::PLUS).size(typography::SIZE_SM).center())
+                    .padding([spacing::XS, spacing::SM])
+                    .style(styles::button_secondary)
+                    .on_press(Message::FormAddFailoverEndpoint)
+            ]
+            .align_y(iced::Alignment::Center),
+        ]
+        .spacing(spacing::MD);
+
+        if self.form_failover_endpoints.is_empty() {
+            return content.into();
+        }
+
+        for (idx, (host, port)) in self.form_failover_endpoints.iter().enumerate() {
+            content = content.push(
+                row![
+                    text_input("broker-2.example.com", host)
+                        .padding(spacing::SM)
+                        .style(styles::text_input_default)
+                        .on_input(move |v| Message::FormFailoverHostChanged(idx, v))
+                        .width(Length::FillPortion(3)),
+                    text_input(&self.form_port, port)
+                        .padding(spacing::SM)
+                        .style(styles::text_input_default)
+                        .on_input(move |v| Message::FormFailoverPortChanged(idx, v))
+                        .width(Length::FillPortion(1)),
+                    button(text(icons::TIMES).size(typography::SIZE_SM).center())
+                        .padding([spacing::XS, spacing::SM])
+                        .style(styles::button_text)
+                        .on_press(Message::FormRemoveFailoverEndpoint(idx))
+                ]
+                .spacing(spacing::SM)
+                .align_y(iced::Alignment::Center),
+            );
+        }
+
+        content
+            .push(
+                toggler(self.form_failover_round_robin)
+                    .label("Round-robin: reconnect to the next node instead of the main host")
+                    .text_size(typography::SIZE_SM)
+                    .on_toggle(Message::FormFailoverRoundRobinChanged),
+            )
+            .push(
+                text("Nodes are tried in order after the main host when connecting")
+                    .size(typography::SIZE_XS)
+                    .color(colors::TEXT_MUTED),
+            )
+            .into()
+    }
+
+    pub fn view_proxy_form(&self) -> Element<'_, Message> {
+        let kind = self.form_proxy_kind;
+        let settings = (kind != ProxyKind::None).then(|| {
+            let default_port = kind.default_port().to_string();
+            column![
+                row![
+                    column![
+                        text("Proxy host")
+                            .size(typography::SIZE_SM)
+                            .color(colors::TEXT_SECONDARY),
+                        text_input("proxy.example.com", &self.form_proxy_host)
+                            .padding(spacing::SM)
+                            .style(styles::text_input_default)
+                            .on_input(Message::FormProxyHostChanged)
+                    ]
+                    .spacing(spacing::XS)
+                    .width(Length::FillPortion(3)),
+                    column![
+                        text("Port")
+                            .size(typography::SIZE_SM)
+                            .color(colors::TEXT_SECONDARY),
+                        text_input(&default_port, &self.form_proxy_port)
+                            .padding(spacing::SM)
+                            .style(styles::text_input_default)
+                            .on_input(Message::FormProxyPortChanged)
+                    ]
+                    .spacing(spacing::XS)
+                    .width(Length::FillPortion(1)),
+                ]
+                .spacing(spacing::MD),
+                row![
+                    form_field(
+                        "Proxy username (optional)",
+                        "",
+                        &self.form_proxy_username,
+                        Message::FormProxyUsernameChanged,
+                    )
+                    .width(Length::FillPortion(1)),
+                    column![
+                        text("Proxy password (optional)")
+                            .size(typography::SIZE_SM)
+                            .color(colors::TEXT_SECONDARY),
+                        text_input("", &self.form_proxy_password)
+                            .padding(spacing::SM)
+                            .secure(true)
+                            .style(styles::text_input_default)
+                            .on_input(Message::FormProxyPasswordChanged)
+                    ]
+                    .spacing(spacing::XS)
+                    .width(Length::FillPortion(1)),
+                ]
+                .spacing(spacing::MD),
+            ]
+            .spacing(spacing::MD)
+        });
+
+        column![
+            horizontal_rule(1),
+            row![
+                text("Proxy")
+                    .size(typography::SIZE_MD)
+                    .color(colors::TEXT_PRIMARY),
+                horizontal_space(),
+                pick_list(ProxyKind::all(), Some(kind), Message::FormProxyKindChanged)
+                    .padding(spacing::XS),
+            ]
+            .align_y(iced::Alignment::Center),
+        ]
+        .push_maybe(settings)
+        .spacing(spacing::MD)
+        .into()
+    }
+
+    pub fn view_ssh_form(&self) -> Element<'_, Message> {
+        let key_file = (self.form_ssh_auth == SshAuth::KeyFile).then(|| {
+            row![
+                form_field(
+                    "Private key file",
+                    "~/.ssh/id_ed25519",
+                    &self.form_ssh_key_file,
+                    Message::FormSshKeyFileChanged,
+                )
+                .width(Length::FillPortion(3)),
+                column![
+                    text("Passphrase (optional)")
+                        .size(typography::SIZE_SM)
+                        .color(colors::TEXT_SECONDARY),
+                    text_input("", &self.form_ssh_key_passphrase)
+                        .padding(spacing::SM)
+                        .secure(true)
+                        .style(styles::text_input_default)
+                        .on_input(Message::FormSshKeyPassphraseChanged)
+                ]
+                .spacing(spacing::XS)
+                .width(Length::FillPortion(2)),
+            ]
+            .spacing(spacing::MD)
+        });
+
+        let settings = self.form_ssh_enabled.then(|| {
+            column![
+                row![
+                    form_field(
+                        "Jump host",
+                        "bastion.example.com",
+                        &self.form_ssh_host,
+                        Message::FormSshHostChanged,
+                    )
+                    .width(Length::FillPortion(3)),
+                    form_field(
+                        "Port",
+                        "22",
+                        &self.form_ssh_port,
+                        Message::FormSshPortChanged
+                    )
+                    .width(Length::FillPortion(1)),
+                ]
+                .spacing(spacing::MD),
+                row![
+                    form_field("User", "", &self.form_ssh_user, Message::FormSshUserChanged)
+                        .width(Length::FillPortion(3)),
+                    column![
+                        text("Authentication")
+                            .size(typography::SIZE_SM)
+                            .color(colors::TEXT_SECONDARY),
+                        pick_list(
+                            SshAuth::all(),
+                            Some(self.form_ssh_auth),
+                            Message::FormSshAuthChanged
+                        )
+                        .padding(spacing::SM)
+                        .width(Length::Fill),
+                    ]
+                    .spacing(spacing::XS)
+                    .width(Length::FillPortion(2)),
+                ]
+                .spacing(spacing::MD),
+            ]
+            .push_maybe(key_file)
+            .push(
+                row![
+                    form_field(
+                        "Remote broker host (optional)",
+                        &self.form_host,
+                        &self.form_ssh_remote_host,
+                        Message::FormSshRemoteHostChanged,
+                    )
+                    .width(Length::FillPortion(3)),
+                    form_field(
+                        "Remote port",
+                        &self.form_port,
+                        &self.form_ssh_remote_port,
+                        Message::FormSshRemotePortChanged,
+                    )
+                    .width(Length::FillPortion(1)),
+                ]
+                .spacing(spacing::MD),
+            )
+            .push(
+                text("The broker address as seen from the jump host, e.g. localhost")
+                    .size(typography::SIZE_XS)
+                    .color(colors::TEXT_MUTED),
+            )
+            .spacing(spacing::MD)
+        });
+
+        column![
+            horizontal_rule(1),
+            text("SSH tunnel")
+                .size(typography::SIZE_MD)
+                .color(colors::TEXT_PRIMARY),
+            toggler(self.form_ssh_enabled)
+                .label("Forward the connection through an SSH jump host")
+                .text_size(typography::SIZE_SM)
+                .on_toggle(Message::FormSshEnabledChanged),
+        ]
+        .push_maybe(settings)
+        .spacing(spacing::MD)
+        .into()
+    }
+
+    pub fn view_advanced_form(&self) -> Element<'_, Message> {
+        let is_v5 = self.form_version == MqttVersion::V5;
+        let clean_label = if is_v5 {
+            "Clean start"
+        } else {
+            "Clean session"
+        };
+        let keep_alive_error = self
+            .form_keep_alive_error()
+            .map(|error| text(error).size(typography::SIZE_XS).color(colors::RED));
+        // v5 brokers announce their own limit for what we may send
+        let outgoing_packet_size = (!is_v5).then(|| {
+            form_field(
+                "Max outgoing packet (bytes)",
+                "262144",
+                &self.form_max_outgoing_packet,
+                Message::FormMaxOutgoingPacketChanged,
+            )
+            .width(Length::FillPortion(1))
+        });
+
+        column![
+            horizontal_rule(1),
+            text("Advanced")
+                .size(typography::SIZE_MD)
+                .color(colors::TEXT_PRIMARY),
+            toggler(self.form_clean_session)
+                .label(clean_label)
+                .text_size(typography::SIZE_SM)
+                .on_toggle(Message::FormCleanSessionChanged),
+            text("Persistent sessions need a fixed client ID")
+                .size(typography::SIZE_XS)
+                .color(colors::TEXT_MUTED),
+            column![row![
+                form_field(
+                    "Keep-alive (s)",
+                    "30",
+                    &self.form_keep_alive,
+                    Message::FormKeepAliveChanged,
+                )
+                .width(Length::FillPortion(1)),
+                form_field(
+                    "Max inflight",
+                    "100",
+                    &self.form_max_inflight,
+                    Message::FormMaxInflightChanged,
+                )
+                .width(Length::FillPortion(1)),
+            ]
+            .spacing(spacing::MD)]
+            .push_maybe(keep_alive_error)
+            .spacing(spacing::XS),
+            row![form_field(
+                "Max incoming packet (bytes)",
+                "262144",
+                &self.form_max_incoming_packet,
+                Message::FormMaxIncomingPacketChanged,
+            )
+            .width(Length::FillPortion(1))]
+            .push_maybe(outgoing_packet_size)
+            .spacing(spacing::MD),
+            row![
+                form_field(
+                    "Message buffer (MB)",
+                    "64",
+                    &self.form_message_buffer,
+                    Message::FormMessageBufferChanged,
+                )
+                .width(Length::FillPortion(1)),
+                horizontal_space().width(Length::FillPortion(1)),
+            ]
+            .spacing(spacing::MD),
+            text("Messages wait here until shown; new ones are dropped once it is full")
+                .size(typography::SIZE_XS)
+                .color(colors::TEXT_MUTED),
+        ]
+        .spacing(spacing::MD)
         .into()
     }
 
@@ -181,15 +992,21 @@ impl MqttUi {
             .align_y(iced::Alignment::Center),
         );
 
-        for (idx, (topic, qos)) in self.form_subscriptions.iter().enumerate() {
-            let topic_clone = topic.clone();
+        let is_v5 = self.form_version == MqttVersion::V5;
+        for (idx, sub) in self.form_subscriptions.iter().enumerate() {
+            let share_group = sub.share_group.clone().unwrap_or_default();
             let sub_row = row![
-                text_input("topic/#", &topic_clone)
+                text_input("Share group", &share_group)
+                    .padding(spacing::SM)
+                    .style(styles::text_input_default)
+                    .on_input(move |v| Message::FormSubscriptionShareGroupChanged(idx, v))
+                    .width(120),
+                text_input("topic/#", &sub.topic)
                     .padding(spacing::SM)
                     .style(styles::text_input_default)
                     .on_input(move |v| Message::FormSubscriptionTopicChanged(idx, v))
                     .width(Length::Fill),
-                pick_list(qos_options.clone(), Some(*qos), move |v| {
+                pick_list(qos_options.clone(), Some(sub.qos), move |v| {
                     Message::FormSubscriptionQosChanged(idx, v)
                 })
                 .padding(spacing::XS)
@@ -207,8 +1024,135 @@ impl MqttUi {
             .align_y(iced::Alignment::Center);
 
             content = content.push(sub_row);
+
+            // Subscription options only exist in MQTT 5
+            if is_v5 {
+                let identifier = sub.identifier.map(|id| id.to_string()).unwrap_or_default();
+                let options_row = row![
+                    toggler(sub.no_local)
+                        .label("No local")
+                        .text_size(typography::SIZE_SM)
+                        .on_toggle(move |v| Message::FormSubscriptionNoLocalChanged(idx, v)),
+                    toggler(sub.retain_as_published)
+                        .label("Retain as published")
+                        .text_size(typography::SIZE_SM)
+                        .on_toggle(move |v| {
+                            Message::FormSubscriptionRetainAsPublishedChanged(idx, v)
+                        }),
+                    pick_list(RetainHandling::all(), Some(sub.retain_handling), move |v| {
+                        Message::FormSubscriptionRetainHandlingChanged(idx, v)
+                    })
+                    .padding(spacing::XS)
+                    .text_size(typography::SIZE_SM),
+                    horizontal_space(),
+                    text_input("Subscription ID", &identifier)
+                        .padding(spacing::SM)
+                        .style(styles::text_input_default)
+                        .on_input(move |v| Message::FormSubscriptionIdentifierChanged(idx, v))
+                        .width(130),
+                ]
+                .spacing(spacing::MD)
+                .align_y(iced::Alignment::Center);
+                content = content.push(options_row);
+            }
         }
 
+        content = content.push(
+            toggler(self.form_subscribe_sys)
+                .label("Subscribe to $SYS/# for the broker dashboard")
+                .text_size(typography::SIZE_SM)
+                .on_toggle(Message::FormSubscribeSysChanged),
+        );
+
         content.into()
     }
+
+    fn connection_test_running(&self) -> bool {
+        self.connection_test
+            .iter()
+            .any(|(_, result)| matches!(result, StageResult::Pending | StageResult::Running))
+    }
+
+    /// Outcome and latency of each stage of the last connection test
+    pub fn view_connection_test(&self) -> Option<Element<'_, Message>> {
+        if self.connection_test.is_empty() {
+            return None;
+        }
+
+        let mut content = column![
+            horizontal_rule(1),
+            text("Connection test")
+                .size(typography::SIZE_MD)
+                .color(colors::TEXT_PRIMARY),
+        ]
+        .spacing(spacing::SM);
+
+        for (stage, result) in &self.connection_test {
+            let (icon, color, latency, detail) = match result {
+                StageResult::Pending => (icons::CIRCLE_EMPTY, colors::TEXT_MUTED, None, None),
+                StageResult::Running => (icons::CIRCLE_HALF, colors::AMBER, None, None),
+                StageResult::Passed { elapsed, detail } => (
+                    icons::CIRCLE_FILLED,
+                    colors::GREEN,
+                    Some(*elapsed),
+                    Some((detail.clone(), colors::TEXT_SECONDARY)),
+                ),
+                StageResult::Failed { elapsed, error } => (
+                    icons::CIRCLE_FILLED,
+                    colors::RED,
+                    Some(*elapsed),
+                    Some((error.clone(), colors::RED)),
+                ),
+                StageResult::Skipped(reason) => (
+                    icons::CIRCLE_EMPTY,
+                    colors::TEXT_MUTED,
+                    None,
+                    Some((reason.clone(), colors::TEXT_MUTED)),
+                ),
+            };
+            let latency = latency.map(|elapsed| {
+                text(format!("{} ms", elapsed.as_millis()))
+                    .size(typography::SIZE_SM)
+                    .color(colors::TEXT_SECONDARY)
+            });
+            let detail =
+                detail.map(|(detail, color)| text(detail).size(typography::SIZE_XS).color(color));
+
+            content = content.push(
+                column![row![
+                    text(icon).size(typography::SIZE_SM).color(color),
+                    text(stage.label())
+                        .size(typography::SIZE_SM)
+                        .color(colors::TEXT_PRIMARY),
+                    horizontal_space(),
+                ]
+                .push_maybe(latency)
+                .spacing(spacing::SM)
+                .align_y(iced::Alignment::Center)]
+                .push_maybe(detail)
+                .spacing(2),
+            );
+        }
+
+        Some(content.into())
+    }
+}
+
+/// Labelled text input, laid out like the fields of the main form
+pub(super) fn form_field<'a>(
+    label: &'a str,
+    placeholder: &'a str,
+    value: &'a str,
+    on_input: impl Fn(String) -> Message + 'a,
+) -> Column<'a, Message> {
+    column![
+        text(label)
+            .size(typography::SIZE_SM)
+            .color(colors::TEXT_SECONDARY),
+        text_input(placeholder, value)
+            .padding(spacing::SM)
+            .style(styles::text_input_default)
+            .on_input(on_input)
+    ]
+    .spacing(spacing::XS)
 }
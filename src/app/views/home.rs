@@ -1,8 +1,8 @@
 //! Home view with connection cards
 
 use iced::widget::{
-    button, column, container, horizontal_rule, horizontal_space, row, scrollable, text, Column,
-    Row,
+    button, column, container, horizontal_rule, horizontal_space, row, scrollable, text,
+    text_input, toggler, Column, Row,
 };
 use iced::{Element, Length};
 
@@ -25,6 +25,14 @@ impl MqttUi {
                     .size(typography::SIZE_3XL)
                     .color(colors::TEXT_PRIMARY),
                 horizontal_space(),
+                button(text("Snapshots").size(typography::SIZE_MD))
+                    .padding([spacing::SM, spacing::MD])
+                    .style(styles::button_secondary)
+                    .on_press(Message::ShowSnapshots),
+                button(text("Bridges").size(typography::SIZE_MD))
+                    .padding([spacing::SM, spacing::MD])
+                    .style(styles::button_secondary)
+                    .on_press(Message::ShowBridges),
                 button(
                     row![
                         text(icons::PLUS).size(typography::SIZE_MD),
@@ -36,10 +44,12 @@ impl MqttUi {
                 .style(styles::button_primary)
                 .on_press(Message::NewConnection)
             ]
+            .spacing(spacing::SM)
             .align_y(iced::Alignment::Center),
         );
 
         content = content.push(horizontal_rule(1));
+        content = content.push(self.view_local_broker());
 
         // Connection cards
         if self.config.connections.is_empty() {
@@ -84,6 +94,97 @@ impl MqttUi {
             .into()
     }
 
+    /// Start the embedded broker, or where it is running
+    fn view_local_broker(&self) -> Element<'_, Message> {
+        let title = text("Local broker")
+            .size(typography::SIZE_LG)
+            .color(colors::TEXT_PRIMARY);
+
+        let controls: Element<'_, Message> = match self.local_broker {
+            Some(addr) => {
+                let connect = self
+                    .config
+                    .embedded_broker
+                    .connection_id
+                    .clone()
+                    .filter(|id| self.config.get_connection(id).is_some())
+                    .map(|id| {
+                        button(text("Connect").size(typography::SIZE_SM))
+                            .padding([spacing::XS, spacing::SM])
+                            .style(styles::button_primary)
+                            .on_press(Message::Connect(id))
+                    });
+                row![
+                    text(icons::CIRCLE_FILLED)
+                        .size(typography::SIZE_XS)
+                        .color(colors::GREEN),
+                    text(format!("Running on {} until mqttui exits", addr))
+                        .size(typography::SIZE_SM)
+                        .color(colors::GREEN),
+                    horizontal_space(),
+                ]
+                .push_maybe(connect)
+                .spacing(spacing::SM)
+                .align_y(iced::Alignment::Center)
+                .into()
+            }
+            None => row![
+                text_input("Port", &self.local_broker_port)
+                    .padding(spacing::SM)
+                    .style(styles::text_input_default)
+                    .on_input(Message::LocalBrokerPortChanged)
+                    .width(80),
+                text_input("Username (optional)", &self.local_broker_username)
+                    .padding(spacing::SM)
+                    .style(styles::text_input_default)
+                    .on_input(Message::LocalBrokerUsernameChanged),
+                text_input("Password", &self.local_broker_password)
+                    .padding(spacing::SM)
+                    .secure(true)
+                    .style(styles::text_input_default)
+                    .on_input(Message::LocalBrokerPasswordChanged),
+                toggler(self.local_broker_listen_all)
+                    .label("Allow other devices")
+                    .text_size(typography::SIZE_SM)
+                    .on_toggle(Message::LocalBrokerListenAllChanged)
+                    .width(Length::Shrink),
+                button(text("Start").size(typography::SIZE_SM))
+                    .padding([spacing::XS, spacing::MD])
+                    .style(styles::button_primary)
+                    .on_press_maybe(
+                        (!self.local_broker_starting).then_some(Message::StartLocalBroker)
+                    ),
+            ]
+            .spacing(spacing::SM)
+            .align_y(iced::Alignment::Center)
+            .into(),
+        };
+
+        // rumqttd can't be shut down, so say so before it is started
+        let notice = self.local_broker.is_none().then(|| {
+            text("Once started, the broker keeps running until mqttui exits")
+                .size(typography::SIZE_XS)
+                .color(colors::TEXT_MUTED)
+        });
+
+        let error = self.local_broker_error.as_ref().map(|e| {
+            text(e.as_str())
+                .size(typography::SIZE_XS)
+                .color(colors::RED)
+        });
+
+        container(
+            column![title, controls]
+                .push_maybe(notice)
+                .push_maybe(error)
+                .spacing(spacing::SM),
+        )
+        .padding(spacing::MD)
+        .width(Length::Fill)
+        .style(styles::container_card)
+        .into()
+    }
+
     pub fn view_connection_card(
         &self,
         config: &ConnectionConfig,
@@ -92,21 +193,36 @@ impl MqttUi {
         let status_text = match status {
             ConnectionStatus::Connected => "Connected",
             ConnectionStatus::Connecting => "Connecting...",
+            ConnectionStatus::Reconnecting { .. } => "Reconnecting...",
             ConnectionStatus::Disconnected => "Disconnected",
             ConnectionStatus::Error(_) => "Error",
         };
 
         let status_color = match status {
             ConnectionStatus::Connected => colors::GREEN,
-            ConnectionStatus::Connecting => colors::AMBER,
+            ConnectionStatus::Connecting | ConnectionStatus::Reconnecting { .. } => colors::AMBER,
             ConnectionStatus::Error(_) => colors::RED,
             _ => colors::TEXT_MUTED,
         };
 
+        // Error text, retry progress, or the last reason code a v5 broker sent
+        let detail = match status {
+            ConnectionStatus::Error(e) => Some((e.clone(), colors::RED)),
+            ConnectionStatus::Reconnecting { .. } => {
+                status.retry_text().map(|retry| (retry, colors::AMBER))
+            }
+            _ => self
+                .connections
+                .get(&config.id)
+                .and_then(|c| c.last_reason.clone())
+                .map(|reason| (reason, colors::TEXT_SECONDARY)),
+        };
+
         let id = config.id.clone();
-        let is_connected = status.is_connected();
+        // Reconnecting connections can be stopped like connected ones
+        let can_disconnect = status.is_connected() || status.is_reconnecting();
 
-        let connect_btn = if is_connected {
+        let connect_btn = if can_disconnect {
             button(
                 row![
                     text(icons::DISCONNECT).size(typography::SIZE_SM),
@@ -133,16 +249,49 @@ impl MqttUi {
         let name = config.name.clone();
         let uri = config.uri();
 
-        let card_content = column![
-            row![
-                text(icons::CIRCLE_FILLED)
+        let status_row = row![
+            text(icons::CIRCLE_FILLED)
+                .size(typography::SIZE_XS)
+                .color(status_color),
+            text(status_text)
+                .size(typography::SIZE_SM)
+                .color(status_color),
+        ]
+        .spacing(spacing::XS);
+        let detail_text =
+            detail.map(|(detail, color)| text(detail).size(typography::SIZE_XS).color(color));
+        let node_text = self
+            .connections
+            .get(&config.id)
+            .and_then(|c| c.node.as_ref())
+            .filter(|_| !config.failover.endpoints.is_empty())
+            .map(|node| {
+                text(format!("Attached to {}", node))
                     .size(typography::SIZE_XS)
-                    .color(status_color),
-                text(status_text)
-                    .size(typography::SIZE_SM)
-                    .color(status_color),
-            ]
-            .spacing(spacing::XS),
+                    .color(colors::CYAN)
+            });
+        let trust_button = self
+            .connections
+            .get(&config.id)
+            .and_then(|c| self.view_trust_host_key(c));
+        let dropped_text = self
+            .connections
+            .get(&config.id)
+            .map(|c| c.inbox.dropped())
+            .filter(|&dropped| dropped > 0)
+            .map(|dropped| {
+                text(format!("{} messages dropped", dropped))
+                    .size(typography::SIZE_XS)
+                    .color(colors::RED)
+            });
+
+        let card_content = column![
+            column![status_row]
+                .push_maybe(detail_text)
+                .push_maybe(trust_button)
+                .push_maybe(node_text)
+                .push_maybe(dropped_text)
+                .spacing(spacing::XS),
             text(name).size(typography::SIZE_LG).color(colors::TEXT_PRIMARY),
             text(uri).size(typography::SIZE_SM).color(colors::TEXT_SECONDARY),
             row![
@@ -3,6 +3,7 @@
 use iced::widget::{column, container, horizontal_rule, row, scrollable, text, Column};
 use iced::{Element, Length};
 
+use crate::mqtt::MessageProperties;
 use crate::styles::{self, colors, icons, spacing, typography};
 
 use crate::app::{Message, MqttUi};
@@ -31,6 +32,29 @@ impl MqttUi {
             let retain = if msg.retain { "Yes" } else { "No" };
             let time = msg.timestamp.format("%H:%M:%S").to_string();
             let payload = msg.formatted_payload();
+            let properties = msg.properties.as_ref().map(message_properties);
+            let rpc_reply = self.connections.get(id).and_then(|conn| {
+                conn.publishes
+                    .iter()
+                    .find(|p| p.rpc.as_ref().is_some_and(|call| call.is_reply(&msg)))
+            });
+            let rpc_reply = rpc_reply.and_then(|record| {
+                let reply = record.rpc.as_ref()?.reply.as_ref()?;
+                Some(
+                    row![
+                        text("Reply to:")
+                            .size(typography::SIZE_SM)
+                            .color(colors::TEXT_SECONDARY),
+                        text(record.topic.clone())
+                            .size(typography::SIZE_SM)
+                            .color(colors::MAGENTA),
+                        text(format!("in {} ms", reply.round_trip.as_millis()))
+                            .size(typography::SIZE_SM)
+                            .color(colors::GREEN),
+                    ]
+                    .spacing(spacing::SM),
+                )
+            });
 
             content = content.push(
                 column![
@@ -63,21 +87,27 @@ impl MqttUi {
                         text(time).size(typography::SIZE_SM).color(colors::TEXT_PRIMARY)
                     ]
                     .spacing(spacing::SM),
-                    horizontal_rule(1),
+                ]
+                .push_maybe(rpc_reply)
+                .push_maybe(properties)
+                .push(horizontal_rule(1))
+                .push(
                     text("Payload:")
                         .size(typography::SIZE_SM)
                         .color(colors::TEXT_SECONDARY),
+                )
+                .push(
                     scrollable(
                         container(
                             text(payload)
                                 .size(typography::SIZE_SM)
-                                .color(colors::GREEN)
+                                .color(colors::GREEN),
                         )
                         .padding(spacing::MD)
-                        .style(styles::container_code)
+                        .style(styles::container_code),
                     )
-                    .height(Length::Fill)
-                ]
+                    .height(Length::Fill),
+                )
                 .spacing(spacing::SM),
             );
         } else {
@@ -91,3 +121,47 @@ impl MqttUi {
         content.height(Length::Fill).into()
     }
 }
+
+/// MQTT 5 PUBLISH properties, one labelled row per property that is set
+fn message_properties<'a>(properties: &MessageProperties) -> Element<'a, Message> {
+    let mut rows: Vec<(String, String)> = Vec::new();
+    if let Some(content_type) = &properties.content_type {
+        rows.push(("Content type:".to_string(), content_type.clone()));
+    }
+    if let Some(response_topic) = &properties.response_topic {
+        rows.push(("Response topic:".to_string(), response_topic.clone()));
+    }
+    if let Some(correlation) = properties.correlation_data_display() {
+        rows.push(("Correlation data:".to_string(), correlation));
+    }
+    if let Some(expiry) = properties.message_expiry_interval {
+        rows.push(("Expires in:".to_string(), format!("{}s", expiry)));
+    }
+    if !properties.subscription_identifiers.is_empty() {
+        let ids: Vec<String> = properties
+            .subscription_identifiers
+            .iter()
+            .map(u32::to_string)
+            .collect();
+        rows.push(("Subscription IDs:".to_string(), ids.join(", ")));
+    }
+    for (key, value) in &properties.user_properties {
+        rows.push((format!("{}:", key), value.clone()));
+    }
+
+    let mut content = Column::new().spacing(spacing::SM);
+    for (label, value) in rows {
+        content = content.push(
+            row![
+                text(label)
+                    .size(typography::SIZE_SM)
+                    .color(colors::TEXT_SECONDARY),
+                text(value)
+                    .size(typography::SIZE_SM)
+                    .color(colors::TEXT_PRIMARY),
+            ]
+            .spacing(spacing::SM),
+        );
+    }
+    content.into()
+}
@@ -6,13 +6,23 @@
 //! - connection_form: New/edit connection form
 //! - connection: Active connection view with pane grid
 //! - publish: Publish panel
+//! - subscriptions: Subscription manager
 //! - topic_tree: Topic tree panel
 //! - message: Message panel
+//! - broker: Broker dashboard from $SYS topics
+//! - snapshots: Retained message snapshot and restore
+//! - bridges: Live forwarding between open connections
+//! - chart: Line charts used by the broker dashboard
 
+mod bridges;
+mod broker;
+mod chart;
 mod connection;
 mod connection_form;
 mod home;
 mod message;
 mod publish;
+mod snapshots;
+mod subscriptions;
 mod tabs;
 pub mod topic_tree;
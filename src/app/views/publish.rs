@@ -1,16 +1,130 @@
 //! Publish panel view
 
-use iced::widget::{button, column, horizontal_rule, pick_list, row, text, text_input, toggler};
+use iced::widget::{
+    button, column, horizontal_rule, horizontal_space, pick_list, row, text, text_input, toggler,
+    Column,
+};
 use iced::{Element, Length};
 
+use crate::config::MqttVersion;
 use crate::styles::{self, colors, icons, spacing, typography};
 
+use crate::app::types::{DeliveryStatus, PublishJob, PublishRecord};
 use crate::app::{Message, MqttUi};
 
 impl MqttUi {
-    pub fn view_publish_panel(&self, _id: &str, is_connected: bool) -> Element<'_, Message> {
+    pub fn view_publish_panel(&self, id: &str, is_connected: bool) -> Element<'_, Message> {
         let qos_options = vec![0u8, 1, 2];
 
+        // Newest first
+        let mut deliveries = Column::new().spacing(spacing::SM);
+        let mut jobs = Column::new().spacing(spacing::SM);
+        let mut version = MqttVersion::V311;
+        if let Some(conn) = self.connections.get(id) {
+            version = conn.config.version;
+            for job in &conn.jobs {
+                jobs = jobs.push(job_row(id, job));
+            }
+            for record in conn.publishes.iter().rev() {
+                deliveries = deliveries.push(delivery_row(record));
+            }
+        }
+
+        // Requests carry their reply topic and correlation id, in the payload
+        // on MQTT 3.1.1
+        let rpc_options = self.publish_rpc.then(|| {
+            column![
+                text("Reply topic")
+                    .size(typography::SIZE_SM)
+                    .color(colors::TEXT_SECONDARY),
+                text_input("mqttui/reply/<connection>", &self.publish_reply_topic)
+                    .padding(spacing::SM)
+                    .style(styles::text_input_default)
+                    .on_input(Message::PublishReplyTopicChanged)
+            ]
+            .push_maybe((version == MqttVersion::V311).then(|| {
+                column![
+                    text("Correlation field")
+                        .size(typography::SIZE_SM)
+                        .color(colors::TEXT_SECONDARY),
+                    text_input("correlation_id", &self.publish_correlation_field)
+                        .padding(spacing::SM)
+                        .style(styles::text_input_default)
+                        .on_input(Message::PublishCorrelationFieldChanged)
+                ]
+                .spacing(spacing::XS)
+            }))
+            .spacing(spacing::XS)
+        });
+
+        // Every N ms, N times, from a wall-clock time; blanks mean now and
+        // until cancelled
+        let repeat_options = self.publish_repeat.then(|| {
+            column![
+                row![
+                    column![
+                        text("Every (ms)")
+                            .size(typography::SIZE_SM)
+                            .color(colors::TEXT_SECONDARY),
+                        text_input("1000", &self.publish_interval)
+                            .padding(spacing::SM)
+                            .style(styles::text_input_default)
+                            .on_input(Message::PublishIntervalChanged)
+                    ]
+                    .spacing(spacing::XS),
+                    column![
+                        text("Times")
+                            .size(typography::SIZE_SM)
+                            .color(colors::TEXT_SECONDARY),
+                        text_input("Until cancelled", &self.publish_times)
+                            .padding(spacing::SM)
+                            .style(styles::text_input_default)
+                            .on_input(Message::PublishTimesChanged)
+                    ]
+                    .spacing(spacing::XS),
+                ]
+                .spacing(spacing::MD),
+                text("Start at")
+                    .size(typography::SIZE_SM)
+                    .color(colors::TEXT_SECONDARY),
+                text_input("Now, HH:MM:SS or YYYY-MM-DD HH:MM", &self.publish_start_at)
+                    .padding(spacing::SM)
+                    .style(styles::text_input_default)
+                    .on_input(Message::PublishStartAtChanged)
+            ]
+            .spacing(spacing::XS)
+        });
+        let job_error = self.publish_job_error.as_ref().map(|e| {
+            text(e.as_str())
+                .size(typography::SIZE_XS)
+                .color(colors::RED)
+        });
+
+        let (send_label, send_message) = if self.publish_repeat {
+            (" Start", Message::StartPublishJob)
+        } else {
+            (" Send", Message::SendMessage)
+        };
+        let send_button = if is_connected {
+            button(
+                row![
+                    text(icons::SEND).size(typography::SIZE_MD),
+                    text(send_label).size(typography::SIZE_MD)
+                ]
+                .spacing(spacing::XS)
+                .width(Length::Fill)
+            )
+            .padding([spacing::SM, spacing::MD])
+            .width(Length::Fill)
+            .style(styles::button_primary)
+            .on_press(send_message)
+        } else {
+            button(text("Send").size(typography::SIZE_MD).width(Length::Fill))
+                .padding([spacing::SM, spacing::MD])
+                .width(Length::Fill)
+                .style(styles::button_secondary)
+        };
+
         column![
             row![
                 text(icons::SEND).size(typography::SIZE_MD).color(colors::CYAN),
@@ -64,28 +178,113 @@ impl MqttUi {
                 .width(Length::FillPortion(1)),
             ]
             .spacing(spacing::MD),
-            if is_connected {
-                button(
-                    row![
-                        text(icons::SEND).size(typography::SIZE_MD),
-                        text(" Send").size(typography::SIZE_MD)
-                    ]
-                    .spacing(spacing::XS)
-                    .width(Length::Fill)
-                )
-                .padding([spacing::SM, spacing::MD])
-                .width(Length::Fill)
-                .style(styles::button_primary)
-                .on_press(Message::SendMessage)
-            } else {
-                button(text("Send").size(typography::SIZE_MD).width(Length::Fill))
-                    .padding([spacing::SM, spacing::MD])
-                    .width(Length::Fill)
-                    .style(styles::button_secondary)
-            }
+            toggler(self.publish_rpc)
+                .label("Request/response")
+                .text_size(typography::SIZE_SM)
+                .on_toggle(Message::PublishRpcChanged),
         ]
+        .push_maybe(rpc_options)
+        .push(
+            toggler(self.publish_repeat)
+                .label("Repeat or schedule")
+                .text_size(typography::SIZE_SM)
+                .on_toggle(Message::PublishRepeatChanged),
+        )
+        .push_maybe(repeat_options)
+        .push(send_button)
+        .push_maybe(job_error)
+        .push(jobs)
+        .push(deliveries)
         .spacing(spacing::MD)
         .padding(spacing::MD)
         .into()
     }
 }
+
+fn job_row<'a>(conn_id: &str, job: &'a PublishJob) -> Element<'a, Message> {
+    let mut progress = match job.count {
+        Some(count) => format!("{}/{}", job.sent, count),
+        None => job.sent.to_string(),
+    };
+    if job.skipped > 0 {
+        progress.push_str(&format!(", {} skipped", job.skipped));
+    }
+    let (state, state_color) = if job.is_done() {
+        ("Done".to_string(), colors::GREEN)
+    } else if job.paused {
+        ("Paused".to_string(), colors::TEXT_MUTED)
+    } else if let Some(at) = job.start_at {
+        let at = at.with_timezone(&chrono::Local);
+        (format!("Starts {}", at.format("%H:%M:%S")), colors::AMBER)
+    } else {
+        let interval = job.interval.as_millis();
+        (format!("Every {} ms", interval), colors::CYAN)
+    };
+
+    let id = (conn_id.to_string(), job.id);
+    let (toggle_icon, toggle_message) = if job.paused {
+        (icons::PLAY, Message::ResumePublishJob(id.0, id.1))
+    } else {
+        (icons::PAUSE, Message::PausePublishJob(id.0, id.1))
+    };
+    let toggle = (!job.is_done()).then(|| {
+        button(text(toggle_icon).size(typography::SIZE_XS))
+            .padding(spacing::XS)
+            .style(styles::button_text)
+            .on_press(toggle_message)
+    });
+
+    row![
+        text(&job.topic)
+            .size(typography::SIZE_SM)
+            .color(colors::TEXT_PRIMARY),
+        text(progress)
+            .size(typography::SIZE_XS)
+            .color(colors::TEXT_MUTED),
+        horizontal_space(),
+        text(state).size(typography::SIZE_XS).color(state_color),
+    ]
+    .push_maybe(toggle)
+    .push(
+        button(text(icons::TIMES).size(typography::SIZE_XS))
+            .padding(spacing::XS)
+            .style(styles::button_text)
+            .on_press(Message::CancelPublishJob(conn_id.to_string(), job.id)),
+    )
+    .spacing(spacing::SM)
+    .align_y(iced::Alignment::Center)
+    .into()
+}
+
+fn delivery_row(record: &PublishRecord) -> Element<'_, Message> {
+    let (status, status_color) = match &record.status {
+        DeliveryStatus::Pending => ("Pending".to_string(), colors::AMBER),
+        DeliveryStatus::Sent => ("Sent".to_string(), colors::TEXT_SECONDARY),
+        DeliveryStatus::Acked(rtt) => (format!("Acked in {} ms", rtt.as_millis()), colors::GREEN),
+        DeliveryStatus::Failed(reason) => (reason.clone(), colors::RED),
+        DeliveryStatus::TimedOut => ("Timed out".to_string(), colors::RED),
+    };
+    // A request is done once its reply arrives
+    let (status, status_color) = match record.rpc.as_ref().and_then(|call| call.reply.as_ref()) {
+        Some(reply) => (
+            format!("Reply in {} ms", reply.round_trip.as_millis()),
+            colors::GREEN,
+        ),
+        None if record.awaits_reply() => ("Awaiting reply".to_string(), colors::AMBER),
+        None => (status, status_color),
+    };
+
+    row![
+        text(&record.topic)
+            .size(typography::SIZE_SM)
+            .color(colors::TEXT_PRIMARY),
+        text(format!("QoS {}", record.qos))
+            .size(typography::SIZE_XS)
+            .color(colors::TEXT_MUTED),
+        horizontal_space(),
+        text(status).size(typography::SIZE_XS).color(status_color),
+    ]
+    .spacing(spacing::SM)
+    .align_y(iced::Alignment::Center)
+    .into()
+}
@@ -0,0 +1,237 @@
+//! Retained message snapshots: back up a broker's retained state to a file and
+//! publish it again, after a dry-run preview
+
+use iced::widget::{
+    button, column, container, horizontal_rule, horizontal_space, pick_list, row, scrollable, text,
+    Column,
+};
+use iced::{Element, Length};
+
+use crate::mqtt::stats::format_bytes;
+use crate::mqtt::RetainedSnapshot;
+use crate::styles::{self, colors, spacing, typography};
+
+use super::connection_form::form_field;
+use crate::app::types::{ConnectionChoice, TransferStatus};
+use crate::app::{Message, MqttUi};
+
+/// Topics listed in the restore preview
+const MAX_PREVIEW_TOPICS: usize = 50;
+
+impl MqttUi {
+    pub fn view_snapshots(&self) -> Element<'_, Message> {
+        let choices: Vec<ConnectionChoice> = self
+            .config
+            .connections
+            .iter()
+            .map(|c| ConnectionChoice {
+                id: c.id.clone(),
+                name: c.name.clone(),
+            })
+            .collect();
+        let selected =
+            |id: &Option<String>| choices.iter().find(|c| Some(&c.id) == id.as_ref()).cloned();
+
+        let snapshot_running = matches!(self.snapshot_status, Some(TransferStatus::Running(_)));
+        let restore_running = matches!(self.restore_status, Some(TransferStatus::Running(_)));
+
+        let snapshot = column![
+            text("Take snapshot")
+                .size(typography::SIZE_MD)
+                .color(colors::TEXT_PRIMARY),
+            text("Collects retained messages until none arrive for the quiet period")
+                .size(typography::SIZE_XS)
+                .color(colors::TEXT_MUTED),
+            column![
+                text("Connection")
+                    .size(typography::SIZE_SM)
+                    .color(colors::TEXT_SECONDARY),
+                pick_list(
+                    choices.clone(),
+                    selected(&self.snapshot_source),
+                    Message::SnapshotSourceChanged
+                )
+                .placeholder("Choose a connection")
+                .padding(spacing::SM)
+                .width(Length::Fill),
+            ]
+            .spacing(spacing::XS),
+            row![
+                form_field(
+                    "Filter",
+                    "#",
+                    &self.snapshot_filter,
+                    Message::SnapshotFilterChanged
+                )
+                .width(Length::FillPortion(3)),
+                form_field(
+                    "Quiet period (s)",
+                    "3",
+                    &self.snapshot_quiet,
+                    Message::SnapshotQuietChanged
+                )
+                .width(Length::FillPortion(1)),
+            ]
+            .spacing(spacing::MD),
+            form_field(
+                "File",
+                "Default: data directory/snapshots",
+                &self.snapshot_file,
+                Message::SnapshotFileChanged
+            ),
+            row![
+                horizontal_space(),
+                button(text("Take snapshot").size(typography::SIZE_MD))
+                    .padding([spacing::SM, spacing::LG])
+                    .style(styles::button_primary)
+                    .on_press_maybe(
+                        (self.snapshot_source.is_some() && !snapshot_running)
+                            .then_some(Message::TakeSnapshot)
+                    ),
+            ],
+        ]
+        .push_maybe(self.snapshot_status.as_ref().map(transfer_status))
+        .spacing(spacing::MD);
+
+        let restore = column![
+            text("Restore snapshot")
+                .size(typography::SIZE_MD)
+                .color(colors::TEXT_PRIMARY),
+            text("Publishes every message in the snapshot as retained")
+                .size(typography::SIZE_XS)
+                .color(colors::TEXT_MUTED),
+            form_field(
+                "File",
+                "/path/to/snapshot.json",
+                &self.restore_file,
+                Message::RestoreFileChanged
+            ),
+            column![
+                text("Target connection")
+                    .size(typography::SIZE_SM)
+                    .color(colors::TEXT_SECONDARY),
+                pick_list(
+                    choices.clone(),
+                    selected(&self.restore_target),
+                    Message::RestoreTargetChanged
+                )
+                .placeholder("Choose a connection")
+                .padding(spacing::SM)
+                .width(Length::Fill),
+            ]
+            .spacing(spacing::XS),
+        ]
+        .push_maybe(self.restore_preview.as_ref().map(restore_preview))
+        .push(
+            row![
+                horizontal_space(),
+                button(text("Dry run").size(typography::SIZE_MD))
+                    .padding([spacing::SM, spacing::LG])
+                    .style(styles::button_secondary)
+                    .on_press_maybe(
+                        (!self.restore_file.trim().is_empty() && !restore_running)
+                            .then_some(Message::PreviewRestore)
+                    ),
+                button(text("Restore").size(typography::SIZE_MD))
+                    .padding([spacing::SM, spacing::LG])
+                    .style(styles::button_danger)
+                    .on_press_maybe(
+                        (self.restore_preview.is_some()
+                            && self.restore_target.is_some()
+                            && !restore_running)
+                            .then_some(Message::RestoreSnapshot)
+                    ),
+            ]
+            .spacing(spacing::MD),
+        )
+        .push_maybe(self.restore_status.as_ref().map(transfer_status))
+        .spacing(spacing::MD);
+
+        let page = column![
+            text("Retained snapshots")
+                .size(typography::SIZE_2XL)
+                .color(colors::CYAN),
+            horizontal_rule(1),
+            snapshot,
+            horizontal_rule(1),
+            restore,
+        ]
+        .spacing(spacing::MD)
+        .padding(spacing::LG)
+        .max_width(600);
+
+        scrollable(
+            container(container(page).style(styles::container_panel))
+                .width(Length::Fill)
+                .center_x(Length::Fill)
+                .padding(spacing::LG),
+        )
+        .height(Length::Fill)
+        .into()
+    }
+}
+
+pub(super) fn transfer_status(status: &TransferStatus) -> Element<'_, Message> {
+    let (label, color) = match status {
+        TransferStatus::Running(detail) => (detail, colors::AMBER),
+        TransferStatus::Done(detail) => (detail, colors::GREEN),
+        TransferStatus::Failed(error) => (error, colors::RED),
+    };
+    text(label.as_str())
+        .size(typography::SIZE_SM)
+        .color(color)
+        .into()
+}
+
+/// What a restore would publish, without connecting
+fn restore_preview(snapshot: &RetainedSnapshot) -> Element<'_, Message> {
+    let mut topics = Column::new().spacing(spacing::XS);
+    for msg in snapshot.messages.iter().take(MAX_PREVIEW_TOPICS) {
+        topics = topics.push(
+            row![
+                text(msg.topic.as_str())
+                    .size(typography::SIZE_XS)
+                    .color(colors::TEXT_PRIMARY),
+                horizontal_space(),
+                text(format!(
+                    "QoS {}, {}",
+                    msg.qos,
+                    format_bytes(msg.payload.len() as f64)
+                ))
+                .size(typography::SIZE_XS)
+                .color(colors::TEXT_MUTED),
+            ]
+            .spacing(spacing::SM),
+        );
+    }
+    if snapshot.messages.len() > MAX_PREVIEW_TOPICS {
+        topics = topics.push(
+            text(format!(
+                "and {} more",
+                snapshot.messages.len() - MAX_PREVIEW_TOPICS
+            ))
+            .size(typography::SIZE_XS)
+            .color(colors::TEXT_MUTED),
+        );
+    }
+
+    container(
+        column![
+            text(format!(
+                "Would publish {} retained messages ({}), taken from {} ({}) on {}",
+                snapshot.messages.len(),
+                format_bytes(snapshot.total_bytes() as f64),
+                snapshot.broker,
+                snapshot.filter,
+                snapshot.taken_at.format("%Y-%m-%d %H:%M:%S UTC")
+            ))
+            .size(typography::SIZE_SM)
+            .color(colors::TEXT_SECONDARY),
+            topics,
+        ]
+        .spacing(spacing::SM),
+    )
+    .padding(spacing::SM)
+    .style(styles::container_card)
+    .into()
+}
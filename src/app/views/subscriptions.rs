@@ -0,0 +1,124 @@
+//! Subscription manager view
+
+use iced::widget::{
+    button, column, horizontal_rule, horizontal_space, pick_list, row, text, text_input, Column,
+};
+use iced::{Element, Length};
+
+use crate::styles::{self, colors, icons, spacing, typography};
+
+use crate::app::types::{ActiveSubscription, ConnectionState, SubscriptionStatus};
+use crate::app::{Message, MqttUi};
+
+impl MqttUi {
+    pub fn view_subscriptions_panel<'a>(
+        &'a self,
+        conn: &'a ConnectionState,
+        is_connected: bool,
+    ) -> Element<'a, Message> {
+        let qos_options = vec![0u8, 1, 2];
+
+        let subscribe_btn = button(text(icons::PLUS).size(typography::SIZE_SM).center())
+            .padding([spacing::XS, spacing::SM])
+            .style(styles::button_secondary)
+            .on_press_maybe(is_connected.then_some(Message::Subscribe));
+
+        let mut content = column![
+            row![
+                text(icons::TOPIC)
+                    .size(typography::SIZE_MD)
+                    .color(colors::CYAN),
+                text(" Subscriptions")
+                    .size(typography::SIZE_LG)
+                    .color(colors::CYAN)
+            ]
+            .spacing(spacing::XS),
+            horizontal_rule(1),
+            row![
+                text_input("topic/#", &self.subscribe_topic)
+                    .padding(spacing::SM)
+                    .style(styles::text_input_default)
+                    .on_input(Message::SubscribeTopicChanged)
+                    .on_submit(Message::Subscribe)
+                    .width(Length::Fill),
+                pick_list(
+                    qos_options,
+                    Some(self.subscribe_qos),
+                    Message::SubscribeQosChanged
+                )
+                .padding(spacing::XS)
+                .width(60),
+                subscribe_btn,
+            ]
+            .spacing(spacing::SM)
+            .align_y(iced::Alignment::Center),
+        ]
+        .spacing(spacing::MD)
+        .padding(spacing::MD);
+
+        let saved: Vec<String> = self
+            .config
+            .get_connection(&conn.config.id)
+            .map(|c| c.session_subscriptions())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|s| s.filter())
+            .collect();
+
+        let mut list = Column::new().spacing(spacing::SM);
+        for sub in &conn.subscriptions {
+            let is_saved = saved.contains(&sub.topic);
+            list = list.push(subscription_row(&conn.config.id, sub, is_saved));
+        }
+        content = content.push(list);
+
+        content.into()
+    }
+}
+
+fn subscription_row<'a>(
+    conn_id: &str,
+    sub: &'a ActiveSubscription,
+    is_saved: bool,
+) -> Element<'a, Message> {
+    let (status, status_color) = match &sub.status {
+        SubscriptionStatus::Pending => ("Pending".to_string(), colors::AMBER),
+        SubscriptionStatus::Granted(qos) if *qos < sub.qos => (
+            format!("QoS {} (requested {})", qos, sub.qos),
+            colors::AMBER,
+        ),
+        SubscriptionStatus::Granted(qos) => (format!("QoS {}", qos), colors::GREEN),
+        SubscriptionStatus::Failed(reason) => (reason.clone(), colors::RED),
+    };
+
+    let save_btn = (!is_saved).then(|| {
+        button(text(icons::SAVE).size(typography::SIZE_SM).center())
+            .padding([spacing::XS, spacing::SM])
+            .style(styles::button_text)
+            .on_press(Message::SaveSubscription(
+                conn_id.to_string(),
+                sub.topic.clone(),
+            ))
+    });
+
+    row![
+        column![
+            text(&sub.topic)
+                .size(typography::SIZE_SM)
+                .color(colors::TEXT_PRIMARY),
+            text(status).size(typography::SIZE_XS).color(status_color),
+        ]
+        .spacing(2),
+        horizontal_space(),
+    ]
+    .push_maybe(save_btn)
+    .push(
+        button(text(icons::TIMES).size(typography::SIZE_SM).center())
+            .padding([spacing::XS, spacing::SM])
+            .style(styles::button_text)
+            .on_press(Message::Unsubscribe(conn_id.to_string(), sub.topic.clone())),
+    )
+    .spacing(spacing::SM)
+    .align_y(iced::Alignment::Center)
+    .into()
+}
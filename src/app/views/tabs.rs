@@ -37,13 +37,17 @@ impl MqttUi {
 
                 let status_dot = text(match status {
                     ConnectionStatus::Connected => icons::CIRCLE_FILLED,
-                    ConnectionStatus::Connecting => icons::CIRCLE_HALF,
+                    ConnectionStatus::Connecting | ConnectionStatus::Reconnecting { .. } => {
+                        icons::CIRCLE_HALF
+                    }
                     _ => icons::CIRCLE_EMPTY,
                 })
                 .size(typography::SIZE_XS)
                 .color(match status {
                     ConnectionStatus::Connected => colors::GREEN,
-                    ConnectionStatus::Connecting => colors::AMBER,
+                    ConnectionStatus::Connecting | ConnectionStatus::Reconnecting { .. } => {
+                        colors::AMBER
+                    }
                     ConnectionStatus::Error(_) => colors::RED,
                     _ => colors::TEXT_MUTED,
                 });
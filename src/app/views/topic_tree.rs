@@ -1,41 +1,72 @@
 //! Topic tree panel view
 
-use iced::widget::{button, horizontal_rule, horizontal_space, row, scrollable, text, Column};
+use chrono::Utc;
+use iced::widget::{
+    button, container, horizontal_rule, horizontal_space, pick_list, row, scrollable, text, Column,
+};
 use iced::{Element, Length};
 
+use crate::mqtt::stats::{format_bytes, format_rate};
 use crate::mqtt::TopicNode;
 use crate::styles::{self, colors, icons, spacing, typography};
 
-use crate::app::types::TreeNodeInfo;
+use super::connection_form::form_field;
+use super::snapshots::transfer_status;
+use crate::app::types::{
+    ConnectionChoice, RetainedClear, RetainedCopy, TransferStatus, TreeNodeInfo,
+};
 use crate::app::{Message, MqttUi};
 
 /// Static function to collect tree nodes - called from mod.rs for caching
 pub fn collect_tree_nodes_static(node: &TopicNode, depth: usize) -> Vec<TreeNodeInfo> {
     let mut result = Vec::new();
+    collect_nodes(node, depth, Utc::now().timestamp(), true, &mut result);
+    result
+}
 
+/// Add a row for each child of `node` while `visible`, and return the number
+/// of retained topics below `node`. Collapsed subtrees are still walked for
+/// their counts, so each node is visited once per rebuild.
+fn collect_nodes(
+    node: &TopicNode,
+    depth: usize,
+    now: i64,
+    visible: bool,
+    result: &mut Vec<TreeNodeInfo>,
+) -> usize {
     // Sort children alphabetically
     let mut children: Vec<_> = node.children.iter().collect();
-    children.sort_by(|a, b| a.0.cmp(b.0));
+    if visible {
+        children.sort_by(|a, b| a.0.cmp(b.0));
+    }
 
+    let mut retained = 0;
     for (name, child) in children {
-        let info = TreeNodeInfo {
-            name: name.clone(),
-            full_path: child.full_path.clone(),
-            depth,
-            has_children: !child.children.is_empty(),
-            has_messages: !child.messages.is_empty(),
-            message_count: child.message_count,
-            is_expanded: child.expanded,
-        };
-        result.push(info);
+        let row = visible.then(|| {
+            result.push(TreeNodeInfo {
+                name: name.clone(),
+                full_path: child.full_path.clone(),
+                depth,
+                has_children: !child.children.is_empty(),
+                has_messages: !child.messages.is_empty(),
+                message_count: child.message_count,
+                total_bytes: child.stats.total_bytes,
+                rates: child.stats.rates(now),
+                retained_count: 0,
+                is_expanded: child.expanded,
+            });
+            result.len() - 1
+        });
 
-        // Recursively add children if expanded
-        if child.expanded && !child.children.is_empty() {
-            result.extend(collect_tree_nodes_static(child, depth + 1));
+        // Children of expanded nodes follow their parent's row
+        let below = collect_nodes(child, depth + 1, now, visible && child.expanded, result);
+        let count = usize::from(child.has_retained()) + below;
+        if let Some(row) = row {
+            result[row].retained_count = count;
         }
+        retained += count;
     }
-
-    result
+    retained
 }
 
 impl MqttUi {
@@ -59,6 +90,16 @@ impl MqttUi {
             .align_y(iced::Alignment::Center),
         );
         content = content.push(horizontal_rule(1));
+        if let Some(clear) = self.retained_clears.get(id) {
+            let connected = self
+                .connections
+                .get(id)
+                .is_some_and(|c| c.status.is_connected());
+            content = content.push(retained_clear_prompt(id, clear, connected));
+        }
+        if let Some(copy) = self.retained_copies.get(id) {
+            content = content.push(self.retained_copy_prompt(id, copy));
+        }
 
         // Use cached nodes if available, otherwise compute
         let has_tree = self.topic_trees.get(id).map(|t| !t.root.children.is_empty()).unwrap_or(false);
@@ -126,11 +167,31 @@ impl MqttUi {
         };
 
         let msg_count = if node.message_count > 0 {
-            format!(" ({})", node.message_count)
+            format!(
+                " ({}, {})",
+                node.message_count,
+                format_bytes(node.total_bytes as f64)
+            )
         } else {
             String::new()
         };
 
+        // Message rates and bandwidth over 1s, 10s and 60s
+        let [short, mid, long] = node.rates;
+        let rate = (long.messages > 0.0).then(|| {
+            text(format!(
+                "{} / {} / {} msg/s, {} / {} / {}/s",
+                format_rate(short.messages),
+                format_rate(mid.messages),
+                format_rate(long.messages),
+                format_bytes(short.bytes),
+                format_bytes(mid.bytes),
+                format_bytes(long.bytes)
+            ))
+            .size(typography::SIZE_XS)
+            .color(colors::CYAN)
+        });
+
         // Truncate long names with ellipsis
         let max_chars = 30;
         let name = if node.name.chars().count() > max_chars {
@@ -151,6 +212,7 @@ impl MqttUi {
                 .size(typography::SIZE_XS)
                 .color(colors::TEXT_MUTED),
         ]
+        .push_maybe(rate)
         .spacing(spacing::XS)
         .align_y(iced::Alignment::Center);
 
@@ -196,8 +258,228 @@ impl MqttUi {
                 .style(styles::button_tab(false))
         };
 
+        let clear_btn = (node.retained_count > 0).then(|| {
+            button(text(icons::ERASER).size(typography::SIZE_XS))
+                .padding(spacing::XS)
+                .style(styles::button_text)
+                .on_press(Message::ClearRetained(
+                    conn_id.to_string(),
+                    node.full_path.clone(),
+                ))
+        });
+
+        let copy_btn = (node.retained_count > 0).then(|| {
+            button(text(icons::COPY).size(typography::SIZE_XS))
+                .padding(spacing::XS)
+                .style(styles::button_text)
+                .on_press(Message::CopyRetained(
+                    conn_id.to_string(),
+                    node.full_path.clone(),
+                ))
+        });
+
         row![horizontal_space().width(indent as u16), node_btn,]
+            .push_maybe(copy_btn)
+            .push_maybe(clear_btn)
+            .align_y(iced::Alignment::Center)
             .width(Length::Fill)
             .into()
     }
+
+    /// Target and prefix for copying a subtree, with a preview of the topics
+    /// that will be written
+    fn retained_copy_prompt<'a>(
+        &'a self,
+        conn_id: &str,
+        copy: &'a RetainedCopy,
+    ) -> Element<'a, Message> {
+        const MAX_LISTED: usize = 20;
+
+        let choices: Vec<ConnectionChoice> = self
+            .config
+            .connections
+            .iter()
+            .map(|c| ConnectionChoice {
+                id: c.id.clone(),
+                name: c.name.clone(),
+            })
+            .collect();
+        let selected = choices
+            .iter()
+            .find(|c| Some(&c.id) == copy.target.as_ref())
+            .cloned();
+        let running = matches!(copy.status, Some(TransferStatus::Running(_)));
+        let (target_id, prefix_id) = (conn_id.to_string(), conn_id.to_string());
+
+        let mut content = Column::new().spacing(spacing::XS);
+        if copy.messages.is_empty() {
+            content = content.push(
+                text(format!("No retained messages seen under {}", copy.path))
+                    .size(typography::SIZE_SM)
+                    .color(colors::TEXT_SECONDARY),
+            );
+        } else {
+            content = content
+                .push(
+                    text(format!(
+                        "Copy {} retained topic{} under {} to",
+                        copy.messages.len(),
+                        if copy.messages.len() == 1 { "" } else { "s" },
+                        copy.path
+                    ))
+                    .size(typography::SIZE_SM)
+                    .color(colors::TEXT_PRIMARY),
+                )
+                .push(
+                    pick_list(choices, selected, move |choice| {
+                        Message::CopyTargetChanged(target_id.clone(), choice)
+                    })
+                    .placeholder("Choose a connection")
+                    .padding(spacing::XS)
+                    .width(Length::Fill),
+                )
+                .push(form_field(
+                    "Rewrite prefix",
+                    "Empty: drop the prefix",
+                    &copy.prefix,
+                    move |prefix| Message::CopyPrefixChanged(prefix_id.clone(), prefix),
+                ));
+            if let Some(error) = copy.prefix_error() {
+                content = content.push(text(error).size(typography::SIZE_XS).color(colors::RED));
+            }
+            for msg in copy.messages.iter().take(MAX_LISTED) {
+                let destination = copy
+                    .destination(&msg.topic)
+                    .unwrap_or_else(|| "(no topic without a prefix)".to_string());
+                content = content.push(
+                    text(format!("{} \u{2192} {}", msg.topic, destination))
+                        .size(typography::SIZE_XS)
+                        .color(colors::TEXT_SECONDARY),
+                );
+            }
+            if copy.messages.len() > MAX_LISTED {
+                content = content.push(
+                    text(format!("and {} more", copy.messages.len() - MAX_LISTED))
+                        .size(typography::SIZE_XS)
+                        .color(colors::TEXT_MUTED),
+                );
+            }
+        }
+
+        let confirm = (!copy.messages.is_empty()).then(|| {
+            button(text("Copy").size(typography::SIZE_SM))
+                .padding([spacing::XS, spacing::SM])
+                .style(styles::button_primary)
+                .on_press_maybe(
+                    (copy.target.is_some() && copy.prefix_error().is_none() && !running)
+                        .then(|| Message::ConfirmCopyRetained(conn_id.to_string())),
+                )
+        });
+        content = content
+            .push(
+                row![]
+                    .push_maybe(confirm)
+                    .push(
+                        button(text("Close").size(typography::SIZE_SM))
+                            .padding([spacing::XS, spacing::SM])
+                            .style(styles::button_secondary)
+                            .on_press(Message::CancelCopyRetained(conn_id.to_string())),
+                    )
+                    .spacing(spacing::SM),
+            )
+            .push_maybe(copy.status.as_ref().map(transfer_status));
+
+        container(content)
+            .padding(spacing::SM)
+            .style(styles::container_card)
+            .into()
+    }
+}
+
+/// Topics about to lose their retained message, with confirm and cancel
+fn retained_clear_prompt<'a>(
+    conn_id: &str,
+    clear: &RetainedClear,
+    connected: bool,
+) -> Element<'a, Message> {
+    const MAX_LISTED: usize = 20;
+
+    let mut content = Column::new().spacing(spacing::XS);
+    if clear.topics.is_empty() {
+        content = content.push(
+            text(format!("No retained messages seen under {}", clear.path))
+                .size(typography::SIZE_SM)
+                .color(colors::TEXT_SECONDARY),
+        );
+    } else {
+        content = content.push(
+            text(format!(
+                "Clear {} retained topic{} under {}?",
+                clear.topics.len(),
+                if clear.topics.len() == 1 { "" } else { "s" },
+                clear.path
+            ))
+            .size(typography::SIZE_SM)
+            .color(colors::AMBER),
+        );
+        for topic in clear.topics.iter().take(MAX_LISTED) {
+            content = content.push(
+                text(topic.clone())
+                    .size(typography::SIZE_XS)
+                    .color(colors::TEXT_SECONDARY),
+            );
+        }
+        if clear.topics.len() > MAX_LISTED {
+            content = content.push(
+                text(format!("and {} more", clear.topics.len() - MAX_LISTED))
+                    .size(typography::SIZE_XS)
+                    .color(colors::TEXT_MUTED),
+            );
+        }
+    }
+
+    if !clear.pending.is_empty() {
+        content = content.push(
+            text(format!("Clearing, {} left", clear.pending.len()))
+                .size(typography::SIZE_XS)
+                .color(colors::TEXT_MUTED),
+        );
+    } else if !connected && !clear.topics.is_empty() {
+        content = content.push(
+            text("Connect to clear retained messages")
+                .size(typography::SIZE_XS)
+                .color(colors::TEXT_MUTED),
+        );
+    }
+    if let Some(error) = &clear.error {
+        content = content.push(
+            text(error.clone())
+                .size(typography::SIZE_XS)
+                .color(colors::RED),
+        );
+    }
+
+    let can_clear = connected && clear.pending.is_empty();
+    let confirm = (!clear.topics.is_empty()).then(|| {
+        button(text("Clear retained").size(typography::SIZE_SM))
+            .padding([spacing::XS, spacing::SM])
+            .style(styles::button_danger)
+            .on_press_maybe(can_clear.then(|| Message::ConfirmClearRetained(conn_id.to_string())))
+    });
+    content = content.push(
+        row![]
+            .push_maybe(confirm)
+            .push(
+                button(text("Cancel").size(typography::SIZE_SM))
+                    .padding([spacing::XS, spacing::SM])
+                    .style(styles::button_secondary)
+                    .on_press(Message::CancelClearRetained(conn_id.to_string())),
+            )
+            .spacing(spacing::SM),
+    );
+
+    container(content)
+        .padding(spacing::SM)
+        .style(styles::container_card)
+        .into()
 }
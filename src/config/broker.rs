@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+/// Settings for the broker mqttui can run itself
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EmbeddedBrokerSettings {
+    pub port: u16,
+    /// Accept connections from other machines, not just this one
+    #[serde(default)]
+    pub listen_all: bool,
+    /// Require this username and password when set
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Saved connection registered for the broker
+    #[serde(default)]
+    pub connection_id: Option<String>,
+}
+
+impl Default for EmbeddedBrokerSettings {
+    fn default() -> Self {
+        Self {
+            port: 1883,
+            listen_all: false,
+            username: None,
+            password: None,
+            connection_id: None,
+        }
+    }
+}
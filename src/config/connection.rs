@@ -1,4 +1,8 @@
+use std::fmt;
+use std::time::Duration;
+
 use chrono::{DateTime, Utc};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -27,7 +31,14 @@ impl MqttProtocol {
         }
     }
 
-    #[allow(dead_code)]
+    pub fn is_tls(&self) -> bool {
+        matches!(self, MqttProtocol::Mqtts | MqttProtocol::MqttsWs)
+    }
+
+    pub fn is_websocket(&self) -> bool {
+        matches!(self, MqttProtocol::MqttWs | MqttProtocol::MqttsWs)
+    }
+
     pub fn default_port(&self) -> u16 {
         match self {
             MqttProtocol::Mqtt => 1883,
@@ -48,14 +59,19 @@ impl MqttProtocol {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
 pub enum MqttVersion {
     #[default]
     V311,
     V5,
 }
 
-#[allow(dead_code)]
+impl std::fmt::Display for MqttVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MQTT {}", self.as_str())
+    }
+}
+
 impl MqttVersion {
     pub fn as_str(&self) -> &'static str {
         match self {
@@ -69,17 +85,536 @@ impl MqttVersion {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// CONNECT properties sent by MQTT 5 connections. `None` leaves the
+/// property out so the broker default applies.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default)]
+pub struct V5Settings {
+    /// Seconds the broker keeps the session after disconnect
+    pub session_expiry_interval: Option<u32>,
+    /// Maximum unacknowledged QoS 1/2 publishes the broker may send us
+    pub receive_maximum: Option<u16>,
+    /// Highest topic alias the broker may use towards us
+    pub topic_alias_max: Option<u16>,
+}
+
+/// Session and flow-control options for both protocol versions
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SessionSettings {
+    /// Discard any previous session on connect (clean start in MQTT 5)
+    pub clean_session: bool,
+    /// Seconds between keep-alive pings
+    pub keep_alive_secs: u16,
+    /// Largest packet accepted from the broker, in bytes
+    pub max_incoming_packet_size: u32,
+    /// Largest packet we send, in bytes. MQTT 5 brokers announce their own limit.
+    pub max_outgoing_packet_size: u32,
+    /// Outgoing QoS 1/2 publishes awaiting acknowledgement at once
+    pub max_inflight: u16,
+    /// Memory for received messages the UI has not caught up with, in MiB.
+    /// Messages arriving while it is full are dropped and counted.
+    pub message_buffer_mb: u32,
+}
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        Self {
+            clean_session: true,
+            keep_alive_secs: 30,
+            max_incoming_packet_size: 256 * 1024,
+            max_outgoing_packet_size: 256 * 1024,
+            max_inflight: 100,
+            message_buffer_mb: 64,
+        }
+    }
+}
+
+/// Last Will and Testament the broker publishes if we drop without DISCONNECT
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default)]
+pub struct LastWill {
+    pub topic: String,
+    pub payload: String,
+    pub qos: u8,
+    pub retain: bool,
+    /// Seconds the broker waits before publishing the will (v5 only)
+    pub delay_interval: Option<u32>,
+    /// Seconds the will message stays valid once published (v5 only)
+    pub message_expiry_interval: Option<u32>,
+    pub content_type: Option<String>,
+    pub response_topic: Option<String>,
+    pub user_properties: Vec<(String, String)>,
+}
+
+/// Automatic reconnect after an established connection drops
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ReconnectSettings {
+    pub enabled: bool,
+    /// Delay before the first retry, in milliseconds
+    pub initial_backoff_ms: u64,
+    /// Upper bound for the doubling delay, in milliseconds
+    pub max_backoff_ms: u64,
+    /// Random spread applied to each delay, in percent
+    pub jitter_percent: u8,
+    /// Give up after this many failed attempts in a row; `None` retries forever
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            initial_backoff_ms: 1000,
+            max_backoff_ms: 30_000,
+            jitter_percent: 20,
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectSettings {
+    /// Delay before retry number `attempt` (starting at 1), jitter included
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u64
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u64::MAX);
+        let backoff = self
+            .initial_backoff_ms
+            .saturating_mul(factor)
+            .min(self.max_backoff_ms);
+        let spread = backoff * u64::from(self.jitter_percent.min(100)) / 100;
+        let jittered = backoff - spread + rand::thread_rng().gen_range(0..=spread * 2);
+        Duration::from_millis(jittered)
+    }
+}
+
+/// One node of a broker cluster
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Endpoint {
+    pub host: String,
+    pub port: u16,
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// Other nodes to fail over to when the main host is unreachable
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default)]
+pub struct FailoverSettings {
+    /// Tried in order after the main host and port
+    pub endpoints: Vec<Endpoint>,
+    /// On reconnect, move on to the node after the one that dropped instead
+    /// of starting over from the main host
+    pub round_robin: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum ProxyKind {
+    #[default]
+    None,
+    Socks5,
+    /// HTTP CONNECT
+    Http,
+}
+
+impl fmt::Display for ProxyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ProxyKind::None => "No proxy",
+            ProxyKind::Socks5 => "SOCKS5",
+            ProxyKind::Http => "HTTP CONNECT",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl ProxyKind {
+    pub fn default_port(&self) -> u16 {
+        match self {
+            ProxyKind::None | ProxyKind::Socks5 => 1080,
+            ProxyKind::Http => 8080,
+        }
+    }
+
+    pub fn all() -> &'static [ProxyKind] {
+        &[ProxyKind::None, ProxyKind::Socks5, ProxyKind::Http]
+    }
+}
+
+/// Proxy the broker connection is tunnelled through, for every transport
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default)]
+pub struct ProxySettings {
+    pub kind: ProxyKind,
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl ProxySettings {
+    pub fn is_enabled(&self) -> bool {
+        self.kind != ProxyKind::None && !self.host.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum SshAuth {
+    #[default]
+    Agent,
+    KeyFile,
+}
+
+impl fmt::Display for SshAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SshAuth::Agent => "SSH agent",
+            SshAuth::KeyFile => "Key file",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl SshAuth {
+    pub fn all() -> &'static [SshAuth] {
+        &[SshAuth::Agent, SshAuth::KeyFile]
+    }
+}
+
+/// Jump host the broker connection is forwarded through, like `ssh -L`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SshTunnelSettings {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub auth: SshAuth,
+    /// Private key for `SshAuth::KeyFile`
+    pub key_file: Option<String>,
+    pub key_passphrase: Option<String>,
+    /// Broker host as seen from the jump host; `None` uses the broker host
+    pub remote_host: Option<String>,
+    /// Broker port as seen from the jump host; `None` uses the broker port
+    pub remote_port: Option<u16>,
+    /// Jump host key accepted in mqttui, in OpenSSH format
+    pub host_key: Option<String>,
+}
+
+impl Default for SshTunnelSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: String::new(),
+            port: 22,
+            user: String::new(),
+            auth: SshAuth::default(),
+            key_file: None,
+            key_passphrase: None,
+            remote_host: None,
+            remote_port: None,
+            host_key: None,
+        }
+    }
+}
+
+impl SshTunnelSettings {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled && !self.host.is_empty()
+    }
+
+    /// Where the jump host forwards a connection meant for `endpoint`
+    pub fn forward_target(&self, endpoint: &Endpoint) -> Endpoint {
+        Endpoint {
+            host: self
+                .remote_host
+                .clone()
+                .unwrap_or_else(|| endpoint.host.clone()),
+            port: self.remote_port.unwrap_or(endpoint.port),
+        }
+    }
+
+    pub fn jump_host(&self) -> Endpoint {
+        Endpoint {
+            host: self.host.clone(),
+            port: self.port,
+        }
+    }
+}
+
+/// Where the password sent in CONNECT comes from
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum CredentialKind {
+    /// `ConnectionConfig.password` as entered
+    #[default]
+    Static,
+    /// A JWT signed with a device key
+    Jwt,
+    /// An Azure IoT Hub shared access signature
+    Sas,
+}
+
+impl fmt::Display for CredentialKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CredentialKind::Static => "Static",
+            CredentialKind::Jwt => "JWT",
+            CredentialKind::Sas => "SAS token",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl CredentialKind {
+    pub fn all() -> &'static [CredentialKind] {
+        &[
+            CredentialKind::Static,
+            CredentialKind::Jwt,
+            CredentialKind::Sas,
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum JwtAlgorithm {
+    #[default]
+    Rs256,
+    Es256,
+}
+
+impl fmt::Display for JwtAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            JwtAlgorithm::Rs256 => "RS256",
+            JwtAlgorithm::Es256 => "ES256",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl JwtAlgorithm {
+    pub fn all() -> &'static [JwtAlgorithm] {
+        &[JwtAlgorithm::Rs256, JwtAlgorithm::Es256]
+    }
+}
+
+/// Claims template used for new JWT credentials
+pub const DEFAULT_JWT_CLAIMS: &str = r#"{"iat": {{iat}}, "exp": {{exp}}, "aud": ""}"#;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct JwtSettings {
+    pub algorithm: JwtAlgorithm,
+    /// PEM private key; PKCS#1 or PKCS#8 for RS256, PKCS#8 for ES256
+    pub key_file: String,
+    /// JSON claims with `{{iat}}` and `{{exp}}` filled in for each token, and
+    /// `{{client_id}}` and `{{host}}` within string values
+    pub claims: String,
+    /// Seconds from issue to expiry
+    pub lifetime_secs: u64,
+}
+
+impl Default for JwtSettings {
+    fn default() -> Self {
+        Self {
+            algorithm: JwtAlgorithm::default(),
+            key_file: String::new(),
+            claims: DEFAULT_JWT_CLAIMS.to_string(),
+            lifetime_secs: 3600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SasSettings {
+    /// Base64 shared access key of the device or policy
+    pub key: String,
+    /// Policy name, for keys of a hub-level shared access policy
+    pub key_name: Option<String>,
+    /// Resource the token grants access to; `None` uses
+    /// `<host>/devices/<client id>`
+    pub resource_uri: Option<String>,
+    /// Seconds from issue to expiry
+    pub lifetime_secs: u64,
+}
+
+impl Default for SasSettings {
+    fn default() -> Self {
+        Self {
+            key: String::new(),
+            key_name: None,
+            resource_uri: None,
+            lifetime_secs: 3600,
+        }
+    }
+}
+
+/// Password provider, regenerated for every connection attempt
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default)]
+pub struct CredentialSettings {
+    pub kind: CredentialKind,
+    pub jwt: JwtSettings,
+    pub sas: SasSettings,
+}
+
+/// Broker statistics, which `#` does not match
+pub const SYS_TOPIC: &str = "$SYS/#";
+
+/// When the broker sends retained messages for a new subscription (MQTT 5)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum RetainHandling {
+    #[default]
+    SendOnSubscribe,
+    SendIfNew,
+    DoNotSend,
+}
+
+impl fmt::Display for RetainHandling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RetainHandling::SendOnSubscribe => "Send retained",
+            RetainHandling::SendIfNew => "Send if new",
+            RetainHandling::DoNotSend => "Don't send retained",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl RetainHandling {
+    pub fn all() -> &'static [RetainHandling] {
+        &[
+            RetainHandling::SendOnSubscribe,
+            RetainHandling::SendIfNew,
+            RetainHandling::DoNotSend,
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Subscription {
     pub topic: String,
     pub qos: u8,
+    /// Share the filter with other clients in this group, as `$share/<group>/<topic>`
+    #[serde(default)]
+    pub share_group: Option<String>,
+    /// Don't receive our own publishes (MQTT 5)
+    #[serde(default)]
+    pub no_local: bool,
+    /// Keep the retain flag as the publisher set it (MQTT 5)
+    #[serde(default)]
+    pub retain_as_published: bool,
+    #[serde(default)]
+    pub retain_handling: RetainHandling,
+    /// Subscription identifier echoed back on matching messages (MQTT 5)
+    #[serde(default)]
+    pub identifier: Option<u32>,
 }
 
 impl Default for Subscription {
+    fn default() -> Self {
+        Self::new("#", 0)
+    }
+}
+
+impl Subscription {
+    /// Plain subscription without MQTT 5 options
+    pub fn new(topic: impl Into<String>, qos: u8) -> Self {
+        Self {
+            topic: topic.into(),
+            qos,
+            share_group: None,
+            no_local: false,
+            retain_as_published: false,
+            retain_handling: RetainHandling::default(),
+            identifier: None,
+        }
+    }
+
+    /// Filter sent in the SUBSCRIBE, with the `$share` prefix if shared
+    pub fn filter(&self) -> String {
+        match self.share_group.as_deref().filter(|g| !g.is_empty()) {
+            Some(group) => format!("$share/{}/{}", group, self.topic),
+            None => self.topic.clone(),
+        }
+    }
+}
+
+/// TLS settings used by the `mqtts` and `wss` protocols
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct TlsSettings {
+    /// Trust the operating system's root certificates
+    pub use_system_roots: bool,
+    /// PEM bundle with additional CA certificates
+    pub ca_file: Option<String>,
+    /// PEM client certificate for mutual TLS
+    pub client_cert_file: Option<String>,
+    /// PEM private key matching `client_cert_file`
+    pub client_key_file: Option<String>,
+    /// Name sent as SNI and verified against the broker certificate in
+    /// place of the host
+    pub server_name: Option<String>,
+    /// Accept any broker certificate without verification
+    pub insecure: bool,
+}
+
+impl Default for TlsSettings {
     fn default() -> Self {
         Self {
-            topic: "#".to_string(),
-            qos: 0,
+            use_system_roots: true,
+            ca_file: None,
+            client_cert_file: None,
+            client_key_file: None,
+            server_name: None,
+            insecure: false,
+        }
+    }
+}
+
+/// Extra HTTP header sent with the WebSocket upgrade request
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct HttpHeader {
+    pub name: String,
+    pub value: String,
+}
+
+/// WebSocket settings used by the `ws` and `wss` protocols
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct WebSocketSettings {
+    /// URL path of the MQTT endpoint, e.g. `/mqtt`
+    pub path: String,
+    pub headers: Vec<HttpHeader>,
+}
+
+impl Default for WebSocketSettings {
+    fn default() -> Self {
+        Self {
+            path: "/mqtt".to_string(),
+            headers: Vec::new(),
+        }
+    }
+}
+
+impl WebSocketSettings {
+    /// Path with a guaranteed leading slash
+    pub fn normalized_path(&self) -> String {
+        if self.path.starts_with('/') {
+            self.path.clone()
+        } else {
+            format!("/{}", self.path)
         }
     }
 }
@@ -97,6 +632,29 @@ pub struct ConnectionConfig {
     pub client_id: Option<String>,
     pub use_custom_client_id: bool,
     pub subscriptions: Vec<Subscription>,
+    #[serde(default)]
+    pub tls: TlsSettings,
+    #[serde(default)]
+    pub websocket: WebSocketSettings,
+    #[serde(default)]
+    pub v5: V5Settings,
+    #[serde(default)]
+    pub session: SessionSettings,
+    #[serde(default)]
+    pub last_will: Option<LastWill>,
+    #[serde(default)]
+    pub reconnect: ReconnectSettings,
+    #[serde(default)]
+    pub failover: FailoverSettings,
+    #[serde(default)]
+    pub proxy: ProxySettings,
+    #[serde(default)]
+    pub ssh: SshTunnelSettings,
+    #[serde(default)]
+    pub credentials: CredentialSettings,
+    /// Also subscribe to `$SYS/#` to fill the broker dashboard
+    #[serde(default)]
+    pub subscribe_sys: bool,
     pub created_at: DateTime<Utc>,
     pub last_connected: Option<DateTime<Utc>>,
 }
@@ -115,6 +673,17 @@ impl Default for ConnectionConfig {
             client_id: None,
             use_custom_client_id: false,
             subscriptions: vec![Subscription::default()],
+            tls: TlsSettings::default(),
+            websocket: WebSocketSettings::default(),
+            v5: V5Settings::default(),
+            session: SessionSettings::default(),
+            last_will: None,
+            reconnect: ReconnectSettings::default(),
+            failover: FailoverSettings::default(),
+            proxy: ProxySettings::default(),
+            ssh: SshTunnelSettings::default(),
+            credentials: CredentialSettings::default(),
+            subscribe_sys: false,
             created_at: Utc::now(),
             last_connected: None,
         }
@@ -122,7 +691,6 @@ impl Default for ConnectionConfig {
 }
 
 impl ConnectionConfig {
-    #[allow(dead_code)]
     pub fn new(name: &str, host: &str, port: u16) -> Self {
         Self {
             name: name.to_string(),
@@ -138,15 +706,69 @@ impl ConnectionConfig {
             (Some(u), None) => format!("{}@", u),
             _ => String::new(),
         };
+        let path = if self.protocol.is_websocket() {
+            self.websocket.normalized_path()
+        } else {
+            String::new()
+        };
         format!(
-            "{}://{}{}:{}",
+            "{}://{}{}:{}{}",
             self.protocol.as_str(),
             auth,
             self.host,
-            self.port
+            self.port,
+            path
         )
     }
 
+    /// WebSocket URL for `endpoint`, which may differ from the main host on fallback
+    pub fn websocket_url(&self, endpoint: &Endpoint) -> String {
+        format!(
+            "{}://{}{}",
+            self.protocol.as_str(),
+            endpoint,
+            self.websocket.normalized_path()
+        )
+    }
+
+    /// The main host followed by the failover nodes
+    pub fn endpoints(&self) -> Vec<Endpoint> {
+        let main = Endpoint {
+            host: self.host.clone(),
+            port: self.port,
+        };
+        std::iter::once(main)
+            .chain(self.failover.endpoints.iter().cloned())
+            .collect()
+    }
+
+    /// Filters subscribed on connect
+    pub fn initial_subscriptions(&self) -> Vec<Subscription> {
+        // Don't subscribe to # if we already have subscriptions - it's redundant and causes message floods
+        if self.subscriptions.is_empty() {
+            vec![Subscription::default()]
+        } else {
+            self.subscriptions.clone()
+        }
+    }
+
+    /// Filters subscribed on connect, including `$SYS/#` if enabled
+    pub fn session_subscriptions(&self) -> Vec<Subscription> {
+        let mut subscriptions = self.initial_subscriptions();
+        if self.subscribe_sys && !subscriptions.iter().any(|s| s.topic == SYS_TOPIC) {
+            subscriptions.push(Subscription::new(SYS_TOPIC, 0));
+        }
+        subscriptions
+    }
+
+    /// TLS server name overriding the host, if the protocol uses TLS
+    pub fn tls_server_name(&self) -> Option<&str> {
+        self.tls
+            .server_name
+            .as_deref()
+            .filter(|_| self.protocol.is_tls())
+    }
+
     pub fn effective_client_id(&self) -> String {
         if self.use_custom_client_id {
             self.client_id
@@ -156,4 +778,24 @@ impl ConnectionConfig {
             format!("mqttui-{}", &Uuid::new_v4().to_string()[..8])
         }
     }
+
+    /// Settings for a short extra session next to an open tab, such as a
+    /// connection test. It gets its own client id, so the broker doesn't
+    /// drop the tab's session, and leaves no session state or will behind.
+    ///
+    /// Generated credentials are issued for the device, and brokers that
+    /// use them (Azure IoT Hub, Google Cloud IoT) require the device id as
+    /// the client id, so those connections keep theirs. The broker then
+    /// drops an open tab for the duration, which it reconnects from.
+    pub fn side_session(&self, purpose: &str) -> ConnectionConfig {
+        let mut config = self.clone();
+        if self.credentials.kind == CredentialKind::Static {
+            config.client_id = Some(format!("{}-{}", self.effective_client_id(), purpose));
+            config.use_custom_client_id = true;
+        }
+        config.session.clean_session = true;
+        config.v5.session_expiry_interval = None;
+        config.last_will = None;
+        config
+    }
 }
@@ -1,5 +1,7 @@
+pub mod broker;
 pub mod connection;
 pub mod storage;
 
+pub use broker::*;
 pub use connection::*;
 pub use storage::*;
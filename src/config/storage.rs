@@ -5,6 +5,7 @@ use anyhow::{Context, Result};
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 
+use super::broker::EmbeddedBrokerSettings;
 use super::connection::ConnectionConfig;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -13,6 +14,8 @@ pub struct AppConfig {
     pub last_opened_tabs: Vec<String>, // Connection IDs
     pub window_width: Option<f32>,
     pub window_height: Option<f32>,
+    #[serde(default)]
+    pub embedded_broker: EmbeddedBrokerSettings,
 }
 
 impl AppConfig {
@@ -0,0 +1,341 @@
+//! Forwarding rules for bridging traffic from one connection to another
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+use super::message::MqttMessage;
+
+/// How long a forwarded message is remembered, to recognise it when the
+/// broker sends it back
+const ECHO_TTL: Duration = Duration::from_secs(10);
+
+/// QoS used when forwarding a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BridgeQos {
+    /// QoS the message was received with
+    #[default]
+    Keep,
+    Set(u8),
+}
+
+impl BridgeQos {
+    pub fn all() -> Vec<BridgeQos> {
+        vec![
+            BridgeQos::Keep,
+            BridgeQos::Set(0),
+            BridgeQos::Set(1),
+            BridgeQos::Set(2),
+        ]
+    }
+}
+
+impl fmt::Display for BridgeQos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BridgeQos::Keep => write!(f, "Same QoS"),
+            BridgeQos::Set(qos) => write!(f, "QoS {}", qos),
+        }
+    }
+}
+
+/// Retain flag used when forwarding a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BridgeRetain {
+    /// Retain flag the message was received with
+    #[default]
+    Keep,
+    Never,
+    Always,
+    /// Don't forward retained messages, only live traffic
+    Skip,
+}
+
+impl BridgeRetain {
+    pub fn all() -> Vec<BridgeRetain> {
+        vec![
+            BridgeRetain::Keep,
+            BridgeRetain::Never,
+            BridgeRetain::Always,
+            BridgeRetain::Skip,
+        ]
+    }
+}
+
+impl fmt::Display for BridgeRetain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BridgeRetain::Keep => write!(f, "Keep retain flag"),
+            BridgeRetain::Never => write!(f, "Never retain"),
+            BridgeRetain::Always => write!(f, "Always retain"),
+            BridgeRetain::Skip => write!(f, "Skip retained"),
+        }
+    }
+}
+
+/// Which messages a bridge forwards and how they are rewritten
+#[derive(Debug, Clone, PartialEq)]
+pub struct BridgeRule {
+    pub filter: String,
+    /// Leading topic levels replaced by `rewrite_to`; empty adds
+    /// `rewrite_to` in front
+    pub rewrite_from: String,
+    pub rewrite_to: String,
+    pub qos: BridgeQos,
+    pub retain: BridgeRetain,
+}
+
+/// A message as it is to be published on the target
+#[derive(Debug, Clone)]
+pub struct Forward {
+    pub topic: String,
+    pub qos: u8,
+    pub retain: bool,
+}
+
+impl BridgeRule {
+    /// Where and how `msg` is published on the target, or `None` if the
+    /// rule doesn't forward it
+    pub fn forward(&self, msg: &MqttMessage) -> Option<Forward> {
+        if !topic_matches(&self.filter, &msg.topic) {
+            return None;
+        }
+        let retain = match self.retain {
+            BridgeRetain::Keep => msg.retain,
+            BridgeRetain::Never => false,
+            BridgeRetain::Always => true,
+            BridgeRetain::Skip if msg.retain => return None,
+            BridgeRetain::Skip => false,
+        };
+        let qos = match self.qos {
+            BridgeQos::Keep => msg.qos,
+            BridgeQos::Set(qos) => qos,
+        };
+        let topic = self.rewrite(&msg.topic);
+        (!topic.is_empty()).then_some(Forward { topic, qos, retain })
+    }
+
+    /// Replace the leading `rewrite_from` levels of `topic` with
+    /// `rewrite_to`. Only whole levels match, so `sensors` rewrites
+    /// `sensors/a` but not `sensors2/a`.
+    pub fn rewrite(&self, topic: &str) -> String {
+        let from = self.rewrite_from.trim_end_matches('/');
+        let to = self.rewrite_to.trim_end_matches('/');
+        let rest = if from.is_empty() {
+            Some(topic)
+        } else if topic == from {
+            Some("")
+        } else {
+            topic
+                .strip_prefix(from)
+                .and_then(|rest| rest.strip_prefix('/'))
+        };
+        match rest {
+            Some(rest) if to.is_empty() => rest.to_string(),
+            Some("") => to.to_string(),
+            Some(rest) => format!("{}/{}", to, rest),
+            None => topic.to_string(),
+        }
+    }
+}
+
+/// Messages bridges published recently, per connection. A message coming
+/// back on a connection it was forwarded to is an echo of our own publish
+/// and isn't forwarded again, which would loop forever with bridges both
+/// ways between two connections.
+#[derive(Debug, Default)]
+pub struct Echoes {
+    expiry: HashMap<(String, String, u64), Instant>,
+}
+
+impl Echoes {
+    /// Remember a publish of `payload` on `topic` through `connection`
+    pub fn record(&mut self, connection: &str, topic: &str, payload: &[u8]) {
+        let now = Instant::now();
+        self.expiry.retain(|_, expires| *expires > now);
+        let key = (connection.to_string(), topic.to_string(), hash(payload));
+        self.expiry.insert(key, now + ECHO_TTL);
+    }
+
+    /// Whether `msg`, received on `connection`, is one bridges published
+    pub fn is_echo(&self, connection: &str, msg: &MqttMessage) -> bool {
+        let key = (
+            connection.to_string(),
+            msg.topic.clone(),
+            hash(&msg.payload),
+        );
+        self.expiry
+            .get(&key)
+            .is_some_and(|expires| *expires > Instant::now())
+    }
+}
+
+fn hash(payload: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    payload.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Whether `topic` matches the subscription `filter`, including `+` and `#`
+/// wildcards and the `$share/<group>/` prefix
+pub fn topic_matches(filter: &str, topic: &str) -> bool {
+    let filter = match filter.strip_prefix("$share/") {
+        Some(rest) => rest.split_once('/').map_or(rest, |(_, filter)| filter),
+        None => filter,
+    };
+    // Wildcards at the first level don't match `$SYS` and similar topics
+    if topic.starts_with('$') && (filter.starts_with('+') || filter.starts_with('#')) {
+        return false;
+    }
+
+    let mut levels = topic.split('/');
+    for part in filter.split('/') {
+        match (part, levels.next()) {
+            ("#", _) => return true,
+            ("+", Some(_)) => {}
+            (part, Some(level)) if part == level => {}
+            _ => return false,
+        }
+    }
+    levels.next().is_none()
+}
+
+/// Whether every topic matched by `inner` is also matched by `outer`, so a
+/// subscription to `outer` already receives everything `inner` would
+pub fn filter_covers(outer: &str, inner: &str) -> bool {
+    // Shared subscriptions only deliver a share of the matching messages
+    if outer.starts_with("$share/") || inner.starts_with("$share/") {
+        return false;
+    }
+    // Wildcards at the first level don't match `$SYS` and similar topics
+    if inner.starts_with('$') && (outer.starts_with('+') || outer.starts_with('#')) {
+        return false;
+    }
+
+    let mut levels = inner.split('/');
+    for part in outer.split('/') {
+        match (part, levels.next()) {
+            ("#", _) => return true,
+            ("+", Some(level)) if level != "#" => {}
+            (part, Some(level)) if part == level && part != "+" => {}
+            _ => return false,
+        }
+    }
+    levels.next().is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(rewrite_from: &str, rewrite_to: &str) -> BridgeRule {
+        BridgeRule {
+            filter: "#".to_string(),
+            rewrite_from: rewrite_from.to_string(),
+            rewrite_to: rewrite_to.to_string(),
+            qos: BridgeQos::Keep,
+            retain: BridgeRetain::Keep,
+        }
+    }
+
+    #[test]
+    fn rewrite_matches_whole_levels_only() {
+        let rule = rule("sensors", "field/sensors");
+        assert_eq!(rule.rewrite("sensors/a/b"), "field/sensors/a/b");
+        assert_eq!(rule.rewrite("sensors"), "field/sensors");
+        assert_eq!(rule.rewrite("sensors2/a"), "sensors2/a");
+        assert_eq!(rule.rewrite("other/sensors/a"), "other/sensors/a");
+    }
+
+    #[test]
+    fn rewrite_ignores_trailing_slashes() {
+        let rule = rule("sensors/", "field/");
+        assert_eq!(rule.rewrite("sensors/a"), "field/a");
+        assert_eq!(rule.rewrite("sensors"), "field");
+        assert_eq!(rule.rewrite("sensorsx"), "sensorsx");
+    }
+
+    #[test]
+    fn rewrite_with_empty_prefixes() {
+        assert_eq!(rule("", "site1").rewrite("a/b"), "site1/a/b");
+        assert_eq!(rule("site1", "").rewrite("site1/a/b"), "a/b");
+        assert_eq!(rule("", "").rewrite("a/b"), "a/b");
+    }
+
+    #[test]
+    fn forward_drops_topics_rewritten_to_nothing() {
+        let msg = MqttMessage::new("site1".to_string(), Vec::new(), 0, false);
+        assert!(rule("site1", "").forward(&msg).is_none());
+    }
+
+    #[test]
+    fn echoes_match_connection_topic_and_payload() {
+        let mut echoes = Echoes::default();
+        echoes.record("b", "a/1", b"on");
+        let msg = |topic: &str, payload: &[u8]| {
+            MqttMessage::new(topic.to_string(), payload.to_vec(), 0, false)
+        };
+        assert!(echoes.is_echo("b", &msg("a/1", b"on")));
+        assert!(!echoes.is_echo("a", &msg("a/1", b"on")));
+        assert!(!echoes.is_echo("b", &msg("a/2", b"on")));
+        assert!(!echoes.is_echo("b", &msg("a/1", b"off")));
+    }
+
+    #[test]
+    fn topic_matches_wildcards() {
+        assert!(topic_matches("a/b", "a/b"));
+        assert!(!topic_matches("a/b", "a/c"));
+        assert!(topic_matches("a/+/c", "a/b/c"));
+        assert!(!topic_matches("a/+", "a/b/c"));
+        assert!(topic_matches("a/+", "a/"));
+        assert!(topic_matches("a/#", "a"));
+        assert!(topic_matches("a/#", "a/b/c"));
+        assert!(topic_matches("#", "a/b"));
+        assert!(!topic_matches("a/b", "a/b/c"));
+        assert!(!topic_matches("a/b/c", "a/b"));
+    }
+
+    #[test]
+    fn topic_matches_dollar_topics() {
+        assert!(!topic_matches("#", "$SYS/uptime"));
+        assert!(!topic_matches("+/uptime", "$SYS/uptime"));
+        assert!(topic_matches("$SYS/#", "$SYS/uptime"));
+    }
+
+    #[test]
+    fn topic_matches_shared_subscriptions() {
+        assert!(topic_matches("$share/group/a/+", "a/b"));
+        assert!(!topic_matches("$share/group/a/+", "b/b"));
+    }
+
+    #[test]
+    fn filter_covers_topics_and_filters() {
+        assert!(filter_covers("a/#", "a/b"));
+        assert!(filter_covers("a/#", "a/+/c"));
+        assert!(filter_covers("a/#", "a/#"));
+        assert!(filter_covers("#", "a/#"));
+        assert!(filter_covers("a/+", "a/+"));
+        assert!(filter_covers("a/+/c", "a/b/c"));
+        assert!(filter_covers("a/b", "a/b"));
+    }
+
+    #[test]
+    fn filter_covers_rejects_wider_filters() {
+        // `a/b` would only match the literal topic `a/+`
+        assert!(!filter_covers("a/b", "a/+"));
+        assert!(!filter_covers("a/+", "a/#"));
+        assert!(!filter_covers("a/+", "a/b/c"));
+        assert!(!filter_covers("a/b/#", "a/#"));
+        assert!(!filter_covers("a/+/c", "a/+/#"));
+    }
+
+    #[test]
+    fn filter_covers_skips_shared_and_dollar() {
+        assert!(!filter_covers("$share/group/a/#", "a/b"));
+        assert!(!filter_covers("a/#", "$share/group/a/b"));
+        assert!(!filter_covers("#", "$SYS/#"));
+        assert!(filter_covers("$SYS/#", "$SYS/uptime"));
+    }
+}
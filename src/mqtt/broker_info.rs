@@ -0,0 +1,158 @@
+//! Broker statistics parsed from `$SYS` topics
+//!
+//! Mosquitto publishes under `$SYS/broker/`, EMQX under `$SYS/brokers/<node>/`.
+//! Brokers repeat these every few seconds, so counters are sampled into
+//! short histories for the dashboard charts.
+
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Utc};
+
+use super::MqttMessage;
+
+/// Samples kept per chart
+pub const MAX_SAMPLES: usize = 120;
+
+#[derive(Debug, Clone, Default)]
+pub struct BrokerInfo {
+    pub version: Option<String>,
+    pub uptime: Option<String>,
+    pub clients_connected: Gauge,
+    pub clients_total: Option<u64>,
+    pub clients_maximum: Option<u64>,
+    pub subscriptions: Option<u64>,
+    pub retained: Option<u64>,
+    pub messages_received: Counter,
+    pub messages_sent: Counter,
+    pub bytes_received: Counter,
+    pub bytes_sent: Counter,
+    /// When the last `$SYS` message arrived
+    pub updated: Option<DateTime<Utc>>,
+}
+
+/// A value reported as-is, with its recent history
+#[derive(Debug, Clone, Default)]
+pub struct Gauge {
+    pub value: Option<u64>,
+    pub history: VecDeque<f64>,
+}
+
+/// An ever-increasing total, charted as a per-second rate
+#[derive(Debug, Clone, Default)]
+pub struct Counter {
+    pub total: Option<u64>,
+    /// Per-second rates between consecutive samples
+    pub rates: VecDeque<f64>,
+    last: Option<(u64, DateTime<Utc>)>,
+}
+
+impl Gauge {
+    fn update(&mut self, value: u64) {
+        self.value = Some(value);
+        push_sample(&mut self.history, value as f64);
+    }
+}
+
+impl Counter {
+    fn update(&mut self, value: u64, at: DateTime<Utc>) {
+        self.total = Some(value);
+        if let Some((last, last_at)) = self.last {
+            let secs = (at - last_at).num_milliseconds() as f64 / 1000.0;
+            // A lower total means the broker restarted; start over from it
+            if secs > 0.0 && value >= last {
+                push_sample(&mut self.rates, (value - last) as f64 / secs);
+            }
+        }
+        self.last = Some((value, at));
+    }
+
+    /// Most recent rate
+    pub fn rate(&self) -> Option<f64> {
+        self.rates.back().copied()
+    }
+}
+
+impl BrokerInfo {
+    /// Apply a message if it is one of the known `$SYS` topics. Returns
+    /// `false` for anything else.
+    pub fn update(&mut self, message: &MqttMessage) -> bool {
+        let Some(key) = sys_key(&message.topic) else {
+            return false;
+        };
+        let text = message.payload_as_string();
+        let value = text.trim();
+        let number = parse_number(value);
+        let at = message.timestamp;
+
+        match (key, number) {
+            ("version", _) => self.version = Some(value.to_string()),
+            ("uptime", _) => self.uptime = Some(format_uptime(value)),
+            ("clients/connected" | "clients/active" | "stats/connections/count", Some(n)) => {
+                self.clients_connected.update(n)
+            }
+            ("clients/total", n) => self.clients_total = n,
+            ("clients/maximum" | "stats/connections/max", n) => self.clients_maximum = n,
+            ("subscriptions/count" | "stats/subscriptions/count", n) => self.subscriptions = n,
+            ("retained messages/count" | "stats/retained/count", n) => self.retained = n,
+            ("messages/received" | "metrics/messages/received", Some(n)) => {
+                self.messages_received.update(n, at)
+            }
+            ("messages/sent" | "metrics/messages/sent", Some(n)) => {
+                self.messages_sent.update(n, at)
+            }
+            ("bytes/received" | "metrics/bytes/received", Some(n)) => {
+                self.bytes_received.update(n, at)
+            }
+            ("bytes/sent" | "metrics/bytes/sent", Some(n)) => self.bytes_sent.update(n, at),
+            _ => return false,
+        }
+        self.updated = Some(at);
+        true
+    }
+
+    /// Whether any known `$SYS` topic has been seen
+    pub fn has_data(&self) -> bool {
+        self.updated.is_some()
+    }
+}
+
+/// Topic with the broker-specific `$SYS` prefix removed
+fn sys_key(topic: &str) -> Option<&str> {
+    if let Some(key) = topic.strip_prefix("$SYS/broker/") {
+        return Some(key);
+    }
+    let rest = topic.strip_prefix("$SYS/brokers/")?;
+    rest.split_once('/').map(|(_node, key)| key)
+}
+
+/// Leading number of values like "3600 seconds" or "12.0"
+fn parse_number(value: &str) -> Option<u64> {
+    let number = value.split_whitespace().next()?;
+    number
+        .parse::<u64>()
+        .ok()
+        .or_else(|| number.parse::<f64>().ok().map(|n| n as u64))
+}
+
+fn push_sample(samples: &mut VecDeque<f64>, value: f64) {
+    if samples.len() == MAX_SAMPLES {
+        samples.pop_front();
+    }
+    samples.push_back(value);
+}
+
+/// Uptime in seconds as e.g. "3d 4h 12m". Other formats are kept as sent.
+fn format_uptime(value: &str) -> String {
+    let secs = value.strip_suffix(" seconds").unwrap_or(value);
+    let Ok(secs) = secs.parse::<u64>() else {
+        return value.to_string();
+    };
+    let (days, hours, mins) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60);
+    if days > 0 {
+        format!("{}d {}h {}m", days, hours, mins)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, mins)
+    } else {
+        format!("{}m {}s", mins, secs % 60)
+    }
+}
@@ -0,0 +1,82 @@
+//! A local broker running inside mqttui, for trying things out without any
+//! infrastructure. rumqttd has no way to shut down, so once started the
+//! broker runs until mqttui exits.
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use rumqttd::{Broker, Config, ConnectionSettings, RouterConfig, ServerSettings};
+use tokio::sync::oneshot;
+
+use crate::config::EmbeddedBrokerSettings;
+
+/// Largest PUBLISH payload accepted
+const MAX_PAYLOAD_SIZE: usize = 1024 * 1024;
+
+/// How long rumqttd gets to bind its listener. It only logs why a listener
+/// failed, but the broker stops as soon as it has none left.
+const STARTUP_GRACE: Duration = Duration::from_millis(500);
+
+/// Start the broker in the background. Returns the address it listens on.
+pub async fn start(settings: EmbeddedBrokerSettings) -> Result<SocketAddr> {
+    let ip = if settings.listen_all {
+        Ipv4Addr::UNSPECIFIED
+    } else {
+        Ipv4Addr::LOCALHOST
+    };
+    let listen = SocketAddr::from((ip, settings.port));
+
+    let (stopped_tx, stopped_rx) = oneshot::channel();
+    let mut broker = Broker::new(config(&settings, listen));
+    thread::Builder::new()
+        .name("embedded-broker".to_string())
+        .spawn(move || {
+            let result = broker.start().map_err(|e| e.to_string());
+            if let Err(e) = &result {
+                tracing::error!("Embedded broker stopped: {}", e);
+            }
+            let _ = stopped_tx.send(result);
+        })
+        .context("Failed to start broker thread")?;
+
+    match tokio::time::timeout(STARTUP_GRACE, stopped_rx).await {
+        Err(_) => Ok(listen),
+        Ok(Ok(Err(e))) => Err(anyhow!(e)).with_context(|| format!("Can't listen on {}", listen)),
+        Ok(_) => Err(anyhow!("Can't listen on {}, is the port in use?", listen)),
+    }
+}
+
+fn config(settings: &EmbeddedBrokerSettings, listen: SocketAddr) -> Config {
+    let auth = settings
+        .username
+        .clone()
+        .map(|username| HashMap::from([(username, settings.password.clone().unwrap_or_default())]));
+    let server = ServerSettings {
+        name: "v4-1".to_string(),
+        listen,
+        tls: None,
+        next_connection_delay_ms: 1,
+        connections: ConnectionSettings {
+            connection_timeout_ms: 60000,
+            max_payload_size: MAX_PAYLOAD_SIZE,
+            max_inflight_count: 100,
+            auth,
+            external_auth: None,
+            dynamic_filters: true,
+        },
+    };
+    Config {
+        router: RouterConfig {
+            max_connections: 10010,
+            max_outgoing_packet_count: 200,
+            max_segment_size: 4 * MAX_PAYLOAD_SIZE,
+            max_segment_count: 10,
+            ..Default::default()
+        },
+        v4: Some(HashMap::from([("1".to_string(), server)])),
+        ..Default::default()
+    }
+}
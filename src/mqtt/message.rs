@@ -1,6 +1,33 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+/// PUBLISH properties carried by MQTT 5 messages
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default)]
+pub struct MessageProperties {
+    pub content_type: Option<String>,
+    pub response_topic: Option<String>,
+    pub correlation_data: Option<Vec<u8>>,
+    /// Seconds until the broker discards the message
+    pub message_expiry_interval: Option<u32>,
+    pub payload_format_indicator: Option<u8>,
+    pub user_properties: Vec<(String, String)>,
+    /// Identifiers of the subscriptions this message matched
+    pub subscription_identifiers: Vec<u32>,
+}
+
+impl MessageProperties {
+    /// Correlation data as text, or hex when it is not valid UTF-8
+    pub fn correlation_data_display(&self) -> Option<String> {
+        self.correlation_data
+            .as_ref()
+            .map(|data| match std::str::from_utf8(data) {
+                Ok(s) => s.to_string(),
+                Err(_) => data.iter().map(|b| format!("{:02x}", b)).collect(),
+            })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MqttMessage {
     pub topic: String,
@@ -8,6 +35,8 @@ pub struct MqttMessage {
     pub qos: u8,
     pub retain: bool,
     pub timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub properties: Option<MessageProperties>,
 }
 
 impl MqttMessage {
@@ -18,9 +47,34 @@ impl MqttMessage {
             qos,
             retain,
             timestamp: Utc::now(),
+            properties: None,
         }
     }
 
+    pub fn with_properties(mut self, properties: MessageProperties) -> Self {
+        self.properties = Some(properties);
+        self
+    }
+
+    /// Topic and payload bytes, as counted in traffic statistics
+    pub fn size(&self) -> usize {
+        self.topic.len() + self.payload.len()
+    }
+
+    /// Rough heap and inline size, used to budget buffered messages
+    pub fn approx_size(&self) -> usize {
+        let properties = self.properties.as_ref().map_or(0, |p| {
+            p.content_type.as_ref().map_or(0, String::len)
+                + p.response_topic.as_ref().map_or(0, String::len)
+                + p.correlation_data.as_ref().map_or(0, Vec::len)
+                + p.user_properties
+                    .iter()
+                    .map(|(k, v)| k.len() + v.len())
+                    .sum::<usize>()
+        });
+        std::mem::size_of::<Self>() + self.topic.len() + self.payload.len() + properties
+    }
+
     pub fn payload_as_string(&self) -> String {
         String::from_utf8_lossy(&self.payload).to_string()
     }
@@ -60,6 +114,11 @@ pub enum ConnectionStatus {
     Disconnected,
     Connecting,
     Connected,
+    /// Waiting to retry after the connection dropped
+    Reconnecting {
+        attempt: u32,
+        next_retry: DateTime<Utc>,
+    },
     Error(String),
 }
 
@@ -68,13 +127,32 @@ impl ConnectionStatus {
         matches!(self, ConnectionStatus::Connected)
     }
 
-    #[allow(dead_code)]
+    pub fn is_reconnecting(&self) -> bool {
+        matches!(self, ConnectionStatus::Reconnecting { .. })
+    }
+
     pub fn text(&self) -> &str {
         match self {
             ConnectionStatus::Disconnected => "Disconnected",
             ConnectionStatus::Connecting => "Connecting...",
             ConnectionStatus::Connected => "Connected",
+            ConnectionStatus::Reconnecting { .. } => "Reconnecting...",
             ConnectionStatus::Error(_) => "Error",
         }
     }
+
+    /// Retry progress while reconnecting
+    pub fn retry_text(&self) -> Option<String> {
+        match self {
+            ConnectionStatus::Reconnecting {
+                attempt,
+                next_retry,
+            } => Some(format!(
+                "Attempt {}, next retry at {}",
+                attempt,
+                next_retry.format("%H:%M:%S")
+            )),
+            _ => None,
+        }
+    }
 }
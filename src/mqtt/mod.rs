@@ -1,5 +1,15 @@
+pub mod bridge;
+pub mod broker_info;
+pub mod credentials;
+pub mod embedded_broker;
 pub mod message;
+pub mod snapshot;
+pub mod stats;
+pub mod tls;
 pub mod topic_tree;
 
+pub use broker_info::BrokerInfo;
 pub use message::*;
+pub use snapshot::RetainedSnapshot;
+pub use stats::{TrafficStats, WINDOWS};
 pub use topic_tree::*;
@@ -0,0 +1,125 @@
+//! Retained message snapshots, for backing up a broker's retained state and
+//! publishing it again later
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
+use super::message::{MessageProperties, MqttMessage};
+
+/// Snapshot format written by this version
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetainedSnapshot {
+    pub version: u32,
+    pub taken_at: DateTime<Utc>,
+    /// Broker the snapshot was taken from, as host:port
+    pub broker: String,
+    /// Filter subscribed to while collecting
+    pub filter: String,
+    pub messages: Vec<SnapshotMessage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotMessage {
+    pub topic: String,
+    /// Payload bytes, base64 encoded in the file
+    #[serde(with = "base64_payload")]
+    pub payload: Vec<u8>,
+    pub qos: u8,
+    /// MQTT 5 PUBLISH properties, if the broker sent any
+    #[serde(default)]
+    pub properties: Option<MessageProperties>,
+}
+
+impl From<MqttMessage> for SnapshotMessage {
+    fn from(msg: MqttMessage) -> Self {
+        // Subscription identifiers belong to the collecting session
+        let properties = msg.properties.map(|p| MessageProperties {
+            subscription_identifiers: Vec::new(),
+            ..p
+        });
+        Self {
+            topic: msg.topic,
+            payload: msg.payload,
+            qos: msg.qos,
+            properties,
+        }
+    }
+}
+
+impl RetainedSnapshot {
+    pub fn new(broker: String, filter: String, mut messages: Vec<SnapshotMessage>) -> Self {
+        messages.sort_by(|a, b| a.topic.cmp(&b.topic));
+        Self {
+            version: SNAPSHOT_VERSION,
+            taken_at: Utc::now(),
+            broker,
+            filter,
+            messages,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read snapshot from {:?}", path))?;
+        let snapshot: RetainedSnapshot =
+            serde_json::from_str(&content).with_context(|| "Failed to parse snapshot JSON")?;
+        if snapshot.version > SNAPSHOT_VERSION {
+            bail!(
+                "Snapshot version {} is newer than this version of mqttui supports ({})",
+                snapshot.version,
+                SNAPSHOT_VERSION
+            );
+        }
+        Ok(snapshot)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create snapshot directory {:?}", parent))?;
+        }
+        let content =
+            serde_json::to_string_pretty(self).with_context(|| "Failed to serialize snapshot")?;
+        fs::write(path, content)
+            .with_context(|| format!("Failed to write snapshot to {:?}", path))?;
+        Ok(())
+    }
+
+    /// `<data dir>/snapshots/<name>-<time>.json`
+    pub fn default_path(name: &str) -> Result<PathBuf> {
+        let proj_dirs = ProjectDirs::from("com", "mqttui", "mqttui")
+            .context("Failed to determine data directory")?;
+        let name: String = name
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '-' })
+            .collect();
+        let file = format!("{}-{}.json", name, Utc::now().format("%Y%m%d-%H%M%S"));
+        Ok(proj_dirs.data_dir().join("snapshots").join(file))
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.messages.iter().map(|m| m.payload.len()).sum()
+    }
+}
+
+mod base64_payload {
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(payload: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&BASE64.encode(payload))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        BASE64.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
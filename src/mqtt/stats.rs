@@ -0,0 +1,102 @@
+//! Rolling message and byte rates
+
+use std::collections::VecDeque;
+
+/// Averaging windows shown in the UI, in seconds
+pub const WINDOWS: [i64; 3] = [1, 10, 60];
+
+/// Longest window kept, in seconds
+const HISTORY_SECS: i64 = 60;
+
+/// Traffic in one-second buckets over the last minute, plus running totals
+#[derive(Debug, Clone, Default)]
+pub struct TrafficStats {
+    /// (unix second, messages, bytes), oldest first. Quiet seconds are skipped.
+    buckets: VecDeque<(i64, u32, u64)>,
+    pub total_messages: u64,
+    pub total_bytes: u64,
+}
+
+/// Average throughput over one window
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rate {
+    pub messages: f64,
+    pub bytes: f64,
+}
+
+impl TrafficStats {
+    /// Count a message of `bytes` received during `second`
+    pub fn record(&mut self, second: i64, bytes: usize) {
+        let bytes = bytes as u64;
+        self.total_messages += 1;
+        self.total_bytes += bytes;
+
+        match self.buckets.back_mut() {
+            // Late timestamps are folded into the newest bucket
+            Some(bucket) if bucket.0 >= second => {
+                bucket.1 += 1;
+                bucket.2 += bytes;
+            }
+            _ => self.buckets.push_back((second, 1, bytes)),
+        }
+        while self
+            .buckets
+            .front()
+            .is_some_and(|bucket| bucket.0 <= second - HISTORY_SECS)
+        {
+            self.buckets.pop_front();
+        }
+    }
+
+    /// Average over the `secs` whole seconds before `now`
+    pub fn rate(&self, secs: i64, now: i64) -> Rate {
+        let (messages, bytes) = self
+            .buckets
+            .iter()
+            .filter(|bucket| bucket.0 >= now - secs && bucket.0 < now)
+            .fold((0u64, 0u64), |(m, b), bucket| {
+                (m + bucket.1 as u64, b + bucket.2)
+            });
+        Rate {
+            messages: messages as f64 / secs as f64,
+            bytes: bytes as f64 / secs as f64,
+        }
+    }
+
+    /// Rates for each of `WINDOWS`
+    pub fn rates(&self, now: i64) -> [Rate; 3] {
+        WINDOWS.map(|secs| self.rate(secs, now))
+    }
+
+    /// Whether any window still covers recent traffic, so rates keep changing
+    pub fn is_active(&self, now: i64) -> bool {
+        self.buckets
+            .back()
+            .is_some_and(|bucket| bucket.0 >= now - HISTORY_SECS)
+    }
+}
+
+/// Human-readable byte count, e.g. "1.5 KB"
+pub fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{:.0} {}", value, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Messages per second, with a decimal only for slow rates
+pub fn format_rate(rate: f64) -> String {
+    if rate < 10.0 {
+        format!("{:.1}", rate)
+    } else {
+        format!("{:.0}", rate)
+    }
+}
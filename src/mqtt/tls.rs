@@ -0,0 +1,143 @@
+//! TLS client configuration for broker connections
+
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use rumqttc::tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use rumqttc::tokio_rustls::rustls::crypto::{self, WebPkiSupportedAlgorithms};
+use rumqttc::tokio_rustls::rustls::pki_types::{
+    CertificateDer, PrivateKeyDer, ServerName, UnixTime,
+};
+use rumqttc::tokio_rustls::rustls::{
+    ClientConfig, DigitallySignedStruct, Error as TlsError, RootCertStore, SignatureScheme,
+};
+
+use crate::config::TlsSettings;
+
+/// Build a rustls client config from a connection's TLS settings
+pub fn client_config(settings: &TlsSettings) -> Result<Arc<ClientConfig>> {
+    // The override is used as the host to connect to, not in the config
+    if let Some(name) = &settings.server_name {
+        ServerName::try_from(name.as_str())
+            .with_context(|| format!("Invalid TLS server name {:?}", name))?;
+    }
+
+    let builder = ClientConfig::builder();
+
+    let builder = if settings.insecure {
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(InsecureVerifier::new()))
+    } else {
+        builder.with_root_certificates(root_store(settings)?)
+    };
+
+    let config = match (&settings.client_cert_file, &settings.client_key_file) {
+        (Some(cert_path), Some(key_path)) => builder
+            .with_client_auth_cert(load_certs(cert_path)?, load_key(key_path)?)
+            .context("Invalid client certificate or key")?,
+        (None, None) => builder.with_no_client_auth(),
+        _ => bail!("Client certificate and key must be set together"),
+    };
+
+    Ok(Arc::new(config))
+}
+
+fn root_store(settings: &TlsSettings) -> Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+
+    if settings.use_system_roots {
+        match rustls_native_certs::load_native_certs() {
+            Ok(certs) => {
+                roots.add_parsable_certificates(certs);
+            }
+            Err(e) => tracing::warn!("Failed to load system root certificates: {}", e),
+        }
+    }
+
+    if let Some(path) = &settings.ca_file {
+        let (added, _) = roots.add_parsable_certificates(load_certs(path)?);
+        if added == 0 {
+            bail!("No valid CA certificates in {}", path);
+        }
+    }
+
+    if roots.is_empty() {
+        bail!("No trusted CA certificates: enable system roots or set a CA file");
+    }
+
+    Ok(roots)
+}
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to parse certificates in {}", path))?;
+    if certs.is_empty() {
+        bail!("No certificates found in {}", path);
+    }
+    Ok(certs)
+}
+
+/// First private key in a PEM file
+pub fn load_key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .with_context(|| format!("Failed to parse private key in {}", path))?
+        .with_context(|| format!("No private key found in {}", path))
+}
+
+/// Accepts any certificate. Handshake signatures are still checked so the
+/// session keys belong to whoever presented the certificate.
+#[derive(Debug)]
+struct InsecureVerifier {
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl InsecureVerifier {
+    fn new() -> Self {
+        Self {
+            algorithms: crypto::ring::default_provider().signature_verification_algorithms,
+        }
+    }
+}
+
+impl ServerCertVerifier for InsecureVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, TlsError> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, TlsError> {
+        crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, TlsError> {
+        crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
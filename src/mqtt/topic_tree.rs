@@ -1,6 +1,11 @@
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
 use super::message::MqttMessage;
+use super::stats::TrafficStats;
+
+/// How long an empty publish on a cleared topic counts as the broker's echo
+const CLEARED_TTL: Duration = Duration::from_secs(10);
 
 #[derive(Debug, Clone, Default)]
 pub struct TopicNode {
@@ -10,6 +15,8 @@ pub struct TopicNode {
     pub children: HashMap<String, TopicNode>,
     pub messages: Vec<MqttMessage>,
     pub message_count: usize,
+    /// Traffic for this node and everything below it
+    pub stats: TrafficStats,
     pub expanded: bool,
 }
 
@@ -21,12 +28,15 @@ impl TopicNode {
             children: HashMap::new(),
             messages: Vec::new(),
             message_count: 0,
+            stats: TrafficStats::default(),
             expanded: false,
         }
     }
 
     pub fn insert_message(&mut self, topic_parts: &[&str], message: MqttMessage) {
         self.message_count += 1;
+        self.stats
+            .record(message.timestamp.timestamp(), message.size());
 
         if topic_parts.is_empty() {
             self.messages.push(message);
@@ -58,6 +68,43 @@ impl TopicNode {
         self.messages.last()
     }
 
+    /// Whether the broker holds a retained message for this topic, as far as
+    /// this session has seen
+    pub fn has_retained(&self) -> bool {
+        !self.full_path.starts_with('$')
+            && self
+                .messages
+                .iter()
+                .rev()
+                .find(|m| m.retain)
+                .is_some_and(|m| !m.payload.is_empty())
+    }
+
+    /// Topics with a retained message at or below this node
+    pub fn retained_topics(&self) -> Vec<String> {
+        self.retained_messages()
+            .into_iter()
+            .map(|m| m.topic.clone())
+            .collect()
+    }
+
+    /// Latest retained message of each topic at or below this node, by topic
+    pub fn retained_messages(&self) -> Vec<&MqttMessage> {
+        let mut messages = Vec::new();
+        self.collect_retained(&mut messages);
+        messages.sort_by(|a, b| a.topic.cmp(&b.topic));
+        messages
+    }
+
+    fn collect_retained<'a>(&'a self, messages: &mut Vec<&'a MqttMessage>) {
+        if self.has_retained() {
+            messages.extend(self.messages.iter().rev().find(|m| m.retain));
+        }
+        for child in self.children.values() {
+            child.collect_retained(messages);
+        }
+    }
+
     #[allow(dead_code)]
     pub fn total_children_count(&self) -> usize {
         let mut count = self.children.len();
@@ -80,6 +127,10 @@ pub struct TopicTree {
     pub root: TopicNode,
     pub total_messages: usize,
     pub total_topics: usize,
+    /// Topics whose retained message we deleted; the empty publish echoed
+    /// back by the broker is dropped instead of recreating the node. Entries
+    /// expire, so a later real empty publish still shows up.
+    cleared: HashMap<String, Instant>,
 }
 
 impl TopicTree {
@@ -88,10 +139,17 @@ impl TopicTree {
             root: TopicNode::new("", ""),
             total_messages: 0,
             total_topics: 0,
+            cleared: HashMap::new(),
         }
     }
 
     pub fn insert(&mut self, message: MqttMessage) {
+        if message.payload.is_empty() {
+            let cleared = self.cleared.remove(&message.topic);
+            if cleared.is_some_and(|at| at.elapsed() < CLEARED_TTL) {
+                return;
+            }
+        }
         let topic = message.topic.clone();
         let parts: Vec<&str> = topic.split('/').collect();
         self.root.insert_message(&parts, message);
@@ -99,6 +157,35 @@ impl TopicTree {
         self.recalculate_topic_count();
     }
 
+    /// Drop the nodes of topics whose retained message was cleared. Nodes
+    /// that still have children only lose their messages.
+    pub fn remove_topics(&mut self, topics: &[String]) {
+        self.cleared.retain(|_, at| at.elapsed() < CLEARED_TTL);
+        for topic in topics {
+            let parts: Vec<&str> = topic.split('/').collect();
+            Self::remove_recursive(&mut self.root, &parts);
+            self.cleared.insert(topic.clone(), Instant::now());
+        }
+        self.recalculate_topic_count();
+    }
+
+    /// Returns `true` if `node` is left empty and can be removed
+    fn remove_recursive(node: &mut TopicNode, parts: &[&str]) -> bool {
+        match parts.split_first() {
+            None => node.messages.clear(),
+            Some((part, rest)) => {
+                let empty = node
+                    .children
+                    .get_mut(*part)
+                    .is_some_and(|child| Self::remove_recursive(child, rest));
+                if empty {
+                    node.children.remove(*part);
+                }
+            }
+        }
+        node.messages.is_empty() && node.children.is_empty()
+    }
+
     fn recalculate_topic_count(&mut self) {
         self.total_topics = Self::count_topics(&self.root);
     }
@@ -116,6 +203,7 @@ impl TopicTree {
         self.root = TopicNode::new("", "");
         self.total_messages = 0;
         self.total_topics = 0;
+        self.cleared.clear();
     }
 
     #[allow(dead_code)]
@@ -90,6 +90,7 @@ pub mod icons {
     pub const SEND: &str = "\u{f1d8}"; //
     pub const TOPIC: &str = "\u{f07c}"; //
     pub const MESSAGE: &str = "\u{f075}"; //
+    pub const SERVER: &str = "\u{f233}"; //
 
     // Tree
     pub const CHEVRON_RIGHT: &str = "\u{f054}"; //
@@ -97,6 +98,11 @@ pub mod icons {
 
     // Actions
     pub const TRASH: &str = "\u{f1f8}"; //
+    pub const SAVE: &str = "\u{f0c7}"; //
+    pub const ERASER: &str = "\u{f12d}"; //
+    pub const COPY: &str = "\u{f0c5}"; //
+    pub const PLAY: &str = "\u{f04b}"; //
+    pub const PAUSE: &str = "\u{f04c}"; //
 }
 
 // =============================================================================